@@ -0,0 +1,110 @@
+// Bullet hell stress test: a ring of bullets is fired from the center of
+// the arena every frame and leaves it after about a second, which keeps a
+// few thousand bullets alive. Measures one frame with and without the
+// entity pools.
+//
+// cargo bench --bench pooling
+
+use std::f32::consts::{FRAC_PI_2, PI};
+
+use amethyst::{
+    core::{
+        math::Vector2,
+        transform::components::Transform,
+        timing::Time,
+    },
+    ecs::{System, SystemData,
+          ReadExpect, Write,
+          World, WorldExt},
+    shred::{Dispatcher, DispatcherBuilder},
+};
+use criterion::{criterion_group, criterion_main, Criterion};
+
+use rocket::components::Physical;
+use rocket::level::Level;
+use rocket::pool::{EntityPools, PoolConfig, PoolSystem, Spawn};
+use rocket::resources::{Arena, ArenaConfig};
+use rocket::system::{BoundarySystem, PhysicalSystem};
+
+const BULLETS_PER_FRAME: usize = 64;
+const BULLET_SPEED: f32 = 200.0;
+// frames until as many bullets leave the arena as are fired
+const WARMUP_FRAMES: usize = 120;
+
+struct BulletHellSystem {
+    angle: f32,
+}
+
+impl<'s> System<'s> for BulletHellSystem {
+    type SystemData = (
+        ReadExpect<'s, Arena>,
+        Write<'s, EntityPools>,
+    );
+
+    fn run(&mut self, (arena, mut pools): Self::SystemData) {
+        for i in 0..BULLETS_PER_FRAME {
+            let angle = self.angle + i as f32 * 2.0 * PI / BULLETS_PER_FRAME as f32;
+            let mut transform = Transform::default();
+            transform.set_translation_xyz(arena.width * 0.5, arena.height * 0.5, 0.0);
+            transform.set_rotation_2d(angle - FRAC_PI_2);
+            pools.spawn(Spawn::Bullet {
+                transform,
+                physical: Physical {
+                    velocity: Vector2::new(angle.cos(), angle.sin()) * BULLET_SPEED,
+                    max_velocity: BULLET_SPEED,
+                    rotation: 0.0,
+                },
+            });
+        }
+        // the rings turn a little so that no two bullets share a path
+        self.angle += 0.1;
+    }
+}
+
+fn frame(world: &mut World, dispatcher: &mut Dispatcher<'static, 'static>) {
+    dispatcher.dispatch(world);
+    world.maintain();
+}
+
+fn bullet_hell(pooling: bool) -> (World, Dispatcher<'static, 'static>) {
+    let mut world = World::new();
+    let config = ArenaConfig::default();
+    world.insert(Arena::from_config(&config));
+    world.insert(Level::endless(&config));
+    world.insert(PoolConfig {
+        enabled: pooling,
+        ..Default::default()
+    });
+    let mut time = Time::default();
+    time.set_delta_seconds(1.0 / 60.0);
+    world.insert(time);
+
+    let mut dispatcher = DispatcherBuilder::new()
+        .with(BulletHellSystem { angle: 0.0 }, "bullet_hell_system", &[])
+        .with(PhysicalSystem, "physical_system", &["bullet_hell_system"])
+        .with(BoundarySystem, "boundary_system", &["physical_system"])
+        .with(PoolSystem::default(), "pool_system", &["boundary_system"])
+        .build();
+    dispatcher.setup(&mut world);
+
+    for _ in 0..WARMUP_FRAMES {
+        frame(&mut world, &mut dispatcher);
+    }
+    (world, dispatcher)
+}
+
+fn pooling(c: &mut Criterion) {
+    let mut group = c.benchmark_group("bullet_hell");
+    for &(name, pooling) in &[("pooled", true), ("unpooled", false)] {
+        let (mut world, mut dispatcher) = bullet_hell(pooling);
+        group.bench_function(name, |b| b.iter(|| frame(&mut world, &mut dispatcher)));
+
+        let metrics = world.read_resource::<EntityPools>().total();
+        println!("{}: pool hits {} misses {} ({:.1}% reused)",
+                 name, metrics.hits, metrics.misses, metrics.hit_rate() * 100.0);
+    }
+    group.finish();
+}
+
+criterion_group!(benches, pooling);
+criterion_main!(benches);
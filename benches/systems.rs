@@ -0,0 +1,253 @@
+// The gameplay systems on worlds of different sizes, each system on its
+// own and all of them as one frame of StatePlay.
+//
+// cargo bench --bench systems
+//
+// The worlds are set with BENCH_WORLDS as ships:bullets:asteroids, several
+// separated by commas, e.g. BENCH_WORLDS=1:100:50,8:4000:1000. Criterion
+// keeps the results in target/criterion; collect them as JSON lines with
+//
+// cargo run --bin bench_report -- <label>
+
+use std::{
+    env,
+    f32::consts::PI,
+    fmt,
+    time::{Duration, Instant},
+};
+
+use amethyst::{
+    core::{
+        math::Vector2,
+        transform::components::Transform,
+        timing::Time,
+        Hidden,
+    },
+    ecs::{Join,
+          ReadStorage,
+          RunNow, System,
+          Entities,
+          World, WorldExt},
+    ecs::prelude::Builder,
+    renderer::SpriteRender,
+    shrev::EventChannel,
+};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use rocket::animation::Animation;
+use rocket::components::{Physical, Ship, Bullet, Asteroid, AsteroidSize, Collider, ColliderType,
+                         ColliderShape, ShipCommand, AutoPilot};
+use rocket::hazards::Hazard;
+use rocket::level::{Level, Obstacle};
+use rocket::particles::Emitter;
+use rocket::physics::{AsteroidPhysicsConfig, ShipPhysicsConfig};
+use rocket::pool::{EntityPools, Pooled};
+use rocket::resources::{Arena, ArenaConfig, RandomGen};
+use rocket::scoring::{Scoring, ScoringConfig};
+use rocket::settings::Difficulty;
+use rocket::states::play_dispatcher;
+use rocket::stats::SessionStats;
+use rocket::system::{asteroid_components, BoundarySystem, CollisionEvent, CollisionSystem,
+                     DeletionSystem, PhysicalSystem};
+use rocket::vector::Outline;
+use rocket::weapons::{Cannon, Weapons};
+
+const DEFAULT_WORLDS: &str = "1:50:50,4:500:200,16:2000:800";
+
+#[derive(Clone, Copy)]
+struct WorldSize {
+    ships: usize,
+    bullets: usize,
+    asteroids: usize,
+}
+
+impl fmt::Display for WorldSize {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}s_{}b_{}a", self.ships, self.bullets, self.asteroids)
+    }
+}
+
+fn world_sizes() -> Vec<WorldSize> {
+    let worlds = env::var("BENCH_WORLDS").unwrap_or_else(|_| DEFAULT_WORLDS.to_string());
+    worlds.split(',')
+        .map(|world| {
+            let counts: Vec<usize> = world.split(':')
+                .map(|count| count.trim().parse()
+                     .unwrap_or_else(|_| panic!("BENCH_WORLDS: {} is not a count", count)))
+                .collect();
+            match counts[..] {
+                [ships, bullets, asteroids] => WorldSize { ships, bullets, asteroids },
+                _ => panic!("BENCH_WORLDS: {} is not ships:bullets:asteroids", world),
+            }
+        })
+        .collect()
+}
+
+// the same world for every run, placed by a fixed seed
+fn build_world(size: WorldSize) -> World {
+    let mut world = World::new();
+    world.register::<Transform>();
+    world.register::<Hidden>();
+    world.register::<SpriteRender>();
+    world.register::<Physical>();
+    world.register::<Ship>();
+    world.register::<Bullet>();
+    world.register::<Asteroid>();
+    world.register::<Collider>();
+    world.register::<ShipCommand>();
+    world.register::<AutoPilot>();
+    world.register::<Emitter>();
+    world.register::<Animation>();
+    world.register::<Outline>();
+    world.register::<Hazard>();
+    world.register::<Obstacle>();
+    world.register::<Pooled>();
+    world.register::<Weapons>();
+
+    let config = ArenaConfig::default();
+    let arena = Arena::from_config(&config);
+    let scoring_config = ScoringConfig::default();
+    let mut scoring = Scoring::new(&scoring_config);
+    // the ships are hit all the time, the game must not end
+    scoring.lives = 1_000_000;
+    world.insert(arena);
+    world.insert(Level::endless(&config));
+    world.insert(scoring);
+    world.insert(scoring_config);
+    world.insert(SessionStats::default());
+    world.insert(ShipPhysicsConfig::default());
+    world.insert(AsteroidPhysicsConfig::default());
+    world.insert(RandomGen);
+    world.insert(EntityPools::default());
+    world.insert(EventChannel::<CollisionEvent>::new());
+    let mut time = Time::default();
+    time.set_delta_seconds(1.0 / 60.0);
+    world.insert(time);
+
+    let mut rng = StdRng::seed_from_u64(42);
+    let point = |rng: &mut StdRng| {
+        Vector2::new(rng.gen::<f32>() * arena.width, rng.gen::<f32>() * arena.height)
+    };
+    let position = |rng: &mut StdRng| {
+        let p = point(rng);
+        let mut transform = Transform::default();
+        transform.set_translation_xyz(p.x, p.y, 0.0);
+        transform.set_rotation_2d(rng.gen::<f32>() * 2.0 * PI);
+        transform
+    };
+    let velocity = |rng: &mut StdRng, speed: f32| {
+        let angle = rng.gen::<f32>() * 2.0 * PI;
+        Vector2::new(angle.cos(), angle.sin()) * speed
+    };
+
+    for _ in 0..size.ships {
+        let transform = position(&mut rng);
+        world.create_entity()
+            .with(transform)
+            .with(Ship::new())
+            .with(ShipCommand::default())
+            .with(AutoPilot::new())
+            .with(Physical { velocity: velocity(&mut rng, 20.0), max_velocity: 80.0, rotation: 0.0 })
+            .with(Collider { typ: ColliderType::Ship, shape: ColliderShape::Circle(5.0) })
+            .with(Outline::ship())
+            .with(Weapons::new(vec![Box::new(Cannon::new())]))
+            .build();
+    }
+    for _ in 0..size.bullets {
+        let transform = position(&mut rng);
+        world.create_entity()
+            .with(transform)
+            .with(Bullet { ricochets: 0 })
+            .with(Physical { velocity: velocity(&mut rng, 150.0), max_velocity: 200.0, rotation: 0.0 })
+            .with(Collider { typ: ColliderType::Bullet, shape: ColliderShape::Circle(2.0) })
+            .with(Outline::bullet())
+            .build();
+    }
+    for _ in 0..size.asteroids {
+        let asteroid_size = AsteroidSize::ALL[rng.gen_range(0, 3)];
+        let (asteroid, transform, physical, collider, outline, _) = asteroid_components(
+            None, asteroid_size, point(&mut rng), velocity(&mut rng, 30.0),
+            rng.gen::<f32>() - 0.5, rng.gen());
+        world.create_entity()
+            .with(asteroid)
+            .with(transform)
+            .with(physical)
+            .with(collider)
+            .with(outline)
+            .build();
+    }
+    world
+}
+
+// run one system again and again on the same world, `prepare` runs before
+// every run and is not measured
+fn bench_system<S, F, P>(c: &mut Criterion, name: &str, mut make: F, prepare: P)
+    where S: for<'a> System<'a>,
+          F: FnMut() -> S,
+          P: Fn(&mut World) {
+    let mut group = c.benchmark_group(name);
+    for size in world_sizes() {
+        let mut world = build_world(size);
+        let mut system = make();
+        System::setup(&mut system, &mut world);
+        group.bench_with_input(BenchmarkId::from_parameter(size), &size, |b, _| {
+            b.iter_custom(|iterations| {
+                let mut total = Duration::default();
+                for _ in 0..iterations {
+                    prepare(&mut world);
+                    let start = Instant::now();
+                    system.run_now(&world);
+                    total += start.elapsed();
+                    // drop the queued spawns and releases, the world stays as built
+                    world.insert(EntityPools::default());
+                }
+                total
+            })
+        });
+    }
+    group.finish();
+}
+
+fn no_preparation(_: &mut World) {}
+
+// every bullet hit something this frame
+fn bullet_hits(world: &mut World) {
+    let bullets: Vec<_> = world.exec(|(entities, bullets): (Entities, ReadStorage<Bullet>)| {
+        (&entities, &bullets).join().map(|(e, _)| e).collect()
+    });
+    let mut channel = world.write_resource::<EventChannel<CollisionEvent>>();
+    for e in bullets {
+        channel.single_write(CollisionEvent::new(e));
+    }
+}
+
+fn systems(c: &mut Criterion) {
+    bench_system(c, "physical_system", || PhysicalSystem, no_preparation);
+    bench_system(c, "boundary_system", || BoundarySystem, no_preparation);
+    bench_system(c, "collision_system", CollisionSystem::default, no_preparation);
+    bench_system(c, "collision_system_serial", CollisionSystem::serial, no_preparation);
+    bench_system(c, "deletion_system", DeletionSystem::default, bullet_hits);
+}
+
+// a whole frame of StatePlay, the world changes from frame to frame like
+// in a game
+fn full_frame(c: &mut Criterion) {
+    let mut group = c.benchmark_group("play_frame");
+    for size in world_sizes() {
+        let mut world = build_world(size);
+        let level = world.read_resource::<Level>().clone();
+        let mut dispatcher = play_dispatcher(&level, Difficulty::Normal).build();
+        dispatcher.setup(&mut world);
+        group.bench_with_input(BenchmarkId::from_parameter(size), &size, |b, _| {
+            b.iter(|| {
+                dispatcher.dispatch(&world);
+                world.maintain();
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, systems, full_frame);
+criterion_main!(benches);
@@ -0,0 +1,348 @@
+use std::{
+    collections::HashMap,
+    fs,
+    sync::Arc,
+};
+
+use amethyst::{
+    core::timing::Time,
+    derive::{SystemDesc},
+    ecs::{Join,
+          WriteStorage,
+          System, SystemData,
+          Read, Write,
+          Entities},
+    ecs::prelude::{Component, DenseVecStorage, Entity},
+    renderer::{SpriteRender},
+    shrev::{EventChannel},
+    utils::application_root_dir,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::pool::{EntityPools};
+use crate::textures::{SpriteRegistry};
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum AnimationMode {
+    // start over after the last frame
+    Loop,
+    // stop at the last frame
+    Once,
+    // play forward then backward, forever
+    PingPong,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum FinishAction {
+    Nothing,
+    // release the animated entity, pooled ones are parked for reuse
+    Despawn,
+    // write an AnimationEvent
+    Notify,
+}
+
+// an animation as written in assets/texture/<sheet>.anim.ron
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AnimationDef {
+    // the frames are the sprites <sprite>.<first> to <sprite>.<last>
+    pub sprite: String,
+    // first and last frame, inclusive
+    pub frames: (usize, usize),
+    // duration (s) of each frame, the last one is used for the remaining frames
+    pub durations: Vec<f32>,
+    pub mode: AnimationMode,
+    pub on_finish: FinishAction,
+    // atlas index of each frame, resolved at load
+    #[serde(skip)]
+    pub sprites: Vec<usize>,
+}
+
+impl AnimationDef {
+    pub fn frame_count(&self) -> usize {
+        self.frames.1 + 1 - self.frames.0
+    }
+
+    pub fn duration(&self, frame: usize) -> f32 {
+        self.durations.get(frame)
+            .or_else(|| self.durations.last())
+            .cloned()
+            .unwrap_or(0.1)
+    }
+
+    // look up the atlas index of every frame
+    fn resolve(&mut self, registry: &SpriteRegistry) -> Result<(), String> {
+        self.sprites = (self.frames.0..=self.frames.1).map(|i| {
+            let name = format!("{}.{}", self.sprite, i);
+            registry.index(&name).ok_or_else(|| format!("unknown sprite {}", name))
+        }).collect::<Result<_, _>>()?;
+        Ok(())
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.frames.1 < self.frames.0 {
+            return Err(format!("last frame {} before first frame {}", self.frames.1, self.frames.0));
+        }
+        if self.durations.iter().any(|&d| d <= 0.0) {
+            return Err("frame durations must be positive".to_string());
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub struct AnimationEvent {
+    pub entity: Entity,
+}
+
+pub struct Animation {
+    pub def: Arc<AnimationDef>,
+    // index of the current frame inside the animation
+    frame: usize,
+    // time spent in the current frame
+    elapsed: f32,
+    forward: bool,
+    finished: bool,
+}
+
+impl Animation {
+    pub fn new(def: Arc<AnimationDef>) -> Self {
+        Self {
+            def,
+            frame: 0,
+            elapsed: 0.0,
+            forward: true,
+            finished: false,
+        }
+    }
+
+    pub fn sprite_number(&self) -> usize {
+        self.def.sprites[self.frame]
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    // advance the animation by delta seconds, returns true when it just finished
+    pub fn advance(&mut self, delta: f32) -> bool {
+        if self.finished {
+            return false;
+        }
+
+        let last = self.def.frame_count() - 1;
+        self.elapsed += delta;
+        while self.elapsed >= self.def.duration(self.frame) {
+            self.elapsed -= self.def.duration(self.frame);
+            match self.def.mode {
+                AnimationMode::Loop => {
+                    self.frame = if self.frame == last { 0 } else { self.frame + 1 };
+                },
+                AnimationMode::Once => {
+                    if self.frame == last {
+                        self.finished = true;
+                        return true;
+                    }
+                    self.frame += 1;
+                },
+                AnimationMode::PingPong => {
+                    if last == 0 {
+                        continue;
+                    }
+                    if self.forward && self.frame == last {
+                        self.forward = false;
+                    } else if !self.forward && self.frame == 0 {
+                        self.forward = true;
+                    }
+                    self.frame = if self.forward { self.frame + 1 } else { self.frame - 1 };
+                },
+            }
+        }
+        false
+    }
+}
+
+impl Component for Animation {
+    type Storage = DenseVecStorage<Self>;
+}
+
+// all animation definitions, keyed by "<sheet>.<name>", e.g. "explosion.explode"
+#[derive(Default)]
+pub struct Animations {
+    defs: HashMap<String, Arc<AnimationDef>>,
+}
+
+impl Animations {
+    // load assets/texture/<sheet>.anim.ron
+    pub fn load(&mut self, sheet: &str, registry: &SpriteRegistry) -> Result<(), String> {
+        let path = application_root_dir()
+            .map_err(|e| e.to_string())?
+            .join("assets")
+            .join("texture")
+            .join(format!("{}.anim.ron", sheet));
+        let content = fs::read_to_string(&path)
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+        let defs: HashMap<String, AnimationDef> = ron::de::from_str(&content)
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+        for (name, mut def) in defs {
+            def.validate()
+                .and_then(|_| def.resolve(registry))
+                .map_err(|e| format!("{}: {}: {}", path.display(), name, e))?;
+            self.defs.insert(format!("{}.{}", sheet, name), Arc::new(def));
+        }
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Option<Animation> {
+        self.defs.get(name).map(|def| Animation::new(def.clone()))
+    }
+}
+
+#[derive(SystemDesc)]
+pub struct AnimationSystem;
+
+impl<'s> System<'s> for AnimationSystem {
+    type SystemData = (
+        WriteStorage<'s, Animation>,
+        WriteStorage<'s, SpriteRender>,
+        Write<'s, EventChannel<AnimationEvent>>,
+        Write<'s, EntityPools>,
+        Entities<'s>,
+        Read<'s, Time>,
+    );
+
+    fn run(&mut self,
+           (mut animations,
+            mut spriterenders,
+            mut animation_events,
+            mut pools,
+            entities,
+            time): Self::SystemData) {
+        let delta = time.delta_seconds();
+
+        for (e, animation, spriterender) in (&*entities, &mut animations, &mut spriterenders).join() {
+            let finished = animation.advance(delta);
+            spriterender.sprite_number = animation.sprite_number();
+            if !finished {
+                continue;
+            }
+
+            match animation.def.on_finish {
+                FinishAction::Nothing => {},
+                FinishAction::Despawn => pools.release(e),
+                FinishAction::Notify => {
+                    animation_events.single_write(AnimationEvent { entity: e });
+                },
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn animation(frames: usize, durations: &[f32], mode: AnimationMode) -> Animation {
+        Animation::new(Arc::new(AnimationDef {
+            sprite: "test".to_string(),
+            frames: (0, frames - 1),
+            durations: durations.to_vec(),
+            mode,
+            on_finish: FinishAction::Nothing,
+            sprites: (10..10 + frames).collect(),
+        }))
+    }
+
+    // the frame after each step of `delta`
+    fn frames(animation: &mut Animation, delta: f32, steps: usize) -> Vec<usize> {
+        (0..steps).map(|_| {
+            animation.advance(delta);
+            animation.frame
+        }).collect()
+    }
+
+    #[test]
+    fn loop_starts_over() {
+        let mut animation = animation(3, &[0.25], AnimationMode::Loop);
+        assert_eq!(frames(&mut animation, 0.25, 7), vec![1, 2, 0, 1, 2, 0, 1]);
+        assert!(!animation.is_finished());
+    }
+
+    #[test]
+    fn frames_keep_their_duration() {
+        // the last duration is used for the remaining frames
+        let mut animation = animation(3, &[0.25, 0.5], AnimationMode::Loop);
+        assert_eq!(frames(&mut animation, 0.125, 10), vec![0, 1, 1, 1, 1, 2, 2, 2, 2, 0]);
+    }
+
+    #[test]
+    fn large_steps_skip_frames() {
+        let mut small = animation(3, &[0.25, 0.5], AnimationMode::Loop);
+        frames(&mut small, 0.125, 14);
+        let mut large = animation(3, &[0.25, 0.5], AnimationMode::Loop);
+        // more than a whole round of 1.25 s at once
+        large.advance(1.75);
+        assert_eq!(large.frame, 1);
+        assert_eq!(large.frame, small.frame);
+        assert_eq!(large.elapsed, small.elapsed);
+    }
+
+    #[test]
+    fn once_stops_at_the_last_frame() {
+        let mut animation = animation(3, &[0.25], AnimationMode::Once);
+        assert!(!animation.advance(0.5));
+        assert_eq!(animation.frame, 2);
+        assert!(!animation.is_finished());
+        // finishes once the last frame is over, and only once
+        assert!(animation.advance(0.25));
+        assert!(animation.is_finished());
+        assert!(!animation.advance(1.0));
+        assert_eq!(animation.frame, 2);
+        assert_eq!(animation.sprite_number(), 12);
+    }
+
+    #[test]
+    fn once_finishes_within_one_large_step() {
+        let mut animation = animation(3, &[0.25], AnimationMode::Once);
+        assert!(animation.advance(10.0));
+        assert_eq!(animation.frame, 2);
+    }
+
+    #[test]
+    fn ping_pong_turns_at_both_ends() {
+        let mut animation = animation(3, &[0.25], AnimationMode::PingPong);
+        assert_eq!(frames(&mut animation, 0.25, 8), vec![1, 2, 1, 0, 1, 2, 1, 0]);
+        assert!(!animation.is_finished());
+    }
+
+    #[test]
+    fn ping_pong_of_one_frame() {
+        let mut animation = animation(1, &[0.25], AnimationMode::PingPong);
+        assert!(!animation.advance(1.0));
+        assert_eq!(animation.frame, 0);
+    }
+
+    #[test]
+    fn sprite_of_the_current_frame() {
+        let mut animation = animation(3, &[0.25], AnimationMode::Loop);
+        assert_eq!(animation.sprite_number(), 10);
+        animation.advance(0.25);
+        assert_eq!(animation.sprite_number(), 11);
+    }
+
+    #[test]
+    fn durations_default() {
+        let animation = animation(3, &[], AnimationMode::Loop);
+        assert_eq!(animation.def.duration(2), 0.1);
+    }
+
+    #[test]
+    fn validate_rejects_bad_definitions() {
+        let mut def = (*animation(3, &[0.25], AnimationMode::Loop).def).clone();
+        assert!(def.validate().is_ok());
+        def.durations = vec![0.25, 0.0];
+        assert!(def.validate().is_err());
+        def.durations = vec![0.25];
+        def.frames = (2, 1);
+        assert!(def.validate().is_err());
+    }
+}
@@ -0,0 +1,258 @@
+use amethyst::{
+    assets::{AssetStorage, Loader, ProgressCounter},
+    audio::{output::Output, AudioSink, Source, SourceHandle, WavFormat},
+    core::timing::Time,
+    ecs::{Join,
+          ReadStorage,
+          System, SystemData,
+          Read, Write,
+          World},
+    shrev::{EventChannel, ReaderId},
+};
+
+use log::{error};
+
+use crate::components::{Asteroid, AsteroidSize, Ship};
+use crate::resources::{GameMode};
+use crate::settings::{Settings};
+use crate::system::{GameEvent};
+
+// length of the thrust sample, it is not restarted before it ends
+const THRUST_LENGTH: f32 = 0.2;
+// heartbeat interval (s) with no asteroids and with BEAT_FULL_COUNT asteroids
+const BEAT_SLOW: f32 = 1.0;
+const BEAT_FAST: f32 = 0.25;
+const BEAT_FULL_COUNT: usize = 30;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Sound {
+    Shoot,
+    Thrust,
+    Explosion(AsteroidSize),
+    ShipDeath,
+    MenuMove,
+    MenuSelect,
+    // the two alternating heartbeat tones
+    Beat(usize),
+}
+
+impl Sound {
+    fn is_menu(self) -> bool {
+        match self {
+            Sound::MenuMove | Sound::MenuSelect => true,
+            _ => false,
+        }
+    }
+}
+
+pub struct Sounds {
+    shoot: SourceHandle,
+    thrust: SourceHandle,
+    explosions: [SourceHandle; 3],
+    ship_death: SourceHandle,
+    menu_move: SourceHandle,
+    menu_select: SourceHandle,
+    beats: [SourceHandle; 2],
+    music: SourceHandle,
+}
+
+impl Sounds {
+    pub fn initialize(world: &mut World, progress: &mut ProgressCounter) {
+        let sounds = {
+            let loader = world.read_resource::<Loader>();
+            let storage = world.read_resource::<AssetStorage<Source>>();
+            let mut load = |name: &str| loader.load(
+                format!("audio/{}.wav", name), WavFormat, &mut *progress, &storage);
+            Sounds {
+                shoot: load("shoot"),
+                thrust: load("thrust"),
+                explosions: [
+                    load("explosion_small"),
+                    load("explosion_medium"),
+                    load("explosion_large"),
+                ],
+                ship_death: load("ship_death"),
+                menu_move: load("ui_move"),
+                menu_select: load("ui_select"),
+                beats: [load("beat_low"), load("beat_high")],
+                music: load("music"),
+            }
+        };
+        world.insert(sounds);
+    }
+
+    pub fn handle(&self, sound: Sound) -> &SourceHandle {
+        match sound {
+            Sound::Shoot => &self.shoot,
+            Sound::Thrust => &self.thrust,
+            Sound::Explosion(AsteroidSize::Small) => &self.explosions[0],
+            Sound::Explosion(AsteroidSize::Medium) => &self.explosions[1],
+            Sound::Explosion(AsteroidSize::Large) => &self.explosions[2],
+            Sound::ShipDeath => &self.ship_death,
+            Sound::MenuMove => &self.menu_move,
+            Sound::MenuSelect => &self.menu_select,
+            Sound::Beat(i) => &self.beats[i % 2],
+        }
+    }
+}
+
+pub trait AudioBackend {
+    fn play(&mut self, sound: Sound, volume: f32);
+    // keep the background music looping at the given volume
+    fn update_music(&mut self, volume: f32);
+}
+
+// plays through the amethyst audio output
+pub struct OutputBackend<'a> {
+    output: &'a Output,
+    sink: Option<&'a mut AudioSink>,
+    storage: &'a AssetStorage<Source>,
+    sounds: &'a Sounds,
+}
+
+impl AudioBackend for OutputBackend<'_> {
+    fn play(&mut self, sound: Sound, volume: f32) {
+        if let Some(source) = self.storage.get(self.sounds.handle(sound)) {
+            self.output.play_once(source, volume);
+        }
+    }
+
+    fn update_music(&mut self, volume: f32) {
+        if let Some(sink) = self.sink.as_mut() {
+            sink.set_volume(volume);
+            if sink.empty() {
+                if let Some(music) = self.storage.get(&self.sounds.music) {
+                    if let Err(e) = sink.append(music) {
+                        error!("Failed to queue music: {}", e);
+                    }
+                }
+            }
+        }
+    }
+}
+
+// used when there is no sound device or the sounds are not loaded,
+// e.g. in tests and headless runs
+#[derive(Default)]
+pub struct NullBackend {
+    pub played: usize,
+}
+
+impl AudioBackend for NullBackend {
+    fn play(&mut self, _sound: Sound, _volume: f32) {
+        self.played += 1;
+    }
+
+    fn update_music(&mut self, _volume: f32) {
+    }
+}
+
+// time between two heartbeat tones, faster with more asteroids around
+pub fn beat_interval(asteroid_count: usize) -> f32 {
+    let danger = asteroid_count.min(BEAT_FULL_COUNT) as f32 / BEAT_FULL_COUNT as f32;
+    BEAT_SLOW + (BEAT_FAST - BEAT_SLOW) * danger
+}
+
+#[derive(Default)]
+pub struct AudioSystem {
+    event_reader: Option<ReaderId<GameEvent>>,
+    time_to_beat: f32,
+    beat: usize,
+    thrust_cooldown: f32,
+    null_backend: NullBackend,
+}
+
+impl<'s> System<'s> for AudioSystem {
+    type SystemData = (
+        Read<'s, EventChannel<GameEvent>>,
+        Option<Read<'s, Sounds>>,
+        Option<Read<'s, Output>>,
+        Option<Write<'s, AudioSink>>,
+        Read<'s, AssetStorage<Source>>,
+        Read<'s, Settings>,
+        Read<'s, GameMode>,
+        ReadStorage<'s, Asteroid>,
+        ReadStorage<'s, Ship>,
+        Read<'s, Time>,
+    );
+
+    fn setup(&mut self, world: &mut World) {
+        Self::SystemData::setup(world);
+        self.event_reader = Some(
+            world
+                .fetch_mut::<EventChannel<GameEvent>>()
+                .register_reader()
+        )
+    }
+
+    fn run(&mut self,
+           (events,
+            sounds,
+            output,
+            mut sink,
+            storage,
+            settings,
+            mode,
+            asteroids,
+            ships,
+            time): Self::SystemData) {
+        let delta = time.delta_seconds();
+        // the attract mode only plays the menu sounds
+        let demo = *mode == GameMode::Demo;
+        self.thrust_cooldown = (self.thrust_cooldown - delta).max(0.0);
+
+        let mut queue = vec![];
+        for event in events.read(self.event_reader.as_mut().unwrap()) {
+            let sound = match *event {
+                GameEvent::ShotFired => Sound::Shoot,
+                GameEvent::ShotHit | GameEvent::ShotMissed | GameEvent::ShipMoved { .. } => continue,
+                GameEvent::Thrusting => {
+                    if self.thrust_cooldown > 0.0 {
+                        continue;
+                    }
+                    self.thrust_cooldown = THRUST_LENGTH;
+                    Sound::Thrust
+                },
+                GameEvent::AsteroidDestroyed { size, .. } => Sound::Explosion(size),
+                GameEvent::ShipDestroyed { .. } => Sound::ShipDeath,
+                GameEvent::MenuMoved => Sound::MenuMove,
+                GameEvent::MenuSelected => Sound::MenuSelect,
+            };
+            if !demo || sound.is_menu() {
+                queue.push(sound);
+            }
+        }
+
+        // heartbeat while a game is running
+        let asteroid_count = asteroids.join().count();
+        if !demo && asteroid_count > 0 && ships.join().next().is_some() {
+            self.time_to_beat -= delta;
+            if self.time_to_beat <= 0.0 {
+                queue.push(Sound::Beat(self.beat));
+                self.beat = 1 - self.beat;
+                self.time_to_beat = beat_interval(asteroid_count);
+            }
+        }
+
+        let effects_volume = settings.master_volume * settings.effects_volume;
+        let music_volume = settings.master_volume * settings.music_volume;
+        let mut output_backend;
+        let backend: &mut dyn AudioBackend = match (&sounds, &output) {
+            (Some(sounds), Some(output)) => {
+                output_backend = OutputBackend {
+                    output: &output,
+                    sink: sink.as_mut().map(|s| &mut **s),
+                    storage: &storage,
+                    sounds: &sounds,
+                };
+                &mut output_backend
+            },
+            _ => &mut self.null_backend,
+        };
+
+        for sound in queue {
+            backend.play(sound, effects_volume);
+        }
+        backend.update_music(music_volume);
+    }
+}
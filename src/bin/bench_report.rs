@@ -0,0 +1,92 @@
+// Prints the latest criterion results from target/criterion as JSON lines,
+// one benchmark per line, so that runs on different commits can be kept
+// side by side and compared by any tool reading JSON.
+//
+// cargo bench
+// cargo run --bin bench_report -- <label> >> bench_history.jsonl
+
+use std::{
+    env,
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+// the parts of criterion's benchmark.json and estimates.json used here
+#[derive(Deserialize)]
+struct BenchmarkInfo {
+    full_id: String,
+}
+
+#[derive(Deserialize)]
+struct Estimate {
+    point_estimate: f64,
+}
+
+#[derive(Deserialize)]
+struct Estimates {
+    mean: Estimate,
+    median: Estimate,
+    std_dev: Estimate,
+}
+
+#[derive(Serialize)]
+struct Record<'a> {
+    label: &'a str,
+    benchmark: String,
+    mean_ns: f64,
+    median_ns: f64,
+    std_dev_ns: f64,
+}
+
+// criterion keeps the latest run of every benchmark in a directory "new"
+fn find_results(dir: &Path, results: &mut Vec<PathBuf>) -> Result<(), Box<dyn Error>> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if !path.is_dir() {
+            continue;
+        }
+        if path.file_name().map_or(false, |name| name == "new")
+            && path.join("benchmark.json").exists() {
+            results.push(path);
+        } else {
+            find_results(&path, results)?;
+        }
+    }
+    Ok(())
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let label = env::args().nth(1).unwrap_or_else(|| "latest".to_string());
+    let target = env::var("CARGO_TARGET_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| Path::new(env!("CARGO_MANIFEST_DIR")).join("target"));
+    let criterion = target.join("criterion");
+    if !criterion.is_dir() {
+        return Err(format!("no results in {}, run cargo bench first", criterion.display()).into());
+    }
+
+    let mut results = vec![];
+    find_results(&criterion, &mut results)?;
+    let mut records = vec![];
+    for dir in results {
+        let info: BenchmarkInfo = serde_json::from_str(
+            &fs::read_to_string(dir.join("benchmark.json"))?)?;
+        let estimates: Estimates = serde_json::from_str(
+            &fs::read_to_string(dir.join("estimates.json"))?)?;
+        records.push(Record {
+            label: &label,
+            benchmark: info.full_id,
+            mean_ns: estimates.mean.point_estimate,
+            median_ns: estimates.median.point_estimate,
+            std_dev_ns: estimates.std_dev.point_estimate,
+        });
+    }
+    records.sort_by(|a, b| a.benchmark.cmp(&b.benchmark));
+    for record in &records {
+        println!("{}", serde_json::to_string(record)?);
+    }
+    Ok(())
+}
@@ -0,0 +1,128 @@
+// Packs the sprite sheets listed in assets/texture/atlas_sources.ron into
+// one texture, assets/texture/atlas.png, and writes its sprite sheet
+// atlas.ron and the sprite names atlas_names.ron used by SpriteRegistry.
+//
+// cargo run --bin pack_atlas
+
+use std::{
+    cmp::Reverse,
+    collections::BTreeMap,
+    error::Error,
+    fs,
+    path::Path,
+};
+
+use image::{GenericImage, GenericImageView, RgbaImage};
+use serde::{Deserialize, Serialize};
+
+// space between two sprites, avoids bleeding when sampling
+const PADDING: u32 = 1;
+
+#[derive(Deserialize)]
+struct Source {
+    sheet: String,
+    names: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SpritePosition {
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SpriteList {
+    texture_width: u32,
+    texture_height: u32,
+    sprites: Vec<SpritePosition>,
+}
+
+// the amethyst sprite sheet format
+#[derive(Serialize, Deserialize)]
+enum SpriteSheet {
+    List(SpriteList),
+}
+
+// shelf packing, tallest sprites first, returns the atlas size and the
+// position of each sprite
+fn pack(sizes: &[(u32, u32)]) -> (u32, u32, Vec<(u32, u32)>) {
+    let max_width = sizes.iter().map(|s| s.0 + PADDING).max().unwrap_or(1);
+    let mut width = max_width.next_power_of_two();
+    loop {
+        let mut order: Vec<usize> = (0..sizes.len()).collect();
+        order.sort_by_key(|&i| Reverse(sizes[i].1));
+
+        let mut positions = vec![(0, 0); sizes.len()];
+        let (mut x, mut y, mut shelf) = (0, 0, 0);
+        for i in order {
+            let (w, h) = sizes[i];
+            if x + w + PADDING > width {
+                x = 0;
+                y += shelf;
+                shelf = 0;
+            }
+            positions[i] = (x, y);
+            x += w + PADDING;
+            shelf = shelf.max(h + PADDING);
+        }
+
+        let height = (y + shelf).next_power_of_two();
+        if height <= width {
+            return (width, height, positions);
+        }
+        width *= 2;
+    }
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("assets").join("texture");
+    let sources: Vec<Source> = ron::de::from_str(
+        &fs::read_to_string(dir.join("atlas_sources.ron"))?)?;
+
+    // cut every sprite out of its sheet
+    let mut sprites = vec![];
+    let mut names = BTreeMap::new();
+    for source in &sources {
+        let image = image::open(dir.join(format!("{}.png", source.sheet)))?.to_rgba();
+        let SpriteSheet::List(list) = ron::de::from_str(
+            &fs::read_to_string(dir.join(format!("{}.ron", source.sheet)))?)?;
+        if list.sprites.len() != source.names.len() {
+            return Err(format!("{}: {} sprites but {} names",
+                               source.sheet, list.sprites.len(), source.names.len()).into());
+        }
+        for (sprite, name) in list.sprites.iter().zip(source.names.iter()) {
+            if names.insert(name.clone(), sprites.len()).is_some() {
+                return Err(format!("duplicate sprite name {}", name).into());
+            }
+            sprites.push(image.view(sprite.x, sprite.y, sprite.width, sprite.height).to_image());
+        }
+    }
+
+    let sizes: Vec<(u32, u32)> = sprites.iter().map(|s| s.dimensions()).collect();
+    let (width, height, positions) = pack(&sizes);
+
+    let mut atlas = RgbaImage::new(width, height);
+    for (sprite, &(x, y)) in sprites.iter().zip(positions.iter()) {
+        atlas.copy_from(sprite, x, y);
+    }
+    atlas.save(dir.join("atlas.png"))?;
+
+    let sheet = SpriteSheet::List(SpriteList {
+        texture_width: width,
+        texture_height: height,
+        sprites: sizes.iter().zip(positions.iter()).map(|(&(w, h), &(x, y))| SpritePosition {
+            x,
+            y,
+            width: w,
+            height: h,
+        }).collect(),
+    });
+    let pretty = ron::ser::PrettyConfig::default();
+    fs::write(dir.join("atlas.ron"), ron::ser::to_string_pretty(&sheet, pretty.clone())?)?;
+    fs::write(dir.join("atlas_names.ron"), ron::ser::to_string_pretty(&names, pretty)?)?;
+
+    println!("packed {} sprites into a {}x{} atlas", sprites.len(), width, height);
+    Ok(())
+}
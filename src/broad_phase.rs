@@ -0,0 +1,156 @@
+use std::collections::HashMap;
+
+use amethyst::core::math::Vector2;
+use rayon::prelude::*;
+
+// a circle around a collider, placed in the world
+#[derive(Clone, Copy, Debug)]
+pub struct Bounds {
+    pub center: Vector2<f32>,
+    pub radius: f32,
+}
+
+impl Bounds {
+    pub fn overlaps(&self, other: &Bounds) -> bool {
+        (self.center - other.center).norm() <= self.radius + other.radius
+    }
+
+    fn min(&self) -> Vector2<f32> {
+        self.center - Vector2::new(self.radius, self.radius)
+    }
+
+    fn max(&self) -> Vector2<f32> {
+        self.center + Vector2::new(self.radius, self.radius)
+    }
+}
+
+type Cell = (i32, i32);
+
+// a circle is put into every cell its box covers, so that one large
+// collider, e.g. an obstacle, does not make the cells of everything large
+const CELL_SIZE: f32 = 32.0;
+
+fn cell_of(point: Vector2<f32>) -> Cell {
+    ((point.x / CELL_SIZE).floor() as i32, (point.y / CELL_SIZE).floor() as i32)
+}
+
+// pairs of overlapping circles as indices into `bounds`, the lower index
+// first and sorted, so that the result does not depend on how the cells
+// were shared between the threads; `allowed` skips pairs that never
+// collide before they are tested
+pub fn overlapping_pairs<F>(bounds: &[Bounds], allowed: F, parallel: bool) -> Vec<(usize, usize)>
+    where F: Fn(usize, usize) -> bool + Sync {
+    let mut grid: HashMap<Cell, Vec<usize>> = HashMap::new();
+    for (i, b) in bounds.iter().enumerate() {
+        let (x0, y0) = cell_of(b.min());
+        let (x1, y1) = cell_of(b.max());
+        for x in x0..=x1 {
+            for y in y0..=y1 {
+                grid.entry((x, y)).or_default().push(i);
+            }
+        }
+    }
+    let cells: Vec<(Cell, Vec<usize>)> = grid.into_iter().collect();
+
+    let pairs_in = |(cell, members): &(Cell, Vec<usize>)| {
+        let mut pairs = vec![];
+        for (n, &i) in members.iter().enumerate() {
+            for &j in &members[n + 1..] {
+                let (a, b) = (&bounds[i], &bounds[j]);
+                // two circles can share several cells, the pair belongs to
+                // the cell where their boxes start to overlap
+                let corner = Vector2::new(a.min().x.max(b.min().x), a.min().y.max(b.min().y));
+                if cell_of(corner) != *cell {
+                    continue;
+                }
+                if allowed(i, j) && a.overlaps(b) {
+                    pairs.push((i, j));
+                }
+            }
+        }
+        pairs
+    };
+
+    let mut pairs: Vec<(usize, usize)> = if parallel {
+        cells.par_iter().flat_map(&pairs_in).collect()
+    } else {
+        cells.iter().flat_map(&pairs_in).collect()
+    };
+    pairs.sort();
+    pairs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    fn random_bounds(seed: u64, count: usize) -> Vec<Bounds> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        (0..count)
+            .map(|_| Bounds {
+                center: Vector2::new(rng.gen_range(-50.0, 350.0), rng.gen_range(-50.0, 350.0)),
+                radius: rng.gen_range(1.0, 20.0),
+            })
+            .collect()
+    }
+
+    fn brute_force<F: Fn(usize, usize) -> bool>(bounds: &[Bounds], allowed: F) -> Vec<(usize, usize)> {
+        let mut pairs = vec![];
+        for i in 0..bounds.len() {
+            for j in i + 1..bounds.len() {
+                if allowed(i, j) && bounds[i].overlaps(&bounds[j]) {
+                    pairs.push((i, j));
+                }
+            }
+        }
+        pairs
+    }
+
+    #[test]
+    fn parallel_and_serial_agree() {
+        for seed in 0..8 {
+            let bounds = random_bounds(seed, 400);
+            let parallel = overlapping_pairs(&bounds, |_, _| true, true);
+            let serial = overlapping_pairs(&bounds, |_, _| true, false);
+            assert!(!serial.is_empty());
+            assert_eq!(parallel, serial, "seed {}", seed);
+        }
+    }
+
+    #[test]
+    fn finds_every_pair_once() {
+        let bounds = random_bounds(42, 300);
+        assert_eq!(overlapping_pairs(&bounds, |_, _| true, false), brute_force(&bounds, |_, _| true));
+    }
+
+    #[test]
+    fn skips_pairs_not_allowed() {
+        let bounds = random_bounds(7, 300);
+        let allowed = |i: usize, j: usize| (i + j) % 3 != 0;
+        let pairs = overlapping_pairs(&bounds, allowed, true);
+        assert!(pairs.iter().all(|&(i, j)| allowed(i, j)));
+        assert_eq!(pairs, brute_force(&bounds, allowed));
+    }
+
+    #[test]
+    fn large_collider_meets_small_ones() {
+        let mut bounds = random_bounds(3, 200);
+        // covers many cells, the small ones still share cells among themselves
+        bounds.push(Bounds { center: Vector2::new(150.0, 150.0), radius: 120.0 });
+        let large = bounds.len() - 1;
+        let pairs = overlapping_pairs(&bounds, |_, _| true, true);
+        assert_eq!(pairs, brute_force(&bounds, |_, _| true));
+        assert!(pairs.iter().any(|&(_, j)| j == large));
+    }
+
+    #[test]
+    fn touching_circles_overlap() {
+        let bounds = [
+            Bounds { center: Vector2::new(0.0, 0.0), radius: 1.0 },
+            Bounds { center: Vector2::new(2.0, 0.0), radius: 1.0 },
+            Bounds { center: Vector2::new(10.0, 0.0), radius: 1.0 },
+        ];
+        assert_eq!(overlapping_pairs(&bounds, |_, _| true, false), vec![(0, 1)]);
+    }
+}
@@ -1,14 +1,18 @@
 use amethyst::{
     core::math::Vector2,
-    ecs::prelude::{Component, DenseVecStorage},
+    ecs::prelude::{Component, DenseVecStorage, Entity},
 };
 
+use serde::{Deserialize, Serialize};
+
+use crate::geometry;
+
 pub struct Physical {
     // velocity, [vx, vy]
     pub velocity: Vector2<f32>,
     // maximum velocity (units / s)
     pub max_velocity: f32,
-    // rotation (rad / s)
+    // angular velocity (rad / s)
     pub rotation: f32,
 }
 
@@ -16,72 +20,154 @@ impl Component for Physical {
     type Storage = DenseVecStorage<Self>;
 }
 
+// the movement of a ship is configured by ShipPhysicsConfig, what it
+// fires is in its Weapons
 pub struct Ship {
-    pub acceleration: f32,
-    pub rotate: f32,
-    pub reload_timer: f32,
-    pub time_to_reload: f32,
+    // thrust flame, shown while accelerating
+    pub flame: Option<Entity>,
 }
 
 impl Ship {
     pub fn new() -> Self {
         Self {
-            acceleration: 80f32,
-            rotate: 180f32,
-            // time to reload
-            reload_timer: 0.0f32,
-            time_to_reload: 0.5f32,
+            flame: None,
         }
     }
 }
 
+impl Default for Ship {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Component for Ship {
     type Storage = DenseVecStorage<Self>;
 }
 
-pub struct Bullet;
+pub struct Bullet {
+    // bounces off solid edges so far
+    pub ricochets: u32,
+}
 
 impl Component for Bullet {
     type Storage = DenseVecStorage<Self>;
 }
 
-pub struct Asteroid;
-
-impl Component for Asteroid {
-    type Storage = DenseVecStorage<Self>;
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub enum AsteroidSize {
+    Small,
+    Medium,
+    Large,
 }
 
-pub struct Explosion {
-    pub time_to_update: f32,
-    pub frame_count: i32,
-}
+impl AsteroidSize {
+    pub const ALL: [AsteroidSize; 3] = [
+        AsteroidSize::Small,
+        AsteroidSize::Medium,
+        AsteroidSize::Large,
+    ];
 
-impl Explosion {
-    pub const FRAME_LIMIT: i32 = 4;
-    pub fn new() -> Self {
-        Self {
-            time_to_update: 0.1f32,
-            frame_count: 0,
+    // scale of the sprite and the collider
+    pub fn scale(self) -> f32 {
+        match self {
+            AsteroidSize::Small => 0.6,
+            AsteroidSize::Medium => 1.0,
+            AsteroidSize::Large => 1.6,
         }
     }
+
+    // for bouncing asteroids, grows with the area
+    pub fn mass(self) -> f32 {
+        self.scale() * self.scale()
+    }
+}
+
+pub struct Asteroid {
+    pub size: AsteroidSize,
 }
 
-impl Component for Explosion {
+impl Component for Asteroid {
     type Storage = DenseVecStorage<Self>;
 }
 
 #[derive(Clone)]
 pub struct Collider {
-    pub typ: ColliderType
+    pub typ: ColliderType,
+    pub shape: ColliderShape,
+}
+
+// collision shape in the local space of the entity, scaled with it
+#[derive(Clone, Debug)]
+pub enum ColliderShape {
+    Circle(f32),
+    // outline of the shape, may be concave
+    Polygon(Vec<Vector2<f32>>),
+}
+
+impl ColliderShape {
+    // radius of a circle around the origin containing the shape
+    pub fn bounding_radius(&self) -> f32 {
+        match self {
+            ColliderShape::Circle(radius) => *radius,
+            ColliderShape::Polygon(points) => geometry::polygon_radius(points),
+        }
+    }
 }
 
-#[derive(PartialEq,Clone,Copy)]
+#[derive(PartialEq,Clone,Copy,Debug)]
 pub enum ColliderType {
     Ship,
     Bullet,
     Asteroid,
+    // static walls of a level
+    Obstacle,
 }
 
 impl Component for Collider {
     type Storage = DenseVecStorage<Self>;
 }
+
+// the intent of a ship for this frame, written by the player input or
+// by an autopilot and consumed by ShipControlSystem
+#[derive(Default)]
+pub struct ShipCommand {
+    // thrust, [-1, 1]
+    pub accelerate: f32,
+    // rotation, [-1, 1], positive is counter-clockwise
+    pub rotate: f32,
+    pub shoot: bool,
+    // held down to select the next weapon, it switches once per press
+    pub switch_weapon: bool,
+}
+
+impl Component for ShipCommand {
+    type Storage = DenseVecStorage<Self>;
+}
+
+// ships with this component are driven by AutoPilotSystem instead of input
+pub struct AutoPilot {
+    // angle (rad) within which the autopilot opens fire
+    pub aim_tolerance: f32,
+    // preferred distance to the target asteroid
+    pub keep_distance: f32,
+}
+
+impl AutoPilot {
+    pub fn new() -> Self {
+        Self {
+            aim_tolerance: 0.15f32,
+            keep_distance: 80f32,
+        }
+    }
+}
+
+impl Default for AutoPilot {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Component for AutoPilot {
+    type Storage = DenseVecStorage<Self>;
+}
@@ -0,0 +1,353 @@
+use amethyst::core::{
+    math::Vector2,
+    transform::components::Transform,
+};
+
+use crate::components::{AsteroidSize};
+use crate::geometry;
+use crate::hazards::{HazardConfig, HazardKind, SOFTENING};
+use crate::level::{AsteroidConfig, Level, ObstacleConfig};
+use crate::resources::{Arena};
+use crate::system::{ASTEROID_RADIUS};
+
+// steps kept for undo
+const MAX_UNDO: usize = 100;
+
+// the arena never gets smaller than this
+const MIN_ARENA_SIZE: f32 = 100.0;
+
+// radius around an item that selects it
+const PICK_RADIUS: f32 = 8.0;
+
+// the velocity handle of an asteroid is where it will be after this many seconds
+pub const VELOCITY_HANDLE_TIME: f32 = 0.5;
+
+// one thing of the level, by its index in the level lists
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Item {
+    Asteroid(usize),
+    Obstacle(usize),
+    Hazard(usize),
+}
+
+// a level being edited, every change can be undone
+#[derive(Default)]
+pub struct Editor {
+    pub level: Level,
+    pub selected: Option<Item>,
+    undo: Vec<Level>,
+    redo: Vec<Level>,
+}
+
+fn clamp_position((x, y): (f32, f32)) -> (f32, f32) {
+    (x.max(0.0).min(1.0), y.max(0.0).min(1.0))
+}
+
+// screen pixels, y down, to arena units, y up; the arena fills the
+// viewport in the middle of the screen
+pub fn screen_to_arena(point: (f32, f32), screen: (f32, f32), viewport: (f32, f32),
+                       arena: &Arena) -> Vector2<f32> {
+    let scale = viewport.0 / arena.width;
+    let left = (screen.0 - viewport.0) * 0.5;
+    let top = (screen.1 - viewport.1) * 0.5;
+    Vector2::new((point.0 - left) / scale, arena.height - (point.1 - top) / scale)
+}
+
+// a new obstacle is a square
+fn square(size: f32) -> Vec<(f32, f32)> {
+    let h = size * 0.5;
+    vec![(-h, -h), (h, -h), (h, h), (-h, h)]
+}
+
+impl Editor {
+    pub fn new(level: Level) -> Self {
+        Self {
+            level,
+            ..Default::default()
+        }
+    }
+
+    // call before every change, a drag is one change
+    pub fn checkpoint(&mut self) {
+        self.undo.push(self.level.clone());
+        if self.undo.len() > MAX_UNDO {
+            self.undo.remove(0);
+        }
+        self.redo.clear();
+    }
+
+    // returns whether there was anything to undo
+    pub fn undo(&mut self) -> bool {
+        match self.undo.pop() {
+            Some(level) => {
+                self.redo.push(std::mem::replace(&mut self.level, level));
+                self.selected = None;
+                true
+            },
+            None => false,
+        }
+    }
+
+    pub fn redo(&mut self) -> bool {
+        match self.redo.pop() {
+            Some(level) => {
+                self.undo.push(std::mem::replace(&mut self.level, level));
+                self.selected = None;
+                true
+            },
+            None => false,
+        }
+    }
+
+    // in fractions of the arena size
+    pub fn position(&self, item: Item) -> (f32, f32) {
+        match item {
+            Item::Asteroid(i) => self.level.asteroids[i].position,
+            Item::Obstacle(i) => self.level.obstacles[i].position,
+            Item::Hazard(i) => self.level.hazards[i].position,
+        }
+    }
+
+    // an item that no longer exists is ignored
+    pub fn set_position(&mut self, item: Item, position: (f32, f32)) {
+        let target = match item {
+            Item::Asteroid(i) => self.level.asteroids.get_mut(i).map(|a| &mut a.position),
+            Item::Obstacle(i) => self.level.obstacles.get_mut(i).map(|o| &mut o.position),
+            Item::Hazard(i) => self.level.hazards.get_mut(i).map(|h| &mut h.position),
+        };
+        if let Some(target) = target {
+            *target = clamp_position(position);
+        }
+    }
+
+    pub fn world_position(&self, item: Item, arena: &Arena) -> Vector2<f32> {
+        let (x, y) = self.position(item);
+        Vector2::new(x * arena.width, y * arena.height)
+    }
+
+    // the outline of an obstacle in arena units
+    pub fn obstacle_outline(&self, i: usize, arena: &Arena) -> Vec<Vector2<f32>> {
+        let obstacle = &self.level.obstacles[i];
+        let mut transform = Transform::default();
+        transform.set_translation_xyz(obstacle.position.0 * arena.width,
+                                      obstacle.position.1 * arena.height, 0.0);
+        transform.set_rotation_2d(obstacle.rotation.to_radians());
+        let points: Vec<Vector2<f32>> = obstacle.points.iter()
+            .map(|&(x, y)| Vector2::new(x, y))
+            .collect();
+        geometry::to_world(&transform, &points)
+    }
+
+    // where the velocity handle of an asteroid is drawn
+    pub fn velocity_handle(&self, i: usize, arena: &Arena) -> Vector2<f32> {
+        let (vx, vy) = self.level.asteroids[i].velocity;
+        self.world_position(Item::Asteroid(i), arena) + Vector2::new(vx, vy) * VELOCITY_HANDLE_TIME
+    }
+
+    // the item at a point, asteroids before hazards before obstacles and
+    // the last added first
+    pub fn pick(&self, point: Vector2<f32>, arena: &Arena) -> Option<Item> {
+        let near = |item: Item, radius: f32|
+            (self.world_position(item, arena) - point).norm() <= radius.max(PICK_RADIUS);
+        let level = &self.level;
+        (0..level.asteroids.len()).rev()
+            .find(|&i| near(Item::Asteroid(i), ASTEROID_RADIUS * level.asteroids[i].size.scale()))
+            .map(Item::Asteroid)
+            .or_else(|| (0..level.hazards.len()).rev()
+                     .find(|&i| near(Item::Hazard(i), level.hazards[i].horizon.max(SOFTENING)))
+                     .map(Item::Hazard))
+            .or_else(|| (0..level.obstacles.len()).rev()
+                     .find(|&i| near(Item::Obstacle(i), 0.0)
+                           || geometry::polygon_contains(&self.obstacle_outline(i, arena), point))
+                     .map(Item::Obstacle))
+    }
+
+    // the asteroid whose velocity handle is at a point
+    pub fn pick_velocity(&self, point: Vector2<f32>, arena: &Arena) -> Option<Item> {
+        (0..self.level.asteroids.len()).rev()
+            .find(|&i| (self.velocity_handle(i, arena) - point).norm() <= PICK_RADIUS * 0.5)
+            .map(Item::Asteroid)
+    }
+
+    pub fn add_asteroid(&mut self, size: AsteroidSize, position: (f32, f32)) -> Item {
+        self.level.asteroids.push(AsteroidConfig {
+            size,
+            position: clamp_position(position),
+            velocity: (0.0, 0.0),
+            rotation: 0.0,
+        });
+        Item::Asteroid(self.level.asteroids.len() - 1)
+    }
+
+    pub fn add_obstacle(&mut self, position: (f32, f32)) -> Item {
+        self.level.obstacles.push(ObstacleConfig {
+            position: clamp_position(position),
+            points: square(20.0),
+            rotation: 0.0,
+        });
+        Item::Obstacle(self.level.obstacles.len() - 1)
+    }
+
+    pub fn add_hazard(&mut self, kind: HazardKind, position: (f32, f32)) -> Item {
+        let (strength, horizon) = match kind {
+            HazardKind::GravityWell => (20000.0, 0.0),
+            HazardKind::BlackHole => (30000.0, 8.0),
+        };
+        self.level.hazards.push(HazardConfig {
+            kind,
+            position: clamp_position(position),
+            strength,
+            horizon,
+        });
+        Item::Hazard(self.level.hazards.len() - 1)
+    }
+
+    pub fn delete(&mut self, item: Item) {
+        match item {
+            Item::Asteroid(i) => { self.level.asteroids.remove(i); },
+            Item::Obstacle(i) => { self.level.obstacles.remove(i); },
+            Item::Hazard(i) => { self.level.hazards.remove(i); },
+        }
+        self.selected = None;
+    }
+
+    // obstacles turn by the step in degrees, asteroids change their spin
+    // by a tenth of it in rad / s
+    pub fn rotate(&mut self, item: Item, step: f32) {
+        match item {
+            Item::Obstacle(i) => {
+                let rotation = &mut self.level.obstacles[i].rotation;
+                *rotation = (*rotation + step).rem_euclid(360.0);
+            },
+            Item::Asteroid(i) => self.level.asteroids[i].rotation += step * 0.1,
+            Item::Hazard(_) => {},
+        }
+    }
+
+    // the velocity handle of an asteroid was moved to a point
+    pub fn set_velocity_handle(&mut self, i: usize, point: Vector2<f32>, arena: &Arena) {
+        if i >= self.level.asteroids.len() {
+            return;
+        }
+        let velocity = (point - self.world_position(Item::Asteroid(i), arena)) / VELOCITY_HANDLE_TIME;
+        self.level.asteroids[i].velocity = (velocity.x, velocity.y);
+    }
+
+    // grow the arena by the given amounts, negative shrinks it
+    pub fn resize(&mut self, grow_width: f32, grow_height: f32) {
+        self.level.width = (self.level.width + grow_width).max(MIN_ARENA_SIZE);
+        self.level.height = (self.level.height + grow_height).max(MIN_ARENA_SIZE);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn editor() -> Editor {
+        Editor::new(Level::default())
+    }
+
+    #[test]
+    fn undo_and_redo_go_back_and_forth_in_order() {
+        let mut editor = editor();
+        editor.checkpoint();
+        editor.add_asteroid(AsteroidSize::Small, (0.1, 0.1));
+        editor.checkpoint();
+        editor.add_obstacle((0.2, 0.2));
+
+        assert!(editor.undo());
+        assert_eq!((editor.level.asteroids.len(), editor.level.obstacles.len()), (1, 0));
+        assert!(editor.undo());
+        assert_eq!((editor.level.asteroids.len(), editor.level.obstacles.len()), (0, 0));
+        assert!(!editor.undo());
+
+        assert!(editor.redo());
+        assert_eq!((editor.level.asteroids.len(), editor.level.obstacles.len()), (1, 0));
+        assert!(editor.redo());
+        assert_eq!((editor.level.asteroids.len(), editor.level.obstacles.len()), (1, 1));
+        assert!(!editor.redo());
+    }
+
+    #[test]
+    fn new_edit_clears_redo() {
+        let mut editor = editor();
+        editor.checkpoint();
+        editor.add_asteroid(AsteroidSize::Small, (0.1, 0.1));
+        assert!(editor.undo());
+        editor.checkpoint();
+        editor.add_hazard(HazardKind::GravityWell, (0.3, 0.3));
+        assert!(!editor.redo());
+        assert_eq!(editor.level.asteroids.len(), 0);
+        assert_eq!(editor.level.hazards.len(), 1);
+    }
+
+    #[test]
+    fn undo_keeps_the_latest_steps() {
+        let mut editor = editor();
+        for _ in 0..MAX_UNDO + 10 {
+            editor.checkpoint();
+            editor.add_asteroid(AsteroidSize::Small, (0.5, 0.5));
+        }
+        let mut steps = 0;
+        while editor.undo() {
+            steps += 1;
+        }
+        assert_eq!(steps, MAX_UNDO);
+        // the oldest steps were dropped
+        assert_eq!(editor.level.asteroids.len(), 10);
+    }
+
+    #[test]
+    fn undo_clears_the_selection() {
+        let mut editor = editor();
+        editor.checkpoint();
+        editor.selected = Some(editor.add_asteroid(AsteroidSize::Large, (0.5, 0.5)));
+        editor.undo();
+        assert_eq!(editor.selected, None);
+    }
+
+    #[test]
+    fn added_items_are_clamped_to_the_arena() {
+        let mut editor = editor();
+        let item = editor.add_asteroid(AsteroidSize::Medium, (1.5, -0.5));
+        assert_eq!(item, Item::Asteroid(0));
+        assert_eq!(editor.position(item), (1.0, 0.0));
+        let item = editor.add_hazard(HazardKind::BlackHole, (0.5, 0.5));
+        assert_eq!(editor.level.hazards[0].horizon, 8.0);
+        assert_eq!(item, Item::Hazard(0));
+    }
+
+    #[test]
+    fn delete_removes_the_item() {
+        let mut editor = editor();
+        editor.add_obstacle((0.1, 0.1));
+        let second = editor.add_obstacle((0.9, 0.9));
+        editor.selected = Some(second);
+        editor.delete(Item::Obstacle(0));
+        assert_eq!(editor.level.obstacles.len(), 1);
+        assert_eq!(editor.level.obstacles[0].position, (0.9, 0.9));
+        assert_eq!(editor.selected, None);
+    }
+
+    #[test]
+    fn removed_items_are_not_moved() {
+        let mut editor = editor();
+        editor.checkpoint();
+        let item = editor.add_asteroid(AsteroidSize::Small, (0.1, 0.1));
+        editor.undo();
+        editor.set_position(item, (0.5, 0.5));
+        editor.set_velocity_handle(0, Vector2::new(10.0, 10.0), &Arena { width: 300.0, height: 300.0 });
+        assert!(editor.level.asteroids.is_empty());
+    }
+
+    #[test]
+    fn resize_grows_and_shrinks_down_to_the_minimum() {
+        let mut editor = editor();
+        let (width, height) = (editor.level.width, editor.level.height);
+        editor.resize(20.0, -20.0);
+        assert_eq!((editor.level.width, editor.level.height), (width + 20.0, height - 20.0));
+        editor.resize(-10000.0, 0.0);
+        assert_eq!(editor.level.width, MIN_ARENA_SIZE);
+    }
+}
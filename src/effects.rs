@@ -0,0 +1,186 @@
+use amethyst::{
+    core::{
+        math::Vector2,
+        transform::components::Transform,
+        timing::Time,
+    },
+    ecs::{Join,
+          ReadStorage, WriteStorage,
+          System, SystemData,
+          Read, Write, ReadExpect,
+          Entities,
+          World},
+    ecs::prelude::{Entity},
+    renderer::Camera,
+    shrev::{EventChannel, ReaderId},
+    ui::{Anchor, UiImage, UiTransform},
+};
+use log::{error};
+
+use crate::components::{AsteroidSize};
+use crate::resources::{Arena, GameMode};
+use crate::settings::{Settings};
+use crate::system::{GameEvent};
+
+// camera offset at full trauma, world units
+const MAX_SHAKE: f32 = 6.0;
+// trauma lost per second
+const TRAUMA_DECAY: f32 = 1.5;
+// flash alpha lost per second
+const FLASH_DECAY: f32 = 2.5;
+// game speed during a hit-stop
+const HIT_STOP_SCALE: f32 = 0.05;
+
+// state of the camera feedback, fed by gameplay events
+#[derive(Default)]
+pub struct CameraEffects {
+    // [0, 1], the shake grows with its square
+    pub trauma: f32,
+    // real seconds left of the hit-stop
+    pub hit_stop: f32,
+    // alpha of the white flash overlay
+    pub flash: f32,
+}
+
+impl CameraEffects {
+    pub fn add_trauma(&mut self, amount: f32) {
+        self.trauma = (self.trauma + amount).min(1.0);
+    }
+
+    // a longer hit-stop is not cut short by a shorter one
+    pub fn hit_stop(&mut self, duration: f32) {
+        self.hit_stop = self.hit_stop.max(duration);
+    }
+
+    pub fn flash(&mut self, alpha: f32) {
+        self.flash = self.flash.max(alpha);
+    }
+
+    pub fn update(&mut self, delta: f32) {
+        self.trauma = (self.trauma - TRAUMA_DECAY * delta).max(0.0);
+        self.hit_stop = (self.hit_stop - delta).max(0.0);
+        self.flash = (self.flash - FLASH_DECAY * delta).max(0.0);
+    }
+
+    pub fn time_scale(&self) -> f32 {
+        if self.hit_stop > 0.0 { HIT_STOP_SCALE } else { 1.0 }
+    }
+
+    // camera offset at the given time, a sum of sines stands in for noise
+    pub fn shake_offset(&self, time: f32) -> Vector2<f32> {
+        let amount = MAX_SHAKE * self.trauma * self.trauma;
+        Vector2::new(
+            amount * ((time * 37.0).sin() * 0.6 + (time * 71.0 + 1.3).sin() * 0.4),
+            amount * ((time * 43.0 + 0.7).sin() * 0.6 + (time * 67.0 + 2.1).sin() * 0.4),
+        )
+    }
+}
+
+fn trauma(size: AsteroidSize) -> f32 {
+    match size {
+        AsteroidSize::Small => 0.1,
+        AsteroidSize::Medium => 0.25,
+        AsteroidSize::Large => 0.4,
+    }
+}
+
+// turn gameplay events into shake, hit-stop and flash and apply them to
+// the camera, the game speed and the overlay
+#[derive(Default)]
+pub struct CameraEffectsSystem {
+    event_reader: Option<ReaderId<GameEvent>>,
+    overlay: Option<Entity>,
+}
+
+impl<'s> System<'s> for CameraEffectsSystem {
+    type SystemData = (
+        Entities<'s>,
+        Write<'s, CameraEffects>,
+        Read<'s, EventChannel<GameEvent>>,
+        Read<'s, Settings>,
+        Read<'s, GameMode>,
+        ReadExpect<'s, Arena>,
+        Write<'s, Time>,
+        ReadStorage<'s, Camera>,
+        WriteStorage<'s, Transform>,
+        WriteStorage<'s, UiTransform>,
+        WriteStorage<'s, UiImage>,
+    );
+
+    fn setup(&mut self, world: &mut World) {
+        Self::SystemData::setup(world);
+        self.event_reader = Some(
+            world
+                .fetch_mut::<EventChannel<GameEvent>>()
+                .register_reader()
+        )
+    }
+
+    fn run(&mut self,
+           (entities,
+            mut effects,
+            events,
+            settings,
+            mode,
+            arena,
+            mut time,
+            cameras,
+            mut transforms,
+            mut ui_transforms,
+            mut ui_images): Self::SystemData) {
+        // the attract mode stays calm
+        let demo = *mode == GameMode::Demo;
+        for event in events.read(self.event_reader.as_mut().unwrap()) {
+            if demo {
+                continue;
+            }
+            match *event {
+                GameEvent::AsteroidDestroyed { size, .. } => {
+                    if settings.screen_shake {
+                        effects.add_trauma(trauma(size));
+                    }
+                    if settings.hit_stop && size == AsteroidSize::Large {
+                        effects.hit_stop(0.06);
+                    }
+                },
+                GameEvent::ShipDestroyed { .. } => {
+                    if settings.screen_shake {
+                        effects.add_trauma(1.0);
+                    }
+                    if settings.hit_stop {
+                        effects.hit_stop(0.15);
+                    }
+                    if settings.flash {
+                        effects.flash(0.8);
+                    }
+                },
+                _ => {},
+            }
+        }
+
+        // effects run on real time, the game time may be stopped
+        effects.update(time.delta_real_seconds());
+        time.set_time_scale(effects.time_scale());
+
+        let offset = effects.shake_offset(time.absolute_real_time_seconds() as f32);
+        for (_, transform) in (&cameras, &mut transforms).join() {
+            transform.set_translation_x(arena.width * 0.5 + offset.x);
+            transform.set_translation_y(arena.height * 0.5 + offset.y);
+        }
+
+        let overlay = *self.overlay.get_or_insert_with(|| {
+            let e = entities.create();
+            let result = ui_transforms.insert(e, UiTransform::new(
+                "flash".to_string(), Anchor::Middle, Anchor::Middle,
+                0., 0., 10., 1., 1.).into_percent())
+                .and(ui_images.insert(e, UiImage::SolidColor([1., 1., 1., 0.])));
+            if let Err(e) = result {
+                error!("Failed to create flash overlay: {}", e);
+            }
+            e
+        });
+        if let Some(image) = ui_images.get_mut(overlay) {
+            *image = UiImage::SolidColor([1., 1., 1., effects.flash]);
+        }
+    }
+}
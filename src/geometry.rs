@@ -0,0 +1,108 @@
+use std::f32::consts::PI;
+
+use amethyst::core::{
+    math::{Point3, Vector2},
+    transform::components::Transform,
+};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+// a jagged rock outline around the origin, the same seed gives the same rock
+pub fn asteroid_outline(seed: u64, radius: f32) -> Vec<Vector2<f32>> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let count = rng.gen_range(9, 14);
+    (0..count).map(|i| {
+        // jitter the angle inside its slice so the outline never crosses itself
+        let angle = (i as f32 + rng.gen_range(-0.3, 0.3)) * 2.0 * PI / count as f32;
+        let r = radius * rng.gen_range(0.7, 1.05);
+        Vector2::new(angle.cos() * r, angle.sin() * r)
+    }).collect()
+}
+
+// local points of an entity in world space
+pub fn to_world(transform: &Transform, points: &[Vector2<f32>]) -> Vec<Vector2<f32>> {
+    let matrix = transform.matrix();
+    points.iter().map(|p| {
+        let p = matrix.transform_point(&Point3::new(p.x, p.y, 0.0));
+        Vector2::new(p.x, p.y)
+    }).collect()
+}
+
+// distance of the farthest vertex from the origin
+pub fn polygon_radius(polygon: &[Vector2<f32>]) -> f32 {
+    polygon.iter().map(|p| p.norm()).fold(0.0, f32::max)
+}
+
+// even-odd rule, the polygon may be concave
+pub fn polygon_contains(polygon: &[Vector2<f32>], point: Vector2<f32>) -> bool {
+    let mut inside = false;
+    let mut j = polygon.len().wrapping_sub(1);
+    for i in 0..polygon.len() {
+        let (a, b) = (polygon[i], polygon[j]);
+        if (a.y > point.y) != (b.y > point.y)
+            && point.x < (b.x - a.x) * (point.y - a.y) / (b.y - a.y) + a.x {
+            inside = !inside;
+        }
+        j = i;
+    }
+    inside
+}
+
+pub fn segment_distance(a: Vector2<f32>, b: Vector2<f32>, p: Vector2<f32>) -> f32 {
+    let ab = b - a;
+    let length = ab.norm_squared();
+    let t = if length > 0.0 { ((p - a).dot(&ab) / length).max(0.0).min(1.0) } else { 0.0 };
+    (a + ab * t - p).norm()
+}
+
+fn edges<'a>(polygon: &'a [Vector2<f32>]) -> impl Iterator<Item = (Vector2<f32>, Vector2<f32>)> + 'a {
+    polygon.iter().zip(polygon.iter().cycle().skip(1)).map(|(a, b)| (*a, *b))
+}
+
+pub fn circle_intersects_polygon(center: Vector2<f32>, radius: f32, polygon: &[Vector2<f32>]) -> bool {
+    polygon_contains(polygon, center)
+        || edges(polygon).any(|(a, b)| segment_distance(a, b, center) <= radius)
+}
+
+fn segments_intersect(a: Vector2<f32>, b: Vector2<f32>, c: Vector2<f32>, d: Vector2<f32>) -> bool {
+    let cross = |u: Vector2<f32>, v: Vector2<f32>| u.x * v.y - u.y * v.x;
+    let (r, s) = (b - a, d - c);
+    let denominator = cross(r, s);
+    if denominator == 0.0 {
+        return false;
+    }
+    let t = cross(c - a, s) / denominator;
+    let u = cross(c - a, r) / denominator;
+    t >= 0.0 && t <= 1.0 && u >= 0.0 && u <= 1.0
+}
+
+pub fn polygons_intersect(a: &[Vector2<f32>], b: &[Vector2<f32>]) -> bool {
+    if a.is_empty() || b.is_empty() {
+        return false;
+    }
+    polygon_contains(a, b[0]) || polygon_contains(b, a[0])
+        || edges(a).any(|(p, q)| edges(b).any(|(r, s)| segments_intersect(p, q, r, s)))
+}
+
+// unit normal of the polygon edge closest to the point, pointing out of
+// the polygon
+pub fn closest_edge_normal(polygon: &[Vector2<f32>], point: Vector2<f32>) -> Vector2<f32> {
+    let closest = edges(polygon)
+        .filter(|(a, b)| a != b)
+        .min_by(|(a, b), (c, d)| segment_distance(*a, *b, point)
+                .partial_cmp(&segment_distance(*c, *d, point))
+                .unwrap_or(std::cmp::Ordering::Equal));
+    let (a, b) = match closest {
+        Some(edge) => edge,
+        None => return Vector2::x(),
+    };
+    let edge = b - a;
+    let mut normal = Vector2::new(-edge.y, edge.x).normalize();
+    if (point - a).dot(&normal) < 0.0 {
+        normal = -normal;
+    }
+    // a point inside lies on the inner side of the edge
+    if polygon_contains(polygon, point) {
+        normal = -normal;
+    }
+    normal
+}
@@ -0,0 +1,191 @@
+use std::f32::consts::PI;
+
+use amethyst::{
+    core::{
+        math::{Point3, Vector2},
+        transform::components::Transform,
+        timing::Time,
+    },
+    derive::{SystemDesc},
+    ecs::{Join,
+          ReadStorage,
+          System, SystemData,
+          Read, Write, WriteExpect,
+          Entities,
+          World, WorldExt},
+    ecs::prelude::{Builder, Component, DenseVecStorage},
+    renderer::{debug_drawing::DebugLines, palette::Srgba},
+    shrev::EventChannel,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::components::{Physical, Ship};
+use crate::pool::{EntityPools};
+use crate::resources::{Arena};
+use crate::system::{GameEvent};
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum HazardKind {
+    // pulls everything towards it
+    GravityWell,
+    // pulls and destroys what crosses the event horizon
+    BlackHole,
+}
+
+// a hazard as written in the arena or level files
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct HazardConfig {
+    pub kind: HazardKind,
+    // fraction of the arena size, (0.5, 0.5) is the center
+    pub position: (f32, f32),
+    // acceleration at distance 1, falls off with the square of the distance
+    pub strength: f32,
+    // radius of the event horizon, only used by black holes
+    #[serde(default)]
+    pub horizon: f32,
+}
+
+pub struct Hazard {
+    pub kind: HazardKind,
+    pub strength: f32,
+    pub horizon: f32,
+}
+
+impl Component for Hazard {
+    type Storage = DenseVecStorage<Self>;
+}
+
+// the pull never grows beyond the one at this distance, so that objects
+// passing close by are not flung away
+pub const SOFTENING: f32 = 10.0;
+
+// inverse square acceleration of a hazard at `center` on a body at `position`
+pub fn gravity(position: Vector2<f32>, center: Vector2<f32>, strength: f32) -> Vector2<f32> {
+    let offset = center - position;
+    let distance = offset.norm();
+    if distance == 0.0 {
+        return Vector2::new(0.0, 0.0);
+    }
+    let distance_sq = (distance * distance).max(SOFTENING * SOFTENING);
+    offset / distance * (strength / distance_sq)
+}
+
+// create the hazard entities of an arena layout
+pub fn spawn_hazards(world: &mut World, hazards: &[HazardConfig]) {
+    let arena = *world.read_resource::<Arena>();
+    for config in hazards {
+        let mut transform = Transform::default();
+        transform.set_translation_xyz(config.position.0 * arena.width,
+                                      config.position.1 * arena.height, 0.0);
+        world
+            .create_entity()
+            .with(transform)
+            .with(Hazard {
+                kind: config.kind,
+                strength: config.strength,
+                horizon: if config.kind == HazardKind::BlackHole { config.horizon } else { 0.0 },
+            })
+            .build();
+    }
+}
+
+fn position(transform: &Transform) -> Vector2<f32> {
+    Vector2::new(transform.translation().x, transform.translation().y)
+}
+
+// black holes destroy everything inside the event horizon, a ship is
+// reported as destroyed and handled like any other death
+#[derive(SystemDesc)]
+pub struct HazardSystem;
+
+impl<'s> System<'s> for HazardSystem {
+    type SystemData = (
+        Entities<'s>,
+        ReadStorage<'s, Hazard>,
+        ReadStorage<'s, Physical>,
+        ReadStorage<'s, Ship>,
+        ReadStorage<'s, Transform>,
+        Write<'s, EntityPools>,
+        Write<'s, EventChannel<GameEvent>>,
+    );
+
+    fn run(&mut self,
+           (entities,
+            hazards,
+            physicals,
+            ships,
+            transforms,
+            mut pools,
+            mut game_events): Self::SystemData) {
+        for (hazard, hazard_transform) in (&hazards, &transforms).join() {
+            if hazard.kind != HazardKind::BlackHole {
+                continue;
+            }
+            let center = position(hazard_transform);
+            for (e, _, transform) in (&*entities, &physicals, &transforms).join() {
+                let p = position(transform);
+                if (p - center).norm() > hazard.horizon {
+                    continue;
+                }
+                if ships.contains(e) {
+                    game_events.single_write(GameEvent::ShipDestroyed { position: p });
+                } else {
+                    pools.release(e);
+                }
+            }
+        }
+    }
+}
+
+const COLOR_WELL: [f32; 4] = [0.4, 0.7, 1.0, 0.8];
+const COLOR_HOLE: [f32; 4] = [0.6, 0.1, 0.8, 1.0];
+const RING_SEGMENTS: usize = 24;
+
+pub fn draw_circle(lines: &mut DebugLines, center: Vector2<f32>, radius: f32, [r, g, b, a]: [f32; 4]) {
+    let point = |i: usize| {
+        let angle = i as f32 * 2.0 * PI / RING_SEGMENTS as f32;
+        Point3::new(center.x + angle.cos() * radius, center.y + angle.sin() * radius, -0.2)
+    };
+    for i in 0..RING_SEGMENTS {
+        lines.draw_line(point(i), point(i + 1), Srgba::new(r, g, b, a));
+    }
+}
+
+// hazards have no sprites, they are drawn as rings pulsing inwards
+#[derive(SystemDesc)]
+pub struct HazardRenderSystem;
+
+impl<'s> System<'s> for HazardRenderSystem {
+    type SystemData = (
+        ReadStorage<'s, Hazard>,
+        ReadStorage<'s, Transform>,
+        Read<'s, Time>,
+        WriteExpect<'s, DebugLines>,
+    );
+
+    fn run(&mut self,
+           (hazards,
+            transforms,
+            time,
+            mut lines): Self::SystemData) {
+        let phase = (time.absolute_time_seconds() as f32 * 0.5).fract();
+        for (hazard, transform) in (&hazards, &transforms).join() {
+            let center = position(transform);
+            let (color, inner) = match hazard.kind {
+                HazardKind::GravityWell => (COLOR_WELL, SOFTENING),
+                HazardKind::BlackHole => (COLOR_HOLE, hazard.horizon),
+            };
+            if hazard.kind == HazardKind::BlackHole {
+                draw_circle(&mut lines, center, hazard.horizon, color);
+            }
+            // three rings moving towards the center
+            for i in 0..3 {
+                let t = 1.0 - ((phase + i as f32 / 3.0) % 1.0);
+                let radius = inner + t * 3.0 * SOFTENING;
+                let [r, g, b, a] = color;
+                draw_circle(&mut lines, center, radius, [r, g, b, a * (1.0 - t)]);
+            }
+        }
+    }
+}
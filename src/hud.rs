@@ -0,0 +1,307 @@
+use amethyst::{
+    core::{timing::Time, Parent},
+    ecs::{Join,
+          ReadStorage, WriteStorage,
+          System, SystemData,
+          Read, Write,
+          Entities,
+          World, WorldExt},
+    ecs::prelude::{Builder, Entity},
+    shrev::{EventChannel, ReaderId},
+    ui::{Anchor, UiImage, UiText, UiTransform},
+    utils::fps_counter::FpsCounter,
+};
+use log::{error};
+
+use crate::components::{AutoPilot};
+use crate::pool::{EntityPools};
+use crate::resources::{FontRes, Viewport};
+use crate::settings::{Settings};
+use crate::textures::{SpriteRegistry};
+use crate::weapons::{Weapons};
+
+const COLOR_TEXT: [f32; 4] = [0., 0., 0., 1.];
+
+// the count-up covers this fraction of the remaining difference per second
+const COUNT_UP_RATE: f32 = 8.0;
+// but never less than this many points per second
+const COUNT_UP_MIN: f32 = 20.0;
+
+const LIFE_ICON_SIZE: f32 = 32.0;
+
+// changes the HUD shows, written by the gameplay systems and states
+#[derive(Clone, Debug, PartialEq)]
+pub enum HudEvent {
+    Score(i32),
+    // the combo multiplier, 1 is not shown
+    Multiplier(u32),
+    Lives(u32),
+    Wave(u32),
+    // a power-up became active or was refreshed, seconds left
+    PowerUp { name: String, remaining: f32 },
+    PowerUpEnded { name: String },
+}
+
+// 1234567 -> "1,234,567"
+pub fn format_score(score: i32) -> String {
+    let digits = score.abs().to_string();
+    let mut result = String::new();
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i) % 3 == 0 {
+            result.push(',');
+        }
+        result.push(c);
+    }
+    if score < 0 {
+        result.insert(0, '-');
+    }
+    result
+}
+
+// move the shown score towards the real one, fast for big differences
+pub fn count_up(displayed: f32, target: i32, delta: f32) -> f32 {
+    let diff = target as f32 - displayed;
+    let step = (diff.abs() * COUNT_UP_RATE).max(COUNT_UP_MIN) * delta;
+    if diff.abs() <= step {
+        target as f32
+    } else {
+        displayed + step * diff.signum()
+    }
+}
+
+struct HudEntities {
+    score: Entity,
+    multiplier: Entity,
+    wave: Entity,
+    power_ups: Entity,
+    weapon: Entity,
+    lives: Vec<Entity>,
+}
+
+// the in-game HUD, shown by StatePlay and removed with the play field
+#[derive(Default)]
+pub struct Hud {
+    entities: Option<HudEntities>,
+    score: i32,
+    displayed_score: f32,
+    multiplier: u32,
+    lives: u32,
+    wave: u32,
+    // name and seconds left
+    power_ups: Vec<(String, f32)>,
+}
+
+fn create_text(world: &mut World, id: &str, anchor: Anchor, x: f32, y: f32, size: f32) -> Entity {
+    let font = world.read_resource::<FontRes>().font();
+    let parent = world.read_resource::<Viewport>().hud;
+    let transform = UiTransform::new(id.to_string(), anchor, anchor, x, y, 1., 400., size);
+    let mut builder = world
+        .create_entity()
+        .with(transform)
+        .with(UiText::new(font, "".to_string(), COLOR_TEXT, size));
+    if let Some(parent) = parent {
+        builder = builder.with(Parent { entity: parent });
+    }
+    builder.build()
+}
+
+impl Hud {
+    pub fn show(world: &mut World) {
+        Self::hide(world);
+        let entities = HudEntities {
+            score: create_text(world, "hud_score", Anchor::TopRight, -20., -20., 50.),
+            multiplier: create_text(world, "hud_multiplier", Anchor::TopRight, -20., -75., 30.),
+            wave: create_text(world, "hud_wave", Anchor::TopLeft, 20., -20., 30.),
+            power_ups: create_text(world, "hud_power_ups", Anchor::TopLeft, 20., -100., 25.),
+            weapon: create_text(world, "hud_weapon", Anchor::TopLeft, 20., -130., 25.),
+            lives: vec![],
+        };
+        *world.write_resource::<Hud>() = Hud {
+            entities: Some(entities),
+            ..Default::default()
+        };
+    }
+
+    pub fn hide(world: &mut World) {
+        let entities = match world.write_resource::<Hud>().entities.take() {
+            Some(entities) => entities,
+            None => return,
+        };
+        let mut all = vec![entities.score, entities.multiplier, entities.wave, entities.power_ups,
+                           entities.weapon];
+        all.extend(entities.lives);
+        if let Err(e) = world.delete_entities(&all) {
+            error!("Failed to destroy HUD: {}", e);
+        }
+    }
+
+    fn handle(&mut self, event: &HudEvent) {
+        match event {
+            HudEvent::Score(score) => self.score = *score,
+            HudEvent::Multiplier(multiplier) => self.multiplier = *multiplier,
+            HudEvent::Lives(lives) => self.lives = *lives,
+            HudEvent::Wave(wave) => self.wave = *wave,
+            HudEvent::PowerUp { name, remaining } => {
+                match self.power_ups.iter_mut().find(|(n, _)| n == name) {
+                    Some(power_up) => power_up.1 = *remaining,
+                    None => self.power_ups.push((name.clone(), *remaining)),
+                }
+            },
+            HudEvent::PowerUpEnded { name } => self.power_ups.retain(|(n, _)| n != name),
+        }
+    }
+}
+
+// apply HUD events to the HUD and the optional debug overlay
+#[derive(Default)]
+pub struct HudSystem {
+    event_reader: Option<ReaderId<HudEvent>>,
+    debug_text: Option<Entity>,
+}
+
+impl<'s> System<'s> for HudSystem {
+    type SystemData = (
+        Entities<'s>,
+        Write<'s, Hud>,
+        Read<'s, EventChannel<HudEvent>>,
+        Option<Read<'s, FontRes>>,
+        Option<Read<'s, SpriteRegistry>>,
+        Read<'s, Settings>,
+        Read<'s, FpsCounter>,
+        Option<Read<'s, EntityPools>>,
+        Read<'s, Viewport>,
+        Read<'s, Time>,
+        ReadStorage<'s, Weapons>,
+        ReadStorage<'s, AutoPilot>,
+        WriteStorage<'s, UiText>,
+        WriteStorage<'s, UiTransform>,
+        WriteStorage<'s, UiImage>,
+        WriteStorage<'s, Parent>,
+    );
+
+    fn setup(&mut self, world: &mut World) {
+        Self::SystemData::setup(world);
+        self.event_reader = Some(
+            world
+                .fetch_mut::<EventChannel<HudEvent>>()
+                .register_reader()
+        )
+    }
+
+    fn run(&mut self,
+           (entities,
+            mut hud,
+            events,
+            font,
+            sprites,
+            settings,
+            fps,
+            pools,
+            viewport,
+            time,
+            weapons,
+            autopilots,
+            mut texts,
+            mut ui_transforms,
+            mut ui_images,
+            mut parents): Self::SystemData) {
+        for event in events.read(self.event_reader.as_mut().unwrap()) {
+            hud.handle(event);
+        }
+
+        let delta = time.delta_seconds();
+        hud.displayed_score = count_up(hud.displayed_score, hud.score, delta);
+        for power_up in hud.power_ups.iter_mut() {
+            power_up.1 = (power_up.1 - delta).max(0.0);
+        }
+        hud.power_ups.retain(|(_, remaining)| *remaining > 0.0);
+
+        let hud = &mut *hud;
+        if let Some(hud_entities) = hud.entities.as_mut() {
+            if let Some(text) = texts.get_mut(hud_entities.score) {
+                text.text = format_score(hud.displayed_score.round() as i32);
+            }
+            if let Some(text) = texts.get_mut(hud_entities.multiplier) {
+                text.text = if hud.multiplier > 1 {
+                    format!("x{}", hud.multiplier)
+                } else {
+                    "".to_string()
+                };
+            }
+            if let Some(text) = texts.get_mut(hud_entities.wave) {
+                text.text = format!("Wave {}", hud.wave);
+            }
+            if let Some(text) = texts.get_mut(hud_entities.power_ups) {
+                text.text = hud.power_ups.iter()
+                    .map(|(name, remaining)| format!("{} {:.1}s", name, remaining))
+                    .collect::<Vec<_>>()
+                    .join("  ");
+            }
+            // the selected weapon of the player, not of a ship on autopilot
+            if let Some(text) = texts.get_mut(hud_entities.weapon) {
+                let name = (&weapons, !&autopilots).join()
+                    .next()
+                    .and_then(|(weapons, _)| weapons.selected())
+                    .map_or("", |weapon| weapon.name());
+                if text.text != name {
+                    text.text = name.to_string();
+                }
+            }
+
+            // one ship icon per life
+            let lives = hud.lives as usize;
+            for e in hud_entities.lives.split_off(lives.min(hud_entities.lives.len())) {
+                if let Err(e) = entities.delete(e) {
+                    error!("Failed to destroy life icon: {}", e);
+                }
+            }
+            if let Some(sprites) = sprites.as_ref() {
+                while hud_entities.lives.len() < lives {
+                    let i = hud_entities.lives.len();
+                    let e = entities.create();
+                    let result = ui_transforms.insert(e, UiTransform::new(
+                        format!("hud_life_{}", i), Anchor::TopLeft, Anchor::TopLeft,
+                        20. + (LIFE_ICON_SIZE + 8.) * i as f32, -55., 1.,
+                        LIFE_ICON_SIZE, LIFE_ICON_SIZE))
+                        .and(ui_images.insert(e, UiImage::Sprite(sprites.sprite_render("ship"))))
+                        .and(viewport.hud.map_or(Ok(None), |parent| {
+                            parents.insert(e, Parent { entity: parent })
+                        }));
+                    if let Err(e) = result {
+                        error!("Failed to create life icon: {}", e);
+                    }
+                    hud_entities.lives.push(e);
+                }
+            }
+        }
+
+        // frame rate, entity count and pool use in the bottom left corner
+        if settings.debug_overlay {
+            if let Some(font) = font.as_ref() {
+                let debug_text = *self.debug_text.get_or_insert_with(|| {
+                    let e = entities.create();
+                    let result = ui_transforms.insert(e, UiTransform::new(
+                        "debug_overlay".to_string(), Anchor::BottomLeft, Anchor::BottomLeft,
+                        10., 10., 5., 600., 25.))
+                        .and(texts.insert(e, UiText::new(font.font(), "".to_string(), COLOR_TEXT, 25.)));
+                    if let Err(e) = result {
+                        error!("Failed to create debug overlay: {}", e);
+                    }
+                    e
+                });
+                if let Some(text) = texts.get_mut(debug_text) {
+                    text.text = format!("FPS {:.0}  Entities {}",
+                                        fps.sampled_fps(), (&*entities).join().count());
+                    if let Some(pools) = pools.as_ref() {
+                        let total = pools.total();
+                        text.text += &format!("  Pool hits {} misses {}", total.hits, total.misses);
+                    }
+                }
+            }
+        } else if let Some(debug_text) = self.debug_text.take() {
+            if let Err(e) = entities.delete(debug_text) {
+                error!("Failed to destroy debug overlay: {}", e);
+            }
+        }
+    }
+}
@@ -0,0 +1,1123 @@
+use std::{
+    fmt, fs,
+    path::{Path, PathBuf},
+};
+
+use amethyst::{
+    core::{
+        math::Vector2,
+        transform::components::Transform,
+    },
+    derive::{SystemDesc},
+    ecs::{Join,
+          ReadStorage,
+          System, SystemData,
+          Read, Write, ReadExpect, WriteExpect,
+          Entities, LazyUpdate,
+          World, WorldExt},
+    ecs::prelude::{Builder, Component, DenseVecStorage},
+    prelude::{Trans, TransEvent, GameData, StateEvent},
+    renderer::debug_drawing::DebugLines,
+    shrev::EventChannel,
+    utils::application_root_dir,
+    window::ScreenDimensions,
+};
+use log::{warn};
+use serde::{Deserialize, Serialize};
+
+use crate::components::{Asteroid, AsteroidSize, Collider, ColliderShape, ColliderType};
+use crate::geometry;
+use crate::physics;
+use crate::hazards::{self, HazardConfig, HazardKind};
+use crate::resources::{Arena, ArenaConfig, RandomGen, fit_view};
+use crate::scoring::{Scoring};
+use crate::states;
+use crate::stats::{SessionStats};
+use crate::system::{create_asteroid};
+use crate::textures::{SpriteRegistry};
+use crate::vector::{self, Outline};
+
+// what happens to things leaving the arena over an edge
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum EdgeMode {
+    // come back on the opposite side, bullets are removed
+    Wrap,
+    // bounce back, bullets ricochet
+    Solid,
+    // bounce back, but the ship breaks on it
+    Damage,
+    // everything crossing it is destroyed
+    Kill,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Edges {
+    pub left: EdgeMode,
+    pub right: EdgeMode,
+    pub top: EdgeMode,
+    pub bottom: EdgeMode,
+    // of the bounces, 1 is elastic, 0 stops at the wall
+    pub restitution: f32,
+    // bounces of a bullet before it is removed
+    pub ricochets: u32,
+}
+
+impl Default for Edges {
+    fn default() -> Self {
+        Self {
+            left: EdgeMode::Wrap,
+            right: EdgeMode::Wrap,
+            top: EdgeMode::Wrap,
+            bottom: EdgeMode::Wrap,
+            restitution: 0.8,
+            ricochets: 2,
+        }
+    }
+}
+
+// the worst that happened at the edges, in increasing order
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+pub enum EdgeHit {
+    None,
+    Wrapped,
+    Bounced,
+    // bounced off a damaging wall
+    Damaged,
+    // crossed a kill zone
+    Killed,
+}
+
+// position and velocity after the edges were applied
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct EdgeResult {
+    pub position: Vector2<f32>,
+    pub velocity: Vector2<f32>,
+    pub hit: EdgeHit,
+}
+
+// an edge crossed on one axis: its mode, the position wrapped to the
+// other side, the position mirrored back inside and the inward normal
+fn crossed_edge(position: f32, size: f32, low: EdgeMode, high: EdgeMode) -> Option<(EdgeMode, f32, f32, f32)> {
+    if position < 0.0 {
+        Some((low, size - 0.5, -position, 1.0))
+    } else if position > size {
+        Some((high, 0.5, 2.0 * size - position, -1.0))
+    } else {
+        None
+    }
+}
+
+impl Edges {
+    // whether things come back on the other side, horizontally and vertically
+    pub fn wraps(&self) -> (bool, bool) {
+        (self.left == EdgeMode::Wrap && self.right == EdgeMode::Wrap,
+         self.bottom == EdgeMode::Wrap && self.top == EdgeMode::Wrap)
+    }
+
+    // whether a bullet that ricocheted this many times so far bounces off
+    // the edge it hit, otherwise it is removed
+    pub fn ricochet(&self, hit: EdgeHit, ricochets: u32) -> bool {
+        (hit == EdgeHit::Bounced || hit == EdgeHit::Damaged) && ricochets < self.ricochets
+    }
+
+    // wrap, bounce or destroy something that left the arena of the given
+    // size, in a corner both edges apply
+    pub fn apply(&self, position: Vector2<f32>, velocity: Vector2<f32>,
+                 width: f32, height: f32) -> EdgeResult {
+        let mut result = EdgeResult { position, velocity, hit: EdgeHit::None };
+        let sizes = [width, height];
+        let crossed = [
+            crossed_edge(position.x, width, self.left, self.right),
+            crossed_edge(position.y, height, self.bottom, self.top),
+        ];
+        for (axis, crossed) in crossed.iter().enumerate() {
+            let (mode, wrapped, mirrored, direction) = match crossed {
+                Some(crossed) => *crossed,
+                None => continue,
+            };
+            let hit = match mode {
+                EdgeMode::Wrap => {
+                    result.position[axis] = wrapped;
+                    EdgeHit::Wrapped
+                },
+                EdgeMode::Solid | EdgeMode::Damage => {
+                    result.position[axis] = mirrored.max(0.0).min(sizes[axis]);
+                    let mut normal = Vector2::new(0.0, 0.0);
+                    normal[axis] = direction;
+                    result.velocity = physics::reflect(result.velocity, normal, self.restitution);
+                    if mode == EdgeMode::Solid { EdgeHit::Bounced } else { EdgeHit::Damaged }
+                },
+                EdgeMode::Kill => EdgeHit::Killed,
+            };
+            if hit > result.hit {
+                result.hit = hit;
+            }
+        }
+        result
+    }
+}
+
+// a static wall, the ship breaks on it and asteroids bounce off
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ObstacleConfig {
+    // fraction of the arena size
+    pub position: (f32, f32),
+    // outline around the position in arena units
+    pub points: Vec<(f32, f32)>,
+    // degrees, counter-clockwise
+    #[serde(default)]
+    pub rotation: f32,
+}
+
+// an asteroid present from the start
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AsteroidConfig {
+    pub size: AsteroidSize,
+    // fraction of the arena size
+    pub position: (f32, f32),
+    // units / s
+    #[serde(default)]
+    pub velocity: (f32, f32),
+    // rad / s
+    #[serde(default)]
+    pub rotation: f32,
+}
+
+// the random asteroids appearing during the game
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SpawnRules {
+    // off leaves only the asteroids of the level
+    pub enabled: bool,
+    // seconds between two asteroids on Normal, plus up to one random second
+    pub interval: f32,
+    // the fastest asteroid on Normal (units / s)
+    pub max_velocity: f32,
+    // nothing spawns while this many asteroids are left, 0 is unlimited
+    pub max_asteroids: u32,
+}
+
+impl Default for SpawnRules {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            interval: 0.5,
+            max_velocity: 60.0,
+            max_asteroids: 0,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum WinCondition {
+    // play until the last ship is lost
+    Endless,
+    Score(i32),
+    // destroy every asteroid, spawning should be off
+    ClearAsteroids,
+    // seconds to stay alive
+    Survive(f32),
+}
+
+impl WinCondition {
+    pub fn is_met(self, score: i32, time: f32, asteroids_left: usize, asteroids_destroyed: u32) -> bool {
+        match self {
+            WinCondition::Endless => false,
+            WinCondition::Score(goal) => score >= goal,
+            WinCondition::ClearAsteroids => asteroids_left == 0 && asteroids_destroyed > 0,
+            WinCondition::Survive(seconds) => time >= seconds,
+        }
+    }
+}
+
+// an arena layout as stored in levels/<name>.ron, the running one is a
+// resource; a game without a level file plays the endless arena
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Level {
+    pub name: String,
+    pub width: f32,
+    pub height: f32,
+    pub edges: Edges,
+    pub obstacles: Vec<ObstacleConfig>,
+    pub hazards: Vec<HazardConfig>,
+    pub asteroids: Vec<AsteroidConfig>,
+    pub spawn: SpawnRules,
+    pub win: WinCondition,
+}
+
+// room kept free around the spawn point in the center of the arena, where
+// the ship starts and respawns
+const SPAWN_CLEARANCE: f32 = 20.0;
+
+impl Default for Level {
+    fn default() -> Self {
+        Self {
+            name: "Endless".to_string(),
+            width: 300.0,
+            height: 300.0,
+            edges: Edges::default(),
+            obstacles: vec![],
+            hazards: vec![],
+            asteroids: vec![],
+            spawn: SpawnRules::default(),
+            win: WinCondition::Endless,
+        }
+    }
+}
+
+// where a value is in a level file, e.g. obstacles[2].position
+#[derive(Clone, Debug, PartialEq)]
+pub enum PathPart {
+    Field(String),
+    // of a list or a tuple
+    Index(usize),
+}
+
+fn field(name: &str) -> PathPart {
+    PathPart::Field(name.to_string())
+}
+
+fn format_path(path: &[PathPart]) -> String {
+    let mut result = String::new();
+    for part in path {
+        match part {
+            PathPart::Field(name) => {
+                if !result.is_empty() {
+                    result.push('.');
+                }
+                result.push_str(name);
+            },
+            PathPart::Index(i) => result.push_str(&format!("[{}]", i)),
+        }
+    }
+    result
+}
+
+// a value the file format accepts but the game cannot use
+#[derive(Clone, Debug, PartialEq)]
+pub struct Invalid {
+    pub path: Vec<PathPart>,
+    pub message: String,
+}
+
+impl Invalid {
+    fn new(path: Vec<PathPart>, message: &str) -> Self {
+        Self {
+            path,
+            message: message.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for Invalid {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.path.is_empty() {
+            write!(f, "{}", self.message)
+        } else {
+            write!(f, "{}: {}", format_path(&self.path), self.message)
+        }
+    }
+}
+
+// a level file that could not be read, with the position of the problem
+// when it is known
+#[derive(Clone, Debug)]
+pub struct LevelError {
+    pub file: String,
+    // line and column, counted from 1
+    pub position: Option<(usize, usize)>,
+    pub message: String,
+}
+
+impl LevelError {
+    fn new(file: &str, position: Option<(usize, usize)>, message: String) -> Self {
+        Self {
+            file: file.to_string(),
+            position,
+            message,
+        }
+    }
+
+    // the messages of serde carry no position, it is found from the path
+    // to the value that failed
+    fn from_ron(file: &str, source: &str, mut path: Vec<PathPart>, error: ron::de::Error) -> Self {
+        match error {
+            ron::de::Error::Parser(code, position) => Self::new(
+                file, Some((position.line, position.col)), describe(&format!("{:?}", code))),
+            ron::de::Error::Message(message) => {
+                // point at the unknown name itself, not at its struct
+                if message.starts_with("unknown field") {
+                    if let Some(name) = message.split('`').nth(1).filter(|name| !name.is_empty()) {
+                        if path.last() != Some(&field(name)) {
+                            path.push(field(name));
+                        }
+                    }
+                }
+                Self::from_invalid(file, source, Invalid { path, message })
+            },
+            ron::de::Error::IoError(message) => Self::new(file, None, message),
+        }
+    }
+
+    fn from_invalid(file: &str, source: &str, invalid: Invalid) -> Self {
+        Self::new(file, locate(source, &invalid.path), invalid.to_string())
+    }
+}
+
+impl fmt::Display for LevelError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.position {
+            Some((line, column)) => write!(f, "{}:{}:{}: {}", self.file, line, column, self.message),
+            None => write!(f, "{}: {}", self.file, self.message),
+        }
+    }
+}
+
+impl std::error::Error for LevelError {}
+
+// ExpectedComma -> "expected comma"
+fn describe(code: &str) -> String {
+    if code == "Eof" {
+        return "unexpected end of file".to_string();
+    }
+    let mut words = String::new();
+    for (i, c) in code.chars().enumerate() {
+        if c.is_uppercase() {
+            if i > 0 {
+                words.push(' ');
+            }
+            words.extend(c.to_lowercase());
+        } else {
+            words.push(c);
+        }
+    }
+    words
+}
+
+fn path_of(path: &serde_path_to_error::Path) -> Vec<PathPart> {
+    let mut parts = vec![];
+    for segment in path.iter() {
+        match segment {
+            serde_path_to_error::Segment::Seq { index } => parts.push(PathPart::Index(*index)),
+            serde_path_to_error::Segment::Map { key } => parts.push(field(key)),
+            serde_path_to_error::Segment::Enum { variant } => parts.push(field(variant)),
+            serde_path_to_error::Segment::Unknown => break,
+        }
+    }
+    parts
+}
+
+// a small walk over the RON text, only as far as needed to find a value
+// again; strings and comments are skipped so that brackets in them do not
+// count
+
+fn skip_space(bytes: &[u8], mut i: usize) -> usize {
+    while i < bytes.len() {
+        if bytes[i].is_ascii_whitespace() {
+            i += 1;
+        } else if bytes[i..].starts_with(b"//") {
+            while i < bytes.len() && bytes[i] != b'\n' {
+                i += 1;
+            }
+        } else if bytes[i..].starts_with(b"/*") {
+            i += 2;
+            while i < bytes.len() && !bytes[i..].starts_with(b"*/") {
+                i += 1;
+            }
+            i = (i + 2).min(bytes.len());
+        } else {
+            break;
+        }
+    }
+    i
+}
+
+fn is_word(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || byte == b'_'
+}
+
+// past the string or character starting at i
+fn skip_quoted(bytes: &[u8], i: usize) -> usize {
+    let quote = bytes[i];
+    let mut i = i + 1;
+    while i < bytes.len() && bytes[i] != quote {
+        if bytes[i] == b'\\' {
+            i += 1;
+        }
+        i += 1;
+    }
+    (i + 1).min(bytes.len())
+}
+
+// the end of the list entry or field starting at i: the comma after it or
+// the closing bracket of its container
+fn skip_entry(bytes: &[u8], mut i: usize) -> usize {
+    let mut depth = 0;
+    while i < bytes.len() {
+        i = skip_space(bytes, i);
+        if i >= bytes.len() {
+            break;
+        }
+        match bytes[i] {
+            b'"' | b'\'' => {
+                i = skip_quoted(bytes, i);
+                continue;
+            },
+            b'(' | b'[' | b'{' => depth += 1,
+            b')' | b']' | b'}' if depth == 0 => return i,
+            b')' | b']' | b'}' => depth -= 1,
+            b',' if depth == 0 => return i,
+            _ => {},
+        }
+        i += 1;
+    }
+    i
+}
+
+// inside the struct, list or tuple starting at i, a struct or variant
+// name before the bracket is skipped
+fn enter(bytes: &[u8], i: usize) -> Option<usize> {
+    let mut i = skip_space(bytes, i);
+    while i < bytes.len() && is_word(bytes[i]) {
+        i += 1;
+    }
+    i = skip_space(bytes, i);
+    match bytes.get(i) {
+        Some(b'(') | Some(b'[') | Some(b'{') => Some(i + 1),
+        _ => None,
+    }
+}
+
+// the start of each entry of the container entered at i
+fn entries(bytes: &[u8], mut i: usize) -> Vec<usize> {
+    let mut starts = vec![];
+    loop {
+        i = skip_space(bytes, i);
+        match bytes.get(i) {
+            None | Some(b')') | Some(b']') | Some(b'}') => return starts,
+            _ => {},
+        }
+        starts.push(i);
+        i = skip_entry(bytes, i);
+        if bytes.get(i) != Some(&b',') {
+            return starts;
+        }
+        i += 1;
+    }
+}
+
+// the key and the value of a field of the struct entered at i
+fn find_field(bytes: &[u8], i: usize, name: &str) -> Option<(usize, usize)> {
+    entries(bytes, i).into_iter().find_map(|start| {
+        let end = start + name.len();
+        let is_name = bytes[start..].starts_with(name.as_bytes())
+            && !bytes.get(end).map_or(false, |&b| is_word(b));
+        let colon = skip_space(bytes, end);
+        if is_name && bytes.get(colon) == Some(&b':') {
+            Some((start, skip_space(bytes, colon + 1)))
+        } else {
+            None
+        }
+    })
+}
+
+// line and column of the value at the path, or of the closest enclosing
+// value that could be found
+fn locate(source: &str, path: &[PathPart]) -> Option<(usize, usize)> {
+    let bytes = source.as_bytes();
+    let mut value = skip_space(bytes, 0);
+    let mut found = value;
+    for part in path {
+        let inside = match enter(bytes, value) {
+            Some(inside) => inside,
+            None => break,
+        };
+        let next = match part {
+            PathPart::Field(name) => find_field(bytes, inside, name),
+            PathPart::Index(n) => entries(bytes, inside).get(*n).map(|&start| (start, start)),
+        };
+        match next {
+            Some((key, start)) => {
+                found = key;
+                value = start;
+            },
+            None => break,
+        }
+    }
+    if found >= bytes.len() {
+        return None;
+    }
+    let before = &source[..found];
+    let line = before.matches('\n').count() + 1;
+    let column = before.rsplit('\n').next().unwrap_or("").chars().count() + 1;
+    Some((line, column))
+}
+
+pub fn levels_dir() -> amethyst::Result<PathBuf> {
+    Ok(application_root_dir()?.join("levels"))
+}
+
+// names of the level files, sorted
+pub fn level_names() -> Vec<String> {
+    let entries = levels_dir().and_then(|dir| Ok(fs::read_dir(dir)?));
+    let entries = match entries {
+        Ok(entries) => entries,
+        Err(e) => {
+            warn!("Failed to list the levels: {}", e);
+            return vec![];
+        }
+    };
+    let mut names: Vec<String> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().map_or(false, |ext| ext == "ron"))
+        .filter_map(|path| path.file_stem().map(|stem| stem.to_string_lossy().into_owned()))
+        .collect();
+    names.sort();
+    names
+}
+
+impl Level {
+    // the arena of config/arena.ron without any level features
+    pub fn endless(config: &ArenaConfig) -> Self {
+        Self {
+            width: config.width,
+            height: config.height,
+            hazards: config.hazards.clone(),
+            edges: config.edges.clone(),
+            ..Default::default()
+        }
+    }
+
+    pub fn parse(file: &str, source: &str) -> Result<Self, LevelError> {
+        let error = |e| LevelError::from_ron(file, source, vec![], e);
+        let mut deserializer = ron::de::Deserializer::from_str(source).map_err(error)?;
+        // the path to a value that fails is kept to find it in the file
+        let level: Level = serde_path_to_error::deserialize(&mut deserializer)
+            .map_err(|e| {
+                let path = path_of(e.path());
+                LevelError::from_ron(file, source, path, e.into_inner())
+            })?;
+        deserializer.end().map_err(error)?;
+        level.validate().map_err(|invalid| LevelError::from_invalid(file, source, invalid))?;
+        Ok(level)
+    }
+
+    pub fn load(path: &Path) -> Result<Self, LevelError> {
+        let file = path.file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let source = fs::read_to_string(path)
+            .map_err(|e| LevelError::new(&file, None, e.to_string()))?;
+        Self::parse(&file, &source)
+    }
+
+    // load levels/<name>.ron
+    pub fn load_named(name: &str) -> Result<Self, LevelError> {
+        let file = format!("{}.ron", name);
+        let path = levels_dir().map_err(|e| LevelError::new(&file, None, e.to_string()))?;
+        Self::load(&path.join(&file))
+    }
+
+    pub fn save(&self, path: &Path) -> amethyst::Result<()> {
+        let content = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())?;
+        fs::write(path, content)?;
+        Ok(())
+    }
+
+    // values the file format accepts but the game cannot use
+    pub fn validate(&self) -> Result<(), Invalid> {
+        let in_arena = |(x, y): (f32, f32)| x >= 0.0 && x <= 1.0 && y >= 0.0 && y <= 1.0;
+        let entry = |list: &str, i: usize, name: &str| vec![field(list), PathPart::Index(i), field(name)];
+        let outside = |list: &str, i: usize| Err(Invalid::new(
+            entry(list, i, "position"), "the position is a fraction of the arena size, from 0 to 1"));
+        let spawn = Vector2::new(self.width * 0.5, self.height * 0.5);
+        let covers_spawn = |list: &str, i: usize| Err(Invalid::new(
+            entry(list, i, "position"), "too close to the center of the arena, where the ship spawns"));
+
+        if !(self.width > 0.0 && self.height > 0.0) {
+            let name = if self.width > 0.0 { "height" } else { "width" };
+            return Err(Invalid::new(vec![field(name)],
+                                    &format!("the arena size {}x{} is not positive", self.width, self.height)));
+        }
+        for (i, obstacle) in self.obstacles.iter().enumerate() {
+            if !in_arena(obstacle.position) {
+                return outside("obstacles", i);
+            }
+            if obstacle.points.len() < 3 {
+                return Err(Invalid::new(entry("obstacles", i, "points"), "an obstacle needs at least 3 points"));
+            }
+            let center = Vector2::new(obstacle.position.0 * self.width, obstacle.position.1 * self.height);
+            let (sin, cos) = obstacle.rotation.to_radians().sin_cos();
+            let polygon: Vec<Vector2<f32>> = obstacle.points.iter()
+                .map(|&(x, y)| center + Vector2::new(x * cos - y * sin, x * sin + y * cos))
+                .collect();
+            if geometry::circle_intersects_polygon(spawn, SPAWN_CLEARANCE, &polygon) {
+                return covers_spawn("obstacles", i);
+            }
+        }
+        for (i, hazard) in self.hazards.iter().enumerate() {
+            if !in_arena(hazard.position) {
+                return outside("hazards", i);
+            }
+            if hazard.kind == HazardKind::BlackHole && hazard.horizon <= 0.0 {
+                return Err(Invalid::new(entry("hazards", i, "horizon"), "a black hole needs a positive horizon"));
+            }
+            let center = Vector2::new(hazard.position.0 * self.width, hazard.position.1 * self.height);
+            if hazard.kind == HazardKind::BlackHole
+                && (center - spawn).norm() <= hazard.horizon + SPAWN_CLEARANCE {
+                return covers_spawn("hazards", i);
+            }
+        }
+        for (i, asteroid) in self.asteroids.iter().enumerate() {
+            if !in_arena(asteroid.position) {
+                return outside("asteroids", i);
+            }
+        }
+        if self.spawn.interval <= 0.0 {
+            return Err(Invalid::new(vec![field("spawn"), field("interval")], "the interval must be positive"));
+        }
+        match self.win {
+            WinCondition::Score(goal) if goal <= 0 =>
+                Err(Invalid::new(vec![field("win")], "the score to reach must be positive")),
+            WinCondition::Survive(seconds) if seconds <= 0.0 =>
+                Err(Invalid::new(vec![field("win")], "the time to survive must be positive")),
+            _ => Ok(()),
+        }
+    }
+
+    // the arena config with the size of this level
+    pub fn arena_config(&self, config: &ArenaConfig) -> ArenaConfig {
+        ArenaConfig {
+            width: self.width,
+            height: self.height,
+            ..config.clone()
+        }
+    }
+}
+
+// marks the static walls of a level
+pub struct Obstacle;
+
+impl Component for Obstacle {
+    type Storage = DenseVecStorage<Self>;
+}
+
+// make the level the running one and create its obstacles, hazards and
+// asteroids; the arena is resized at once so everything is placed in it
+pub fn build(world: &mut World, level: &Level) {
+    world.insert(level.clone());
+    let screen = {
+        let dimensions = world.read_resource::<ScreenDimensions>();
+        (dimensions.width(), dimensions.height())
+    };
+    let config = level.arena_config(&world.read_resource::<ArenaConfig>());
+    let (arena, _, _) = fit_view(screen, &config);
+    world.insert(arena);
+
+    hazards::spawn_hazards(world, &level.hazards);
+
+    for obstacle in &level.obstacles {
+        let points: Vec<Vector2<f32>> = obstacle.points.iter()
+            .map(|&(x, y)| Vector2::new(x, y))
+            .collect();
+        let mut transform = Transform::default();
+        transform.set_translation_xyz(obstacle.position.0 * arena.width,
+                                      obstacle.position.1 * arena.height, 0.0);
+        transform.set_rotation_2d(obstacle.rotation.to_radians());
+        world
+            .create_entity()
+            .with(transform)
+            .with(Obstacle)
+            .with(Collider {
+                typ: ColliderType::Obstacle,
+                shape: ColliderShape::Polygon(points.clone()),
+            })
+            .with(Outline::polygon(points))
+            .build();
+    }
+
+    world.exec(|(entities, sprites, rand, lazy):
+                (Entities, ReadExpect<SpriteRegistry>, ReadExpect<RandomGen>, Read<LazyUpdate>)| {
+        for asteroid in &level.asteroids {
+            create_asteroid(lazy.create_entity(&entities), &sprites, asteroid.size,
+                            Vector2::new(asteroid.position.0 * arena.width,
+                                         asteroid.position.1 * arena.height),
+                            Vector2::new(asteroid.velocity.0, asteroid.velocity.1),
+                            asteroid.rotation, rand.next_u32() as u64);
+        }
+    });
+}
+
+// end the game once the win condition of the level is met
+#[derive(Default)]
+pub struct LevelSystem {
+    complete: bool,
+}
+
+impl<'s> System<'s> for LevelSystem {
+    type SystemData = (
+        ReadExpect<'s, Level>,
+        ReadExpect<'s, Scoring>,
+        Read<'s, SessionStats>,
+        ReadStorage<'s, Asteroid>,
+        Write<'s, EventChannel<TransEvent<GameData<'static, 'static>, StateEvent>>>,
+    );
+
+    fn run(&mut self,
+           (level,
+            scoring,
+            stats,
+            asteroids,
+            mut trans_events): Self::SystemData) {
+        if self.complete {
+            return;
+        }
+        if level.win.is_met(scoring.score, stats.time_survived,
+                            asteroids.join().count(), stats.asteroids_total()) {
+            self.complete = true;
+            trans_events.single_write(Box::new(
+                || Trans::Switch(Box::new(states::StateOver::level_complete()))));
+        }
+    }
+}
+
+// obstacles have no sprites, without the vector renderer their outline
+// is drawn on top of the sprites
+#[derive(SystemDesc)]
+pub struct ObstacleRenderSystem;
+
+impl<'s> System<'s> for ObstacleRenderSystem {
+    type SystemData = (
+        ReadStorage<'s, Obstacle>,
+        ReadStorage<'s, Outline>,
+        ReadStorage<'s, Transform>,
+        WriteExpect<'s, DebugLines>,
+    );
+
+    fn run(&mut self,
+           (obstacles,
+            outlines,
+            transforms,
+            mut lines): Self::SystemData) {
+        for (_, outline, transform) in (&obstacles, &outlines, &transforms).join() {
+            let points = geometry::to_world(transform, &outline.points);
+            vector::draw_polyline(&mut lines, &points, outline.closed, outline.color);
+        }
+    }
+}
+
+const COLOR_SOLID: [f32; 4] = [0.9, 0.95, 1.0, 1.0];
+const COLOR_DAMAGE: [f32; 4] = [1.0, 0.6, 0.2, 1.0];
+const COLOR_KILL: [f32; 4] = [1.0, 0.15, 0.1, 1.0];
+
+// the edges that do not wrap are drawn as walls in their color
+#[derive(SystemDesc)]
+pub struct EdgeRenderSystem;
+
+impl<'s> System<'s> for EdgeRenderSystem {
+    type SystemData = (
+        ReadExpect<'s, Level>,
+        ReadExpect<'s, Arena>,
+        WriteExpect<'s, DebugLines>,
+    );
+
+    fn run(&mut self,
+           (level,
+            arena,
+            mut lines): Self::SystemData) {
+        let (w, h) = (arena.width, arena.height);
+        let edges = &level.edges;
+        let walls = [
+            (edges.left, Vector2::new(0.0, 0.0), Vector2::new(0.0, h)),
+            (edges.right, Vector2::new(w, 0.0), Vector2::new(w, h)),
+            (edges.bottom, Vector2::new(0.0, 0.0), Vector2::new(w, 0.0)),
+            (edges.top, Vector2::new(0.0, h), Vector2::new(w, h)),
+        ];
+        for (mode, a, b) in walls.iter() {
+            let color = match mode {
+                EdgeMode::Wrap => continue,
+                EdgeMode::Solid => COLOR_SOLID,
+                EdgeMode::Damage => COLOR_DAMAGE,
+                EdgeMode::Kill => COLOR_KILL,
+            };
+            vector::draw_polyline(&mut lines, &[*a, *b], false, color);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SIZE: f32 = 100.0;
+
+    fn all(mode: EdgeMode) -> Edges {
+        Edges {
+            left: mode,
+            right: mode,
+            top: mode,
+            bottom: mode,
+            ..Default::default()
+        }
+    }
+
+    fn close(a: Vector2<f32>, b: Vector2<f32>) -> bool {
+        (a - b).norm() < 1e-4
+    }
+
+    // something 5 units past each edge, moving out at 10 units / s: the
+    // position, the velocity, the position wrapped and the position mirrored
+    fn crossings() -> Vec<(Vector2<f32>, Vector2<f32>, Vector2<f32>, Vector2<f32>)> {
+        vec![
+            // left
+            (Vector2::new(-5.0, 50.0), Vector2::new(-10.0, 0.0),
+             Vector2::new(SIZE - 0.5, 50.0), Vector2::new(5.0, 50.0)),
+            // right
+            (Vector2::new(SIZE + 5.0, 50.0), Vector2::new(10.0, 0.0),
+             Vector2::new(0.5, 50.0), Vector2::new(SIZE - 5.0, 50.0)),
+            // bottom
+            (Vector2::new(50.0, -5.0), Vector2::new(0.0, -10.0),
+             Vector2::new(50.0, SIZE - 0.5), Vector2::new(50.0, 5.0)),
+            // top
+            (Vector2::new(50.0, SIZE + 5.0), Vector2::new(0.0, 10.0),
+             Vector2::new(50.0, 0.5), Vector2::new(50.0, SIZE - 5.0)),
+        ]
+    }
+
+    #[test]
+    fn inside_nothing_happens() {
+        for &mode in &[EdgeMode::Wrap, EdgeMode::Solid, EdgeMode::Damage, EdgeMode::Kill] {
+            let position = Vector2::new(SIZE, 0.0);
+            let velocity = Vector2::new(10.0, -10.0);
+            let result = all(mode).apply(position, velocity, SIZE, SIZE);
+            assert_eq!(result, EdgeResult { position, velocity, hit: EdgeHit::None });
+        }
+    }
+
+    #[test]
+    fn wrap_comes_back_on_the_other_side() {
+        for (position, velocity, wrapped, _) in crossings() {
+            let result = all(EdgeMode::Wrap).apply(position, velocity, SIZE, SIZE);
+            assert_eq!(result.hit, EdgeHit::Wrapped);
+            assert!(close(result.position, wrapped));
+            assert_eq!(result.velocity, velocity);
+        }
+    }
+
+    #[test]
+    fn solid_bounces_back() {
+        let edges = all(EdgeMode::Solid);
+        for (position, velocity, _, mirrored) in crossings() {
+            let result = edges.apply(position, velocity, SIZE, SIZE);
+            assert_eq!(result.hit, EdgeHit::Bounced);
+            assert!(close(result.position, mirrored));
+            assert!(close(result.velocity, -velocity * edges.restitution));
+        }
+    }
+
+    #[test]
+    fn damage_bounces_back_and_damages() {
+        let edges = all(EdgeMode::Damage);
+        for (position, velocity, _, mirrored) in crossings() {
+            let result = edges.apply(position, velocity, SIZE, SIZE);
+            assert_eq!(result.hit, EdgeHit::Damaged);
+            assert!(close(result.position, mirrored));
+            assert!(close(result.velocity, -velocity * edges.restitution));
+        }
+    }
+
+    #[test]
+    fn kill_leaves_everything_as_it_is() {
+        for (position, velocity, _, _) in crossings() {
+            let result = all(EdgeMode::Kill).apply(position, velocity, SIZE, SIZE);
+            assert_eq!(result, EdgeResult { position, velocity, hit: EdgeHit::Killed });
+        }
+    }
+
+    #[test]
+    fn corner_applies_both_edges() {
+        let edges = Edges { left: EdgeMode::Wrap, bottom: EdgeMode::Solid, ..all(EdgeMode::Wrap) };
+        let result = edges.apply(Vector2::new(-5.0, -5.0), Vector2::new(-10.0, -10.0), SIZE, SIZE);
+        assert_eq!(result.hit, EdgeHit::Bounced);
+        assert!(close(result.position, Vector2::new(SIZE - 0.5, 5.0)));
+        assert!(close(result.velocity, Vector2::new(-10.0, 10.0 * edges.restitution)));
+    }
+
+    #[test]
+    fn corner_reports_the_worst_edge() {
+        let edges = Edges { right: EdgeMode::Damage, top: EdgeMode::Kill, ..all(EdgeMode::Wrap) };
+        let result = edges.apply(Vector2::new(SIZE + 5.0, SIZE + 5.0), Vector2::new(10.0, 10.0), SIZE, SIZE);
+        assert_eq!(result.hit, EdgeHit::Killed);
+        // the wall still turned it around
+        assert!(result.velocity.x < 0.0);
+    }
+
+    #[test]
+    fn restitution_sets_the_bounce() {
+        let velocity = Vector2::new(-10.0, 3.0);
+        let position = Vector2::new(-5.0, 50.0);
+        let elastic = Edges { restitution: 1.0, ..all(EdgeMode::Solid) };
+        let result = elastic.apply(position, velocity, SIZE, SIZE);
+        assert!(close(result.velocity, Vector2::new(10.0, 3.0)));
+        let stopping = Edges { restitution: 0.0, ..all(EdgeMode::Solid) };
+        let result = stopping.apply(position, velocity, SIZE, SIZE);
+        assert!(close(result.velocity, Vector2::new(0.0, 3.0)));
+    }
+
+    #[test]
+    fn moving_back_in_keeps_the_velocity() {
+        let velocity = Vector2::new(10.0, 0.0);
+        let result = all(EdgeMode::Solid).apply(Vector2::new(-5.0, 50.0), velocity, SIZE, SIZE);
+        assert_eq!(result.velocity, velocity);
+        assert!(close(result.position, Vector2::new(5.0, 50.0)));
+    }
+
+    #[test]
+    fn bullets_run_out_of_ricochets() {
+        let edges = Edges { ricochets: 2, ..all(EdgeMode::Solid) };
+        assert!(edges.ricochet(EdgeHit::Bounced, 0));
+        assert!(edges.ricochet(EdgeHit::Damaged, 1));
+        assert!(!edges.ricochet(EdgeHit::Bounced, 2));
+        let none = Edges { ricochets: 0, ..all(EdgeMode::Solid) };
+        assert!(!none.ricochet(EdgeHit::Bounced, 0));
+        // wrapping and killing edges remove bullets at once
+        assert!(!edges.ricochet(EdgeHit::Wrapped, 0));
+        assert!(!edges.ricochet(EdgeHit::Killed, 0));
+    }
+
+    const LEVEL: &str = "(
+    name: \"Test\",
+    obstacles: [
+        (position: (0.8, 0.5), points: [(0.0, 0.0), (1.0, 0.0), (0.0, 1.0)]),
+        (position: (0.2, 0.2), points: [(0.0, 0.0), (1.0, 0.0), (0.0, 1.0)]),
+    ],
+    asteroids: [
+        (size: Small, position: (0.1, 0.1)),
+    ],
+)";
+
+    fn error(source: &str) -> LevelError {
+        Level::parse("test.ron", source).expect_err("the level should not load")
+    }
+
+    #[test]
+    fn valid_level_loads() {
+        let level = Level::parse("test.ron", LEVEL).unwrap();
+        assert_eq!(level.obstacles.len(), 2);
+        assert_eq!(level.asteroids.len(), 1);
+    }
+
+    #[test]
+    fn wrong_type_is_located() {
+        let e = error(&LEVEL.replace("(position: (0.2, 0.2)", "(position: (0.2, \"a\")"));
+        assert_eq!(e.position, Some((5, 26)));
+    }
+
+    #[test]
+    fn wrong_length_is_located_at_its_field() {
+        let e = error(&LEVEL.replace("(position: (0.2, 0.2)", "(position: (0.2)"));
+        assert_eq!(e.position, Some((5, 10)));
+        assert!(e.message.starts_with("obstacles[1].position: "), "{}", e.message);
+    }
+
+    #[test]
+    fn missing_field_is_located_at_its_entry() {
+        let e = error(&LEVEL.replace(
+            "(position: (0.2, 0.2), points: [(0.0, 0.0), (1.0, 0.0), (0.0, 1.0)])",
+            "(position: (0.2, 0.2))"));
+        assert_eq!(e.position, Some((5, 9)));
+        assert!(e.message.contains("missing field `points`"), "{}", e.message);
+    }
+
+    #[test]
+    fn unknown_field_is_located() {
+        let e = error(&LEVEL.replace("position: (0.1, 0.1))", "position: (0.1, 0.1), radius: 3.0)"));
+        assert_eq!(e.position, Some((8, 45)));
+        assert!(e.message.contains("unknown field `radius`"), "{}", e.message);
+    }
+
+    #[test]
+    fn invalid_position_is_located() {
+        let e = error(&LEVEL.replace("position: (0.1, 0.1)", "position: (1.5, 0.1)"));
+        assert_eq!(e.position, Some((8, 23)));
+        assert!(e.message.starts_with("asteroids[0].position: "), "{}", e.message);
+    }
+
+    #[test]
+    fn invalid_list_is_located() {
+        let e = error(&LEVEL.replace(
+            "(0.2, 0.2), points: [(0.0, 0.0), (1.0, 0.0), (0.0, 1.0)]",
+            "(0.2, 0.2), points: [(0.0, 0.0), (1.0, 0.0)]"));
+        assert_eq!(e.position, Some((5, 32)));
+        assert!(e.message.starts_with("obstacles[1].points: "), "{}", e.message);
+    }
+
+    #[test]
+    fn invalid_win_condition_is_located() {
+        let e = error(&LEVEL.replace("    asteroids: [", "    win: Score(0),\n    asteroids: ["));
+        assert_eq!(e.position, Some((7, 5)));
+    }
+
+    #[test]
+    fn syntax_error_is_located() {
+        let e = error(&LEVEL.replace("name: \"Test\",", "name: \"Test\""));
+        assert!(e.position.is_some());
+    }
+
+    #[test]
+    fn locate_skips_strings_and_comments() {
+        let source = "(\n  // a: [(\n  a: [1, (2, \"]\"), 3],\n)";
+        assert_eq!(locate(source, &[field("a"), PathPart::Index(2)]), Some((3, 20)));
+        // the closest value found
+        assert_eq!(locate(source, &[field("a"), PathPart::Index(5)]), Some((3, 3)));
+        assert_eq!(locate(source, &[field("b")]), Some((1, 1)));
+    }
+
+    #[test]
+    fn obstacle_over_the_spawn_point_is_rejected() {
+        let mut level = Level::parse("test.ron", LEVEL).unwrap();
+        level.obstacles[0].position = (0.45, 0.5);
+        assert!(level.validate().is_err());
+        // the outline counts, not only the position
+        level.obstacles[0].position = (0.2, 0.2);
+        level.obstacles[0].points = vec![(-10.0, -10.0), (200.0, -10.0), (200.0, 10.0)];
+        assert!(level.validate().is_err());
+        // turned away from the center
+        level.obstacles[0].rotation = 180.0;
+        assert!(level.validate().is_ok());
+
+        let e = error(&LEVEL.replace("(position: (0.8, 0.5)", "(position: (0.5, 0.55)"));
+        assert_eq!(e.position, Some((4, 10)));
+        assert!(e.message.starts_with("obstacles[0].position: "), "{}", e.message);
+    }
+
+    #[test]
+    fn black_hole_over_the_spawn_point_is_rejected() {
+        let mut level = Level::parse("test.ron", LEVEL).unwrap();
+        let hole = |position, horizon| HazardConfig {
+            kind: HazardKind::BlackHole,
+            position,
+            strength: 1000.0,
+            horizon,
+        };
+        level.hazards = vec![hole((0.6, 0.5), 5.0)];
+        assert!(level.validate().is_ok());
+        level.hazards = vec![hole((0.6, 0.5), 15.0)];
+        let e = level.validate().unwrap_err();
+        assert_eq!(format_path(&e.path), "hazards[0].position");
+        // a gravity well only pulls
+        level.hazards = vec![HazardConfig { kind: HazardKind::GravityWell, ..hole((0.5, 0.5), 0.0) }];
+        assert!(level.validate().is_ok());
+    }
+
+    #[test]
+    fn path_is_formatted_like_code() {
+        let path = [field("obstacles"), PathPart::Index(2), field("position")];
+        assert_eq!(format_path(&path), "obstacles[2].position");
+    }
+}
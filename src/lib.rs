@@ -0,0 +1,25 @@
+// the game as a library, shared by the game binary and the benchmarks
+pub mod animation;
+pub mod audio;
+pub mod broad_phase;
+pub mod particles;
+pub mod physics;
+pub mod pool;
+pub mod states;
+pub mod components;
+pub mod editor;
+pub mod effects;
+pub mod geometry;
+pub mod hazards;
+pub mod hud;
+pub mod level;
+pub mod system;
+pub mod textures;
+pub mod resources;
+pub mod scoring;
+pub mod settings;
+pub mod spatial;
+pub mod starfield;
+pub mod stats;
+pub mod vector;
+pub mod weapons;
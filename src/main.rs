@@ -1,21 +1,38 @@
 use amethyst::{
+    audio::AudioBundle,
+    config::Config,
     core::transform::TransformBundle,
     input::{InputBundle, StringBindings},
     prelude::*,
     renderer::{
-        plugins::{RenderFlat2D, RenderToWindow},
+        debug_drawing::{DebugLines, DebugLinesParams},
+        plugins::{RenderDebugLines, RenderFlat2D, RenderToWindow},
         types::DefaultBackend,
         RenderingBundle,
     },
     ui::{RenderUi, UiBundle},
-    utils::application_root_dir,
+    utils::{application_root_dir, fps_counter::FpsCounterBundle},
+    window::DisplayConfig,
 };
 
-mod states;
-mod components;
-mod system;
-mod textures;
-mod resources;
+use rocket::{
+    audio,
+    effects,
+    hazards,
+    hud,
+    level,
+    particles,
+    physics,
+    pool,
+    resources,
+    scoring,
+    settings,
+    starfield,
+    states,
+    system,
+    vector,
+    weapons,
+};
 
 fn main() -> amethyst::Result<()> {
     amethyst::start_logger(Default::default());
@@ -26,28 +43,86 @@ fn main() -> amethyst::Result<()> {
     let config_dir = app_root.join("config");
     let display_config_path = config_dir.join("display.ron");
     let input_config_path = config_dir.join("input.ron");
+    let arena_config_path = config_dir.join("arena.ron");
+    let starfield_config_path = config_dir.join("starfield.ron");
+    let scoring_config_path = config_dir.join("scoring.ron");
+    let ship_config_path = config_dir.join("ship.ron");
+    let asteroid_physics_path = config_dir.join("asteroids.ron");
+    let pool_config_path = config_dir.join("pools.ron");
+    let laser_config_path = config_dir.join("laser.ron");
+
+    // user settings override the shipped display config
+    let settings = settings::Settings::load(&settings::user_settings_path()?);
+    let mut display_config = DisplayConfig::load(&display_config_path)?;
+    settings.apply_display(&mut display_config);
+
+    let arena_config = resources::ArenaConfig::load(&arena_config_path)?;
+    let arena = resources::Arena::from_config(&arena_config);
+    let endless = level::Level::endless(&arena_config);
+    let starfield_config = starfield::StarfieldConfig::load(&starfield_config_path)?;
+    let scoring_config = scoring::ScoringConfig::load(&scoring_config_path)?;
+    let ship_config = physics::ShipPhysicsConfig::load(&ship_config_path)?;
+    let asteroid_physics = physics::AsteroidPhysicsConfig::load(&asteroid_physics_path)?;
+    let pool_config = pool::PoolConfig::load(&pool_config_path)?;
+    let laser_config = weapons::LaserConfig::load(&laser_config_path)?;
 
     let input_bundle = InputBundle::<StringBindings>::new()
         .with_bindings_from_file(input_config_path)?;
 
-    let game_data = GameDataBuilder::default()
-        .with_bundle(
-            RenderingBundle::<DefaultBackend>::new()
-                .with_plugin(
-                    RenderToWindow::from_config_path(display_config_path)?
-                        .with_clear([0.34, 0.36, 0.52, 1.0]),
-                )
-                .with_plugin(RenderFlat2D::default())
-                .with_plugin(RenderUi::default())
-        )?
+    let vector_mode = settings.render_mode == settings::RenderMode::Vector;
+    let mut rendering_bundle = RenderingBundle::<DefaultBackend>::new()
+        .with_plugin(
+            RenderToWindow::from_config(display_config)
+                .with_clear([0.34, 0.36, 0.52, 1.0]),
+        );
+    if !vector_mode {
+        rendering_bundle = rendering_bundle.with_plugin(RenderFlat2D::default());
+    }
+    // hazards, obstacles, walls and laser beams are drawn as lines in both modes
+    rendering_bundle = rendering_bundle
+        .with_plugin(RenderDebugLines::default())
+        .with_plugin(RenderUi::default());
+
+    let mut game_data = GameDataBuilder::default()
+        .with_bundle(rendering_bundle)?
         .with_bundle(TransformBundle::new())?
         .with_bundle(input_bundle)?
-        .with_bundle(UiBundle::<StringBindings>::new())?;
+        .with_bundle(UiBundle::<StringBindings>::new())?
+        .with_bundle(AudioBundle::default())?
+        .with_bundle(FpsCounterBundle::default())?
+        .with(system::ViewportSystem::default(), "viewport_system", &[])
+        .with(audio::AudioSystem::default(), "game_audio_system", &[])
+        .with(starfield::StarfieldSystem, "starfield_system", &["viewport_system"])
+        .with(effects::CameraEffectsSystem::default(), "camera_effects_system", &["viewport_system"])
+        .with(hud::HudSystem::default(), "hud_system", &["viewport_system"])
+        .with(hazards::HazardRenderSystem, "hazard_render_system", &[])
+        .with(level::EdgeRenderSystem, "edge_render_system", &[])
+        .with(weapons::BeamRenderSystem, "beam_render_system", &[]);
+    game_data = if vector_mode {
+        game_data.with(vector::VectorRenderSystem, "vector_render_system", &["starfield_system"])
+    } else {
+        game_data
+            .with(starfield::StarfieldRenderSystem::default(), "starfield_render_system",
+                  &["starfield_system"])
+            .with(particles::ParticleRenderSystem::default(), "particle_render_system", &[])
+            .with(level::ObstacleRenderSystem, "obstacle_render_system", &[])
+    };
 
-    let mut game = Application::new(
-        assets_dir,
-        states::StateMenu::default(),
-        game_data)?;
+    let mut game = Application::build(assets_dir, states::StateLoading::default())?
+        .with_resource(DebugLines::new())
+        .with_resource(DebugLinesParams { line_width: 2.0 })
+        .with_resource(settings)
+        .with_resource(arena_config)
+        .with_resource(arena)
+        .with_resource(endless)
+        .with_resource(starfield_config)
+        .with_resource(scoring_config)
+        .with_resource(ship_config)
+        .with_resource(asteroid_physics)
+        .with_resource(pool_config)
+        .with_resource(laser_config)
+        .with_resource(resources::Viewport::default())
+        .build(game_data)?;
     game.run();
 
     Ok(())
@@ -0,0 +1,461 @@
+use std::f32::consts::PI;
+
+use amethyst::{
+    core::{
+        math::{Vector2, Vector3},
+        transform::components::Transform,
+        timing::Time,
+        Hidden,
+    },
+    ecs::{Join,
+          ReadStorage, WriteStorage,
+          System, SystemData,
+          Read, Write, ReadExpect,
+          Entities,
+          World},
+    ecs::prelude::{Component, DenseVecStorage, Entity},
+    renderer::{SpriteRender, palette::Srgba, resources::Tint, transparent::Transparent},
+    shrev::{EventChannel, ReaderId},
+};
+use log::{error};
+
+use crate::components::{AsteroidSize, Physical};
+use crate::resources::{RandomGen};
+use crate::textures::{SpriteRegistry};
+use crate::system::{GameEvent};
+
+// how particles look and move when they are emitted
+#[derive(Clone, Copy, Debug)]
+pub struct ParticleConfig {
+    // seconds
+    pub lifetime: f32,
+    pub speed: f32,
+    // the speed is picked in [speed - speed_variance, speed + speed_variance]
+    pub speed_variance: f32,
+    // half angle of the emission cone (rad)
+    pub spread: f32,
+    pub color_start: [f32; 4],
+    pub color_end: [f32; 4],
+    pub size: f32,
+}
+
+impl ParticleConfig {
+    pub fn exhaust() -> Self {
+        Self {
+            lifetime: 0.4,
+            speed: 40.0,
+            speed_variance: 10.0,
+            spread: 0.35,
+            color_start: [1.0, 0.9, 0.4, 1.0],
+            color_end: [0.8, 0.1, 0.0, 0.0],
+            size: 0.6,
+        }
+    }
+
+    pub fn debris(size: AsteroidSize) -> Self {
+        Self {
+            lifetime: 0.5 + 0.3 * size.scale(),
+            speed: 30.0,
+            speed_variance: 20.0,
+            spread: PI,
+            color_start: [0.7, 0.65, 0.6, 1.0],
+            color_end: [0.3, 0.3, 0.3, 0.0],
+            size: 0.5 * size.scale(),
+        }
+    }
+
+    pub fn ship_explosion() -> Self {
+        Self {
+            lifetime: 1.5,
+            speed: 50.0,
+            speed_variance: 40.0,
+            spread: PI,
+            color_start: [1.0, 1.0, 0.8, 1.0],
+            color_end: [1.0, 0.3, 0.0, 0.0],
+            size: 1.0,
+        }
+    }
+}
+
+// continuously emits particles while active, e.g. the engine exhaust
+pub struct Emitter {
+    pub config: ParticleConfig,
+    // particles per second
+    pub rate: f32,
+    pub active: bool,
+    // emission direction relative to the entity's nose (rad)
+    pub direction: f32,
+    // distance from the entity's center along the emission direction
+    pub offset: f32,
+    // fractional particles carried to the next frame
+    accumulator: f32,
+}
+
+impl Emitter {
+    pub fn new(config: ParticleConfig, rate: f32) -> Self {
+        Self {
+            config,
+            rate,
+            active: false,
+            direction: 0.0,
+            offset: 0.0,
+            accumulator: 0.0,
+        }
+    }
+
+    pub fn exhaust() -> Self {
+        Self {
+            direction: PI,
+            offset: 6.0,
+            ..Self::new(ParticleConfig::exhaust(), 60.0)
+        }
+    }
+
+    // the number of particles due after delta seconds at the rate, the
+    // fraction left is carried to the next frame
+    pub fn step(&mut self, delta: f32) -> usize {
+        if !self.active {
+            self.accumulator = 0.0;
+            return 0;
+        }
+        self.accumulator += self.rate * delta;
+        let count = self.accumulator.floor();
+        self.accumulator -= count;
+        count as usize
+    }
+}
+
+impl Component for Emitter {
+    type Storage = DenseVecStorage<Self>;
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Particle {
+    pub position: Vector2<f32>,
+    pub velocity: Vector2<f32>,
+    pub age: f32,
+    pub lifetime: f32,
+    pub size: f32,
+    color_start: [f32; 4],
+    color_end: [f32; 4],
+}
+
+impl Particle {
+    // color interpolated over the lifetime
+    pub fn color(&self) -> [f32; 4] {
+        let t = (self.age / self.lifetime).min(1.0);
+        let mut color = [0.0; 4];
+        for i in 0..4 {
+            color[i] = self.color_start[i] + (self.color_end[i] - self.color_start[i]) * t;
+        }
+        color
+    }
+}
+
+// all live particles, simulated without any rendering
+pub struct ParticlePool {
+    particles: Vec<Particle>,
+    capacity: usize,
+    // particles not spawned because the pool was full
+    pub dropped: usize,
+}
+
+impl Default for ParticlePool {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_CAPACITY)
+    }
+}
+
+impl ParticlePool {
+    pub const DEFAULT_CAPACITY: usize = 512;
+
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            particles: Vec::with_capacity(capacity),
+            capacity,
+            dropped: 0,
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn particles(&self) -> &[Particle] {
+        &self.particles
+    }
+
+    pub fn clear(&mut self) {
+        self.particles.clear();
+    }
+
+    // emit one particle in the cone around direction (rad)
+    // rand returns a random number in [0, 1)
+    pub fn emit(&mut self,
+                config: &ParticleConfig,
+                position: Vector2<f32>,
+                base_velocity: Vector2<f32>,
+                direction: f32,
+                rand: &mut dyn FnMut() -> f32) -> bool {
+        if self.particles.len() >= self.capacity {
+            self.dropped += 1;
+            return false;
+        }
+
+        let angle = direction + (rand() * 2.0 - 1.0) * config.spread;
+        let speed = config.speed + (rand() * 2.0 - 1.0) * config.speed_variance;
+        self.particles.push(Particle {
+            position,
+            velocity: base_velocity + Vector2::new(angle.cos(), angle.sin()) * speed,
+            age: 0.0,
+            lifetime: config.lifetime,
+            size: config.size,
+            color_start: config.color_start,
+            color_end: config.color_end,
+        });
+        true
+    }
+
+    pub fn burst(&mut self,
+                 config: &ParticleConfig,
+                 position: Vector2<f32>,
+                 count: usize,
+                 rand: &mut dyn FnMut() -> f32) {
+        for _ in 0..count {
+            if !self.emit(config, position, Vector2::new(0.0, 0.0), 0.0, rand) {
+                break;
+            }
+        }
+    }
+
+    pub fn update(&mut self, delta: f32) {
+        for p in self.particles.iter_mut() {
+            p.age += delta;
+            p.position += p.velocity * delta;
+        }
+        self.particles.retain(|p| p.age < p.lifetime);
+    }
+}
+
+fn debris_count(size: AsteroidSize) -> usize {
+    match size {
+        AsteroidSize::Small => 8,
+        AsteroidSize::Medium => 14,
+        AsteroidSize::Large => 24,
+    }
+}
+
+// simulate the particles, feed the emitters and the explosion bursts
+#[derive(Default)]
+pub struct ParticleSystem {
+    event_reader: Option<ReaderId<GameEvent>>,
+}
+
+impl<'s> System<'s> for ParticleSystem {
+    type SystemData = (
+        Write<'s, ParticlePool>,
+        WriteStorage<'s, Emitter>,
+        ReadStorage<'s, Transform>,
+        ReadStorage<'s, Physical>,
+        Read<'s, EventChannel<GameEvent>>,
+        ReadExpect<'s, RandomGen>,
+        Read<'s, Time>,
+    );
+
+    fn setup(&mut self, world: &mut World) {
+        Self::SystemData::setup(world);
+        self.event_reader = Some(
+            world
+                .fetch_mut::<EventChannel<GameEvent>>()
+                .register_reader()
+        )
+    }
+
+    fn run(&mut self,
+           (mut pool,
+            mut emitters,
+            transforms,
+            physicals,
+            events,
+            rand,
+            time): Self::SystemData) {
+        let delta = time.delta_seconds();
+        let mut random = || rand.next_f32();
+        pool.update(delta);
+
+        for (emitter, transform, physical) in (&mut emitters, &transforms, (&physicals).maybe()).join() {
+            let count = emitter.step(delta);
+            if count == 0 {
+                continue;
+            }
+
+            let nose = transform.rotation() * Vector3::y();
+            let direction = nose.y.atan2(nose.x) + emitter.direction;
+            let position = Vector2::new(transform.translation().x, transform.translation().y)
+                + Vector2::new(direction.cos(), direction.sin()) * emitter.offset;
+            let velocity = physical.map(|p| p.velocity).unwrap_or_else(|| Vector2::new(0.0, 0.0));
+
+            for _ in 0..count {
+                pool.emit(&emitter.config, position, velocity, direction, &mut random);
+            }
+        }
+
+        for event in events.read(self.event_reader.as_mut().unwrap()) {
+            match *event {
+                GameEvent::AsteroidDestroyed { size, position } => {
+                    pool.burst(&ParticleConfig::debris(size), position,
+                               debris_count(size), &mut random);
+                },
+                GameEvent::ShipDestroyed { position } => {
+                    pool.burst(&ParticleConfig::ship_explosion(), position, 80, &mut random);
+                },
+                _ => {},
+            }
+        }
+    }
+}
+
+// show the particles with a pool of sprite entities, unused ones are hidden
+#[derive(Default)]
+pub struct ParticleRenderSystem {
+    sprites: Vec<Entity>,
+}
+
+impl<'s> System<'s> for ParticleRenderSystem {
+    type SystemData = (
+        Entities<'s>,
+        Read<'s, ParticlePool>,
+        Option<Read<'s, SpriteRegistry>>,
+        WriteStorage<'s, Transform>,
+        WriteStorage<'s, SpriteRender>,
+        WriteStorage<'s, Tint>,
+        WriteStorage<'s, Transparent>,
+        WriteStorage<'s, Hidden>,
+    );
+
+    fn run(&mut self,
+           (entities,
+            pool,
+            registry,
+            mut transforms,
+            mut sprites,
+            mut tints,
+            mut transparents,
+            mut hiddens): Self::SystemData) {
+        let registry = match registry {
+            Some(registry) => registry,
+            None => return,
+        };
+
+        let particles = pool.particles();
+        while self.sprites.len() < particles.len() {
+            let e = entities.create();
+            let result = transforms.insert(e, Transform::default())
+                .and(sprites.insert(e, registry.sprite_render("particle")))
+                .and(tints.insert(e, Tint(Srgba::new(1.0, 1.0, 1.0, 1.0))))
+                .and(transparents.insert(e, Transparent));
+            if let Err(e) = result {
+                error!("Failed to create particle sprite: {}", e);
+            }
+            self.sprites.push(e);
+        }
+
+        for (i, e) in self.sprites.iter().enumerate() {
+            match particles.get(i) {
+                Some(particle) => {
+                    hiddens.remove(*e);
+                    if let Some(transform) = transforms.get_mut(*e) {
+                        // behind the ship and the asteroids
+                        transform.set_translation_xyz(particle.position.x, particle.position.y, -0.1);
+                        transform.set_scale(Vector3::new(particle.size, particle.size, 1.0));
+                    }
+                    if let Some(tint) = tints.get_mut(*e) {
+                        let [r, g, b, a] = particle.color();
+                        tint.0 = Srgba::new(r, g, b, a);
+                    }
+                },
+                None => {
+                    if !hiddens.contains(*e) {
+                        if let Err(e) = hiddens.insert(*e, Hidden) {
+                            error!("Failed to hide particle sprite: {}", e);
+                        }
+                    }
+                },
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn origin() -> Vector2<f32> {
+        Vector2::new(0.0, 0.0)
+    }
+
+    #[test]
+    fn pool_is_capped() {
+        let mut pool = ParticlePool::default();
+        let mut rand = || 0.5;
+        for _ in 0..600 {
+            pool.emit(&ParticleConfig::exhaust(), origin(), origin(), 0.0, &mut rand);
+        }
+        assert_eq!(pool.capacity(), 512);
+        assert_eq!(pool.particles().len(), 512);
+        assert_eq!(pool.dropped, 88);
+    }
+
+    #[test]
+    fn burst_stops_when_full() {
+        let mut pool = ParticlePool::new(10);
+        let mut rand = || 0.5;
+        pool.burst(&ParticleConfig::ship_explosion(), origin(), 80, &mut rand);
+        assert_eq!(pool.particles().len(), 10);
+        assert_eq!(pool.dropped, 1);
+    }
+
+    #[test]
+    fn particles_expire_after_their_lifetime() {
+        let mut pool = ParticlePool::default();
+        let mut rand = || 0.5;
+        let config = ParticleConfig { lifetime: 0.5, ..ParticleConfig::exhaust() };
+        pool.emit(&config, origin(), origin(), 0.0, &mut rand);
+        pool.update(0.25);
+        assert_eq!(pool.particles().len(), 1);
+        assert_eq!(pool.particles()[0].age, 0.25);
+        pool.update(0.25);
+        assert!(pool.particles().is_empty());
+    }
+
+    #[test]
+    fn particles_move_with_their_velocity() {
+        let mut pool = ParticlePool::default();
+        // no spread and no speed variance with rand 0.5
+        let mut rand = || 0.5;
+        let config = ParticleConfig { speed: 10.0, ..ParticleConfig::exhaust() };
+        pool.emit(&config, origin(), Vector2::new(2.0, 0.0), 0.0, &mut rand);
+        pool.update(0.25);
+        assert_eq!(pool.particles()[0].position, Vector2::new(3.0, 0.0));
+    }
+
+    #[test]
+    fn emitter_keeps_its_rate_over_frames() {
+        let mut emitter = Emitter::new(ParticleConfig::exhaust(), 10.0);
+        emitter.active = true;
+        let counts: Vec<usize> = (0..4).map(|_| emitter.step(0.25)).collect();
+        assert_eq!(counts, vec![2, 3, 2, 3]);
+    }
+
+    #[test]
+    fn inactive_emitter_emits_nothing() {
+        let mut emitter = Emitter::new(ParticleConfig::exhaust(), 10.0);
+        emitter.active = true;
+        assert_eq!(emitter.step(0.25), 2);
+        emitter.active = false;
+        assert_eq!(emitter.step(1.0), 0);
+        // the fraction of the last frame is dropped
+        emitter.active = true;
+        assert_eq!(emitter.step(0.25), 2);
+    }
+}
@@ -0,0 +1,408 @@
+use amethyst::core::math::Vector2;
+use serde::{Deserialize, Serialize};
+
+// the ship movement model as configured in config/ship.ron
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ShipPhysicsConfig {
+    // acceleration at full throttle (units / s^2)
+    pub forward_thrust: f32,
+    pub reverse_thrust: f32,
+    // fraction of the velocity lost per second, 0 keeps drifting forever
+    pub drag: f32,
+    pub max_speed: f32,
+    // rad / s^2 at full rotation input
+    pub angular_acceleration: f32,
+    pub max_angular_velocity: f32,
+    // fraction of the angular velocity lost per second without input
+    pub angular_drag: f32,
+    // velocity pushed backwards by every shot, 0 disables recoil
+    pub recoil: f32,
+}
+
+impl Default for ShipPhysicsConfig {
+    fn default() -> Self {
+        Self {
+            forward_thrust: 80.0,
+            reverse_thrust: 30.0,
+            drag: 0.3,
+            max_speed: 100.0,
+            angular_acceleration: 20.0,
+            max_angular_velocity: 4.0,
+            angular_drag: 8.0,
+            recoil: 3.0,
+        }
+    }
+}
+
+// the moving part of a ship, velocity and angular velocity (rad / s)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ShipMotion {
+    pub velocity: Vector2<f32>,
+    pub angular_velocity: f32,
+}
+
+// throttle in [-1, 1], heading is the unit vector the nose points to
+pub fn apply_thrust(velocity: Vector2<f32>, heading: Vector2<f32>, throttle: f32,
+                    config: &ShipPhysicsConfig, delta: f32) -> Vector2<f32> {
+    let throttle = throttle.max(-1.0).min(1.0);
+    let thrust = if throttle >= 0.0 { config.forward_thrust } else { config.reverse_thrust };
+    velocity + heading * throttle * thrust * delta
+}
+
+// exponential decay, independent of the frame rate
+pub fn apply_drag(velocity: Vector2<f32>, drag: f32, delta: f32) -> Vector2<f32> {
+    velocity * (-drag.max(0.0) * delta).exp()
+}
+
+pub fn clamp_speed(velocity: Vector2<f32>, max_speed: f32) -> Vector2<f32> {
+    let speed = velocity.norm();
+    if speed > max_speed && speed > 0.0 {
+        velocity * (max_speed / speed)
+    } else {
+        velocity
+    }
+}
+
+// input in [-1, 1], positive is counter-clockwise, without input the
+// rotation slows down instead of stopping at once
+pub fn update_angular_velocity(angular_velocity: f32, input: f32,
+                               config: &ShipPhysicsConfig, delta: f32) -> f32 {
+    let input = input.max(-1.0).min(1.0);
+    let angular_velocity = if input != 0.0 {
+        angular_velocity + input * config.angular_acceleration * delta
+    } else {
+        angular_velocity * (-config.angular_drag.max(0.0) * delta).exp()
+    };
+    angular_velocity.max(-config.max_angular_velocity).min(config.max_angular_velocity)
+}
+
+// a shot pushes the ship backwards, but never past its top speed
+pub fn apply_recoil(velocity: Vector2<f32>, heading: Vector2<f32>,
+                    config: &ShipPhysicsConfig) -> Vector2<f32> {
+    clamp_speed(velocity - heading * config.recoil, config.max_speed)
+}
+
+// advance the ship by one frame of input
+pub fn step(motion: ShipMotion, heading: Vector2<f32>, throttle: f32, rotate: f32,
+            config: &ShipPhysicsConfig, delta: f32) -> ShipMotion {
+    let velocity = apply_thrust(motion.velocity, heading, throttle, config, delta);
+    let velocity = apply_drag(velocity, config.drag, delta);
+    ShipMotion {
+        velocity: clamp_speed(velocity, config.max_speed),
+        angular_velocity: update_angular_velocity(motion.angular_velocity, rotate, config, delta),
+    }
+}
+
+// asteroid against asteroid collisions as configured in config/asteroids.ron
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AsteroidPhysicsConfig {
+    // asteroids pass through each other when disabled
+    pub collisions: bool,
+    // 1 is elastic, 0 is fully inelastic
+    pub restitution: f32,
+    // friction between the surfaces, turns sliding into spin
+    pub friction: f32,
+}
+
+impl Default for AsteroidPhysicsConfig {
+    fn default() -> Self {
+        Self {
+            collisions: false,
+            restitution: 0.8,
+            friction: 0.3,
+        }
+    }
+}
+
+// bounce off a static surface with the given outward normal, a velocity
+// already moving away is kept
+pub fn reflect(velocity: Vector2<f32>, normal: Vector2<f32>, restitution: f32) -> Vector2<f32> {
+    let normal_speed = velocity.dot(&normal);
+    if normal_speed >= 0.0 {
+        return velocity;
+    }
+    velocity - normal * (1.0 + restitution.max(0.0).min(1.0)) * normal_speed
+}
+
+// a round body for collision response
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Body {
+    pub position: Vector2<f32>,
+    pub velocity: Vector2<f32>,
+    pub angular_velocity: f32,
+    pub mass: f32,
+    pub radius: f32,
+}
+
+impl Body {
+    // inverse moment of inertia of a solid disc, 0 for a point
+    fn inv_inertia(&self) -> f32 {
+        let inertia = 0.5 * self.mass * self.radius * self.radius;
+        if inertia > 0.0 { 1.0 / inertia } else { 0.0 }
+    }
+}
+
+fn cross(a: Vector2<f32>, b: Vector2<f32>) -> f32 {
+    a.x * b.y - a.y * b.x
+}
+
+// velocity of a point at offset r from the center of a spinning body
+fn point_velocity(body: &Body, r: Vector2<f32>) -> Vector2<f32> {
+    body.velocity + Vector2::new(-body.angular_velocity * r.y, body.angular_velocity * r.x)
+}
+
+// push two overlapping bodies apart and exchange impulses, the impulses
+// are equal and opposite so the linear momentum is conserved
+pub fn resolve_collision(a: &mut Body, b: &mut Body, config: &AsteroidPhysicsConfig) {
+    let offset = b.position - a.position;
+    let distance = offset.norm();
+    let penetration = a.radius + b.radius - distance;
+    if penetration <= 0.0 || a.mass <= 0.0 || b.mass <= 0.0 {
+        return;
+    }
+    let normal = if distance > 0.0 { offset / distance } else { Vector2::x() };
+    let (inv_a, inv_b) = (1.0 / a.mass, 1.0 / b.mass);
+
+    // overlapping spawns are separated by moving the bodies, not by an
+    // impulse, so they do not fly apart at high speed
+    let correction = normal * penetration / (inv_a + inv_b);
+    a.position -= correction * inv_a;
+    b.position += correction * inv_b;
+
+    let ra = normal * a.radius;
+    let rb = -normal * b.radius;
+    let relative = point_velocity(b, rb) - point_velocity(a, ra);
+    let normal_speed = relative.dot(&normal);
+    // already separating
+    if normal_speed >= 0.0 {
+        return;
+    }
+
+    let restitution = config.restitution.max(0.0).min(1.0);
+    let j = -(1.0 + restitution) * normal_speed / (inv_a + inv_b);
+    let mut impulse = normal * j;
+
+    // friction along the contact, limited by the normal impulse
+    let tangent = relative - normal * normal_speed;
+    if tangent.norm() > 0.0 {
+        let tangent = tangent.normalize();
+        let ra_t = cross(ra, tangent);
+        let rb_t = cross(rb, tangent);
+        let mass_t = inv_a + inv_b + ra_t * ra_t * a.inv_inertia()
+            + rb_t * rb_t * b.inv_inertia();
+        let max_friction = config.friction.max(0.0) * j;
+        let jt = (-relative.dot(&tangent) / mass_t).max(-max_friction).min(max_friction);
+        impulse += tangent * jt;
+    }
+
+    a.velocity -= impulse * inv_a;
+    b.velocity += impulse * inv_b;
+    a.angular_velocity -= cross(ra, impulse) * a.inv_inertia();
+    b.angular_velocity += cross(rb, impulse) * b.inv_inertia();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EPSILON: f32 = 1e-4;
+
+    fn close(a: Vector2<f32>, b: Vector2<f32>) -> bool {
+        (a - b).norm() < EPSILON
+    }
+
+    fn still() -> Vector2<f32> {
+        Vector2::new(0.0, 0.0)
+    }
+
+    #[test]
+    fn drag_does_not_depend_on_the_frame_rate() {
+        let velocity = Vector2::new(50.0, -20.0);
+        let once = apply_drag(velocity, 0.3, 1.0);
+        let mut often = velocity;
+        for _ in 0..60 {
+            often = apply_drag(often, 0.3, 1.0 / 60.0);
+        }
+        assert!(close(once, often));
+        assert!(once.norm() < velocity.norm());
+    }
+
+    #[test]
+    fn no_drag_keeps_drifting() {
+        let velocity = Vector2::new(50.0, -20.0);
+        assert_eq!(apply_drag(velocity, 0.0, 1.0), velocity);
+        // a negative drag would speed up, it is treated as 0
+        assert_eq!(apply_drag(velocity, -1.0, 1.0), velocity);
+    }
+
+    #[test]
+    fn reverse_thrust_is_weaker_than_forward_thrust() {
+        let config = ShipPhysicsConfig::default();
+        let heading = Vector2::new(0.0, 1.0);
+        let forward = apply_thrust(still(), heading, 1.0, &config, 0.5);
+        let reverse = apply_thrust(still(), heading, -1.0, &config, 0.5);
+        assert!(close(forward, Vector2::new(0.0, config.forward_thrust * 0.5)));
+        assert!(close(reverse, Vector2::new(0.0, -config.reverse_thrust * 0.5)));
+    }
+
+    #[test]
+    fn throttle_is_limited_to_full() {
+        let config = ShipPhysicsConfig::default();
+        let heading = Vector2::new(1.0, 0.0);
+        assert_eq!(apply_thrust(still(), heading, 5.0, &config, 1.0),
+                   apply_thrust(still(), heading, 1.0, &config, 1.0));
+    }
+
+    #[test]
+    fn speed_is_clamped_keeping_the_direction() {
+        let clamped = clamp_speed(Vector2::new(30.0, 40.0), 10.0);
+        assert!(close(clamped, Vector2::new(6.0, 8.0)));
+        assert_eq!(clamp_speed(Vector2::new(3.0, 4.0), 10.0), Vector2::new(3.0, 4.0));
+        assert_eq!(clamp_speed(still(), 0.0), still());
+    }
+
+    #[test]
+    fn rotation_accelerates_up_to_the_limit() {
+        let config = ShipPhysicsConfig::default();
+        let turning = update_angular_velocity(0.0, 1.0, &config, 0.1);
+        assert!((turning - config.angular_acceleration * 0.1).abs() < EPSILON);
+        let spinning = update_angular_velocity(0.0, -1.0, &config, 10.0);
+        assert_eq!(spinning, -config.max_angular_velocity);
+    }
+
+    #[test]
+    fn rotation_slows_down_without_input() {
+        let config = ShipPhysicsConfig::default();
+        let slower = update_angular_velocity(2.0, 0.0, &config, 0.1);
+        assert!(slower > 0.0 && slower < 2.0);
+        assert!((slower - 2.0 * (-config.angular_drag * 0.1).exp()).abs() < EPSILON);
+    }
+
+    #[test]
+    fn recoil_pushes_backwards() {
+        let config = ShipPhysicsConfig::default();
+        let heading = Vector2::new(0.0, 1.0);
+        let velocity = apply_recoil(still(), heading, &config);
+        assert!(close(velocity, Vector2::new(0.0, -config.recoil)));
+    }
+
+    #[test]
+    fn recoil_does_not_exceed_the_top_speed() {
+        let config = ShipPhysicsConfig::default();
+        let heading = Vector2::new(0.0, 1.0);
+        let backwards = Vector2::new(0.0, -config.max_speed);
+        let velocity = apply_recoil(backwards, heading, &config);
+        assert!((velocity.norm() - config.max_speed).abs() < EPSILON);
+    }
+
+    #[test]
+    fn step_limits_the_speed() {
+        let config = ShipPhysicsConfig::default();
+        let motion = ShipMotion { velocity: Vector2::new(0.0, config.max_speed), angular_velocity: 0.0 };
+        let next = step(motion, Vector2::new(0.0, 1.0), 1.0, 0.0, &config, 1.0);
+        assert!(next.velocity.norm() <= config.max_speed + EPSILON);
+    }
+
+    fn body(x: f32, vx: f32, mass: f32) -> Body {
+        Body {
+            position: Vector2::new(x, 0.0),
+            velocity: Vector2::new(vx, 0.0),
+            angular_velocity: 0.0,
+            mass,
+            radius: 1.0,
+        }
+    }
+
+    fn momentum(a: &Body, b: &Body) -> Vector2<f32> {
+        a.velocity * a.mass + b.velocity * b.mass
+    }
+
+    fn bounce(restitution: f32, friction: f32) -> AsteroidPhysicsConfig {
+        AsteroidPhysicsConfig { collisions: true, restitution, friction }
+    }
+
+    #[test]
+    fn collision_conserves_momentum() {
+        let mut a = body(0.0, 5.0, 1.0);
+        let mut b = body(1.5, -3.0, 2.56);
+        // an off-center hit with spin and friction
+        b.position.y = 0.8;
+        a.angular_velocity = 2.0;
+        let before = momentum(&a, &b);
+        resolve_collision(&mut a, &mut b, &bounce(0.8, 0.3));
+        assert!(close(momentum(&a, &b), before));
+        assert!(a.velocity != Vector2::new(5.0, 0.0));
+    }
+
+    #[test]
+    fn elastic_collision_swaps_equal_masses() {
+        let mut a = body(0.0, 4.0, 1.0);
+        let mut b = body(1.9, -2.0, 1.0);
+        resolve_collision(&mut a, &mut b, &bounce(1.0, 0.0));
+        assert!(close(a.velocity, Vector2::new(-2.0, 0.0)));
+        assert!(close(b.velocity, Vector2::new(4.0, 0.0)));
+    }
+
+    #[test]
+    fn elastic_collision_keeps_the_energy() {
+        let mut a = body(0.0, 4.0, 1.0);
+        let mut b = body(1.9, -1.0, 2.56);
+        let energy = |a: &Body, b: &Body| {
+            0.5 * a.mass * a.velocity.norm_squared() + 0.5 * b.mass * b.velocity.norm_squared()
+        };
+        let before = energy(&a, &b);
+        resolve_collision(&mut a, &mut b, &bounce(1.0, 0.0));
+        assert!((energy(&a, &b) - before).abs() < EPSILON * before);
+    }
+
+    #[test]
+    fn inelastic_collision_moves_on_together() {
+        let mut a = body(0.0, 4.0, 1.0);
+        let mut b = body(1.9, -2.0, 1.0);
+        resolve_collision(&mut a, &mut b, &bounce(0.0, 0.0));
+        assert!(close(a.velocity, Vector2::new(1.0, 0.0)));
+        assert!(close(b.velocity, Vector2::new(1.0, 0.0)));
+    }
+
+    #[test]
+    fn separating_bodies_are_not_pushed() {
+        let mut a = body(0.0, -1.0, 1.0);
+        let mut b = body(1.9, 1.0, 1.0);
+        resolve_collision(&mut a, &mut b, &bounce(1.0, 0.3));
+        assert_eq!(a.velocity, Vector2::new(-1.0, 0.0));
+        assert_eq!(b.velocity, Vector2::new(1.0, 0.0));
+    }
+
+    #[test]
+    fn overlapping_bodies_at_rest_stay_slow() {
+        let mut a = body(0.0, 0.0, 1.0);
+        let mut b = body(0.5, 0.0, 1.0);
+        resolve_collision(&mut a, &mut b, &bounce(1.0, 0.3));
+        assert_eq!(a.velocity, still());
+        assert_eq!(b.velocity, still());
+        // moved apart until they touch
+        assert!(((b.position - a.position).norm() - 2.0).abs() < EPSILON);
+    }
+
+    #[test]
+    fn coincident_centers_are_separated() {
+        let mut a = body(3.0, 0.0, 1.0);
+        let mut b = body(3.0, 0.0, 2.56);
+        resolve_collision(&mut a, &mut b, &bounce(0.8, 0.3));
+        assert!(a.position.x.is_finite() && b.position.x.is_finite());
+        assert!(((b.position - a.position).norm() - 2.0).abs() < EPSILON);
+        assert_eq!(a.velocity, still());
+        assert_eq!(b.velocity, still());
+    }
+
+    #[test]
+    fn bodies_apart_do_not_collide() {
+        let mut a = body(0.0, 1.0, 1.0);
+        let mut b = body(3.0, -1.0, 1.0);
+        let (a0, b0) = (a, b);
+        resolve_collision(&mut a, &mut b, &bounce(1.0, 0.3));
+        assert_eq!((a, b), (a0, b0));
+    }
+}
@@ -0,0 +1,359 @@
+use std::collections::HashMap;
+
+use amethyst::{
+    core::{
+        math::Vector2,
+        transform::components::Transform,
+        Hidden,
+    },
+    ecs::{WriteStorage,
+          System, SystemData,
+          Read, Write,
+          Entities},
+    ecs::prelude::{Component, DenseVecStorage, Entity},
+    renderer::SpriteRender,
+};
+use log::{error};
+use serde::{Deserialize, Serialize};
+
+use crate::animation::{Animation, Animations};
+use crate::components::{Physical, Bullet, Asteroid, AsteroidSize, Collider, ColliderType,
+                        ColliderShape};
+use crate::system::{asteroid_components};
+use crate::textures::{SpriteRegistry};
+use crate::vector::{Outline};
+
+// the entities spawned and destroyed many times per second, each kind has
+// its own pool
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum PoolKind {
+    Bullet,
+    Asteroid,
+    Explosion,
+}
+
+impl PoolKind {
+    pub const ALL: [PoolKind; 3] = [PoolKind::Bullet, PoolKind::Asteroid, PoolKind::Explosion];
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PoolSize {
+    // parked entities created when the game starts
+    pub prewarm: usize,
+    // parked entities kept at most, more released ones are deleted
+    pub capacity: usize,
+}
+
+// the pools as configured in config/pools.ron
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PoolConfig {
+    // every spawn creates a new entity and every release deletes it when disabled
+    pub enabled: bool,
+    pub bullets: PoolSize,
+    pub asteroids: PoolSize,
+    pub explosions: PoolSize,
+}
+
+impl Default for PoolConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            bullets: PoolSize { prewarm: 32, capacity: 256 },
+            asteroids: PoolSize { prewarm: 32, capacity: 128 },
+            explosions: PoolSize { prewarm: 16, capacity: 64 },
+        }
+    }
+}
+
+impl PoolConfig {
+    pub fn size(&self, kind: PoolKind) -> PoolSize {
+        match kind {
+            PoolKind::Bullet => self.bullets,
+            PoolKind::Asteroid => self.asteroids,
+            PoolKind::Explosion => self.explosions,
+        }
+    }
+}
+
+// marks an entity owned by a pool, it is parked instead of deleted
+pub struct Pooled(pub PoolKind);
+
+impl Component for Pooled {
+    type Storage = DenseVecStorage<Self>;
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct PoolMetrics {
+    // spawns served by a parked entity
+    pub hits: u64,
+    // spawns that had to create a new entity
+    pub misses: u64,
+    // entities parked for reuse
+    pub parked: u64,
+    // released entities deleted because the pool was full
+    pub dropped: u64,
+}
+
+impl PoolMetrics {
+    // fraction of the spawns served from the pool
+    pub fn hit_rate(&self) -> f32 {
+        let spawns = self.hits + self.misses;
+        if spawns == 0 {
+            return 0.0;
+        }
+        self.hits as f32 / spawns as f32
+    }
+
+    fn add(&mut self, other: &PoolMetrics) {
+        self.hits += other.hits;
+        self.misses += other.misses;
+        self.parked += other.parked;
+        self.dropped += other.dropped;
+    }
+}
+
+// everything needed to bring a pooled entity to life
+pub enum Spawn {
+    Bullet { transform: Transform, physical: Physical },
+    Asteroid {
+        size: AsteroidSize,
+        position: Vector2<f32>,
+        velocity: Vector2<f32>,
+        rotation: f32,
+        seed: u64,
+    },
+    Explosion { transform: Transform },
+}
+
+impl Spawn {
+    pub fn kind(&self) -> PoolKind {
+        match self {
+            Spawn::Bullet { .. } => PoolKind::Bullet,
+            Spawn::Asteroid { .. } => PoolKind::Asteroid,
+            Spawn::Explosion { .. } => PoolKind::Explosion,
+        }
+    }
+}
+
+// systems queue spawns and releases here instead of creating and deleting
+// entities, PoolSystem carries them out at the end of the frame
+#[derive(Default)]
+pub struct EntityPools {
+    free: HashMap<PoolKind, Vec<Entity>>,
+    spawns: Vec<Spawn>,
+    released: Vec<Entity>,
+    metrics: HashMap<PoolKind, PoolMetrics>,
+}
+
+impl EntityPools {
+    pub fn spawn(&mut self, spawn: Spawn) {
+        self.spawns.push(spawn);
+    }
+
+    // any entity can be released, the ones not owned by a pool are deleted
+    pub fn release(&mut self, entity: Entity) {
+        self.released.push(entity);
+    }
+
+    pub fn metrics(&self, kind: PoolKind) -> PoolMetrics {
+        self.metrics.get(&kind).copied().unwrap_or_default()
+    }
+
+    // the metrics of all pools together
+    pub fn total(&self) -> PoolMetrics {
+        let mut total = PoolMetrics::default();
+        for metrics in self.metrics.values() {
+            total.add(metrics);
+        }
+        total
+    }
+
+    pub fn parked(&self, kind: PoolKind) -> usize {
+        self.free.get(&kind).map_or(0, |free| free.len())
+    }
+
+    // a parked entity, or None when a new one must be created
+    fn acquire(&mut self, kind: PoolKind) -> Option<Entity> {
+        let entity = self.free.get_mut(&kind).and_then(|free| free.pop());
+        let metrics = self.metrics.entry(kind).or_default();
+        if entity.is_some() {
+            metrics.hits += 1;
+        } else {
+            metrics.misses += 1;
+        }
+        entity
+    }
+
+    // returns false when the pool is full and the entity must be deleted
+    fn park(&mut self, kind: PoolKind, entity: Entity, capacity: usize) -> bool {
+        let free = self.free.entry(kind).or_default();
+        let metrics = self.metrics.entry(kind).or_default();
+        if free.len() >= capacity {
+            metrics.dropped += 1;
+            return false;
+        }
+        free.push(entity);
+        metrics.parked += 1;
+        true
+    }
+}
+
+// parks the released entities and brings the queued spawns to life,
+// runs after every system spawning or releasing in the frame
+#[derive(Default)]
+pub struct PoolSystem {
+    prewarmed: bool,
+}
+
+impl<'s> System<'s> for PoolSystem {
+    type SystemData = (
+        Entities<'s>,
+        Write<'s, EntityPools>,
+        Read<'s, PoolConfig>,
+        Option<Read<'s, SpriteRegistry>>,
+        Read<'s, Animations>,
+        WriteStorage<'s, Pooled>,
+        WriteStorage<'s, Hidden>,
+        WriteStorage<'s, Transform>,
+        WriteStorage<'s, Physical>,
+        WriteStorage<'s, Collider>,
+        WriteStorage<'s, Outline>,
+        WriteStorage<'s, SpriteRender>,
+        WriteStorage<'s, Bullet>,
+        WriteStorage<'s, Asteroid>,
+        WriteStorage<'s, Animation>,
+    );
+
+    fn run(&mut self,
+           (entities,
+            mut pools,
+            config,
+            sprites,
+            animations,
+            mut pooled,
+            mut hiddens,
+            mut transforms,
+            mut physicals,
+            mut colliders,
+            mut outlines,
+            mut sprite_renders,
+            mut bullets,
+            mut asteroids,
+            mut animation_storage): Self::SystemData) {
+        if config.enabled && !self.prewarmed {
+            for &kind in PoolKind::ALL.iter() {
+                let size = config.size(kind);
+                for _ in pools.parked(kind)..size.prewarm.min(size.capacity) {
+                    let e = entities.create();
+                    if let Err(e) = pooled.insert(e, Pooled(kind)).and(hiddens.insert(e, Hidden)) {
+                        error!("Failed to prewarm {:?}: {}", kind, e);
+                    }
+                    pools.free.entry(kind).or_default().push(e);
+                }
+            }
+            self.prewarmed = true;
+        }
+
+        // an entity may be released by several systems in the same frame
+        let mut released = pools.released.split_off(0);
+        released.sort();
+        released.dedup();
+        for e in released {
+            if !entities.is_alive(e) {
+                continue;
+            }
+            let kind = match pooled.get(e) {
+                Some(Pooled(kind)) if config.enabled => *kind,
+                _ => {
+                    if let Err(e) = entities.delete(e) {
+                        error!("Failed to destroy entity: {}", e);
+                    }
+                    continue;
+                },
+            };
+            if !pools.park(kind, e, config.size(kind).capacity) {
+                if let Err(e) = entities.delete(e) {
+                    error!("Failed to destroy entity: {}", e);
+                }
+                continue;
+            }
+            // parked entities keep their transform and sprite, without the
+            // gameplay components no system sees them
+            physicals.remove(e);
+            colliders.remove(e);
+            outlines.remove(e);
+            bullets.remove(e);
+            asteroids.remove(e);
+            animation_storage.remove(e);
+            if let Err(e) = hiddens.insert(e, Hidden) {
+                error!("Failed to park {:?}: {}", kind, e);
+            }
+        }
+
+        for spawn in pools.spawns.split_off(0) {
+            let kind = spawn.kind();
+            let animation = match spawn {
+                Spawn::Explosion { .. } => match animations.get("explosion.explode") {
+                    Some(animation) => Some(animation),
+                    None => continue,
+                },
+                _ => None,
+            };
+            let e = match pools.acquire(kind) {
+                Some(e) => {
+                    hiddens.remove(e);
+                    e
+                },
+                None => {
+                    let e = entities.create();
+                    if config.enabled {
+                        if let Err(e) = pooled.insert(e, Pooled(kind)) {
+                            error!("Failed to pool {:?}: {}", kind, e);
+                        }
+                    }
+                    e
+                },
+            };
+
+            let result = match spawn {
+                Spawn::Bullet { transform, physical } => {
+                    let sprite = sprites.as_ref().map(|sprites| sprites.sprite_render("bullet"));
+                    bullets.insert(e, Bullet { ricochets: 0 })
+                        .and(transforms.insert(e, transform))
+                        .and(physicals.insert(e, physical))
+                        .and(colliders.insert(e, Collider {
+                            typ: ColliderType::Bullet,
+                            shape: ColliderShape::Circle(2.0),
+                        }))
+                        .and(outlines.insert(e, Outline::bullet()))
+                        .and(sprite.map_or(Ok(None), |sprite| sprite_renders.insert(e, sprite)))
+                        .map(|_| ())
+                },
+                Spawn::Asteroid { size, position, velocity, rotation, seed } => {
+                    let (asteroid, transform, physical, collider, outline, sprite) =
+                        asteroid_components(sprites.as_deref(), size, position, velocity,
+                                            rotation, seed);
+                    asteroids.insert(e, asteroid)
+                        .and(transforms.insert(e, transform))
+                        .and(physicals.insert(e, physical))
+                        .and(colliders.insert(e, collider))
+                        .and(outlines.insert(e, outline))
+                        .and(sprite.map_or(Ok(None), |sprite| sprite_renders.insert(e, sprite)))
+                        .map(|_| ())
+                },
+                Spawn::Explosion { transform } => {
+                    let sprite = sprites.as_ref().map(|sprites| sprites.sprite_render("explosion.0"));
+                    transforms.insert(e, transform)
+                        .and(animation.map_or(Ok(None), |animation| animation_storage.insert(e, animation)))
+                        .and(sprite.map_or(Ok(None), |sprite| sprite_renders.insert(e, sprite)))
+                        .map(|_| ())
+                },
+            };
+            if let Err(e) = result {
+                error!("Failed to spawn {:?}: {}", kind, e);
+            }
+        }
+    }
+}
@@ -1,65 +1,16 @@
 use amethyst::{
-    assets::{Loader},
+    assets::{Loader, ProgressCounter},
     ecs::{World},
     ecs::prelude::{Entity},
     prelude::*,
-    renderer::{SpriteRender},
-    ui::{Anchor, TtfFormat, UiText, UiTransform, FontHandle},
+    ui::{TtfFormat, FontHandle},
 };
 
 use rand;
-use crate::textures::{SpriteStore};
+use serde::{Deserialize, Serialize};
 
-pub struct ShipRes {
-    pub sprite_store: SpriteStore,
-}
-
-impl ShipRes {
-    pub fn initialize(world: &mut World) {
-        let sprite_store = SpriteStore::from_path(world, "ship");
-        world.insert(
-            ShipRes { sprite_store : sprite_store }
-        );
-    }
-
-    pub fn sprite_render(&self) -> SpriteRender {
-        self.sprite_store.sprite_renderer(0)
-    }
-}
-
-pub struct BulletRes {
-    pub sprite_store: SpriteStore,
-}
-
-impl BulletRes {
-    pub fn initialize(world: &mut World) {
-        let sprite_store = SpriteStore::from_path(world, "bullet");
-        world.insert(
-            BulletRes { sprite_store: sprite_store }
-        );
-    }
-
-    pub fn sprite_render(&self) -> SpriteRender {
-        self.sprite_store.sprite_renderer(0)
-    }
-}
-
-pub struct AsteroidRes {
-    pub sprite_store: SpriteStore,
-}
-
-impl AsteroidRes {
-    pub fn initialize(world: &mut World) {
-        let sprite_store = SpriteStore::from_path(world, "asteroids");
-        world.insert(
-            AsteroidRes { sprite_store: sprite_store }
-        );
-    }
-
-    pub fn sprite_render(&self, id: usize) -> SpriteRender {
-        self.sprite_store.sprite_renderer(id)
-    }
-}
+use crate::hazards::{HazardConfig};
+use crate::level::{Edges};
 
 pub struct RandomGen;
 
@@ -75,33 +26,16 @@ impl RandomGen {
     }
 }
 
-pub struct ExplosionRes {
-    pub sprite_store: SpriteStore,
-}
-
-impl ExplosionRes {
-    pub fn initialize(world: &mut World) {
-        let sprite_store = SpriteStore::from_path(world, "explosion");
-        world.insert(
-            ExplosionRes { sprite_store: sprite_store }
-        );
-    }
-
-    pub fn sprite_render(&self) -> SpriteRender {
-        self.sprite_store.sprite_renderer(0)
-    }
-}
-
 pub struct FontRes {
     pub font : FontHandle
 }
 
 impl FontRes {
-    pub fn initialize(world: &mut World) {
+    pub fn initialize(world: &mut World, progress: &mut ProgressCounter) {
         let font = world.read_resource::<Loader>().load(
             "font/square.ttf",
             TtfFormat,
-            (),
+            progress,
             &world.read_resource(),
         );
         world.insert(
@@ -114,27 +48,108 @@ impl FontRes {
 }
 
 
-pub struct ScoreRes {
-    pub score: i32,
-    pub text: Entity,
+
+// whether the play state is a real game or the attract mode demo
+#[derive(Clone, Copy, PartialEq)]
+pub enum GameMode {
+    Normal,
+    Demo,
 }
 
-impl ScoreRes {
-    pub fn initialize(world: &mut World) {
-        let font = world.read_resource::<FontRes>().font();
-        let score_transform = UiTransform::new(
-            "score".to_string(), Anchor::TopMiddle, Anchor::TopMiddle,
-            400., -20., 1., 200., 50.);
-        let text = world
-            .create_entity()
-            .with(score_transform)
-            .with(UiText::new(font, "".to_string(), [0.,0.,0.,1.], 50.))
-            .build();
-
-        world.insert(ScoreRes {
-            score: 0,
-            text: text
-        });
+impl Default for GameMode {
+    fn default() -> Self {
+        GameMode::Normal
     }
 }
 
+#[derive(Default)]
+pub struct HighScores {
+    // sorted, highest first
+    pub scores: Vec<i32>,
+}
+
+impl HighScores {
+    pub const MAX_ENTRIES: usize = 10;
+
+    pub fn record(&mut self, score: i32) {
+        let pos = self.scores.iter().position(|&s| s < score).unwrap_or(self.scores.len());
+        self.scores.insert(pos, score);
+        self.scores.truncate(Self::MAX_ENTRIES);
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ViewMode {
+    // keep the arena size, fill the extra window space with bars
+    Letterbox,
+    // enlarge the arena to the aspect ratio of the window
+    Extend,
+}
+
+// the arena as configured in config/arena.ron
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ArenaConfig {
+    pub width: f32,
+    pub height: f32,
+    pub view_mode: ViewMode,
+    // gravity wells and black holes placed in the arena
+    pub hazards: Vec<HazardConfig>,
+    pub edges: Edges,
+}
+
+impl Default for ArenaConfig {
+    fn default() -> Self {
+        Self {
+            width: 300.0,
+            height: 300.0,
+            view_mode: ViewMode::Letterbox,
+            hazards: vec![],
+            edges: Edges::default(),
+        }
+    }
+}
+
+// the current play area, [0, width] x [0, height]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Arena {
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Arena {
+    pub fn from_config(config: &ArenaConfig) -> Self {
+        Self {
+            width: config.width,
+            height: config.height,
+        }
+    }
+}
+
+// the part of the window showing the arena, in screen pixels
+#[derive(Default)]
+pub struct Viewport {
+    pub width: f32,
+    pub height: f32,
+    // ui root covering the viewport, HUD elements are its children
+    pub hud: Option<Entity>,
+}
+
+// fit the arena into a screen, returns the arena size, the size of the
+// camera view in world units and the viewport size in pixels
+pub fn fit_view(screen: (f32, f32), config: &ArenaConfig) -> (Arena, (f32, f32), (f32, f32)) {
+    let scale = (screen.0 / config.width).min(screen.1 / config.height);
+    let view = (screen.0 / scale, screen.1 / scale);
+    match config.view_mode {
+        ViewMode::Letterbox => (
+            Arena::from_config(config),
+            view,
+            (config.width * scale, config.height * scale),
+        ),
+        ViewMode::Extend => (
+            Arena { width: view.0, height: view.1 },
+            view,
+            screen,
+        ),
+    }
+}
@@ -0,0 +1,359 @@
+use amethyst::{
+    core::{
+        math::{Vector2, Vector3},
+        transform::components::Transform,
+        timing::Time,
+    },
+    ecs::{Join,
+          ReadStorage, WriteStorage,
+          System, SystemData,
+          Read, Write, ReadExpect, WriteExpect,
+          Entities,
+          World},
+    prelude::{Trans, TransEvent, GameData, StateEvent},
+    shrev::{EventChannel, ReaderId},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::components::{Asteroid, AsteroidSize, Physical, Ship};
+use crate::hud::{HudEvent};
+use crate::pool::{EntityPools};
+use crate::resources::{Arena, GameMode};
+use crate::system::{GameEvent};
+use crate::states;
+
+// scoring rules as configured in config/scoring.ron
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ScoringConfig {
+    // points of an asteroid before the multiplier, [small, medium, large]
+    pub points: [i32; 3],
+    // seconds after a hit in which the next hit continues the combo
+    pub combo_window: f32,
+    // consecutive hits needed to raise the multiplier by one
+    pub combo_step: u32,
+    pub max_multiplier: u32,
+    // an extra life every this many points, 0 disables extra lives
+    pub bonus_life_every: i32,
+    pub starting_lives: u32,
+}
+
+impl Default for ScoringConfig {
+    fn default() -> Self {
+        Self {
+            points: [100, 50, 20],
+            combo_window: 2.0,
+            combo_step: 3,
+            max_multiplier: 8,
+            bonus_life_every: 10000,
+            starting_lives: 3,
+        }
+    }
+}
+
+impl ScoringConfig {
+    pub fn points(&self, size: AsteroidSize) -> i32 {
+        match size {
+            AsteroidSize::Small => self.points[0],
+            AsteroidSize::Medium => self.points[1],
+            AsteroidSize::Large => self.points[2],
+        }
+    }
+}
+
+// score, combo and lives of the running game
+#[derive(Clone, Debug, Default)]
+pub struct Scoring {
+    pub score: i32,
+    pub lives: u32,
+    // consecutive hits of the current combo
+    pub combo: u32,
+    // seconds left to continue the combo
+    pub combo_timer: f32,
+    pub best_multiplier: u32,
+    pub lives_awarded: u32,
+    next_bonus: i32,
+}
+
+impl Scoring {
+    pub fn new(config: &ScoringConfig) -> Self {
+        Self {
+            lives: config.starting_lives.max(1),
+            best_multiplier: 1,
+            next_bonus: config.bonus_life_every,
+            ..Default::default()
+        }
+    }
+
+    pub fn multiplier(&self, config: &ScoringConfig) -> u32 {
+        (1 + self.combo / config.combo_step.max(1)).min(config.max_multiplier.max(1))
+    }
+
+    // a bullet left the arena without hitting anything
+    pub fn miss(&mut self) {
+        self.combo = 0;
+        self.combo_timer = 0.0;
+    }
+
+    // returns the points awarded
+    pub fn hit(&mut self, size: AsteroidSize, config: &ScoringConfig) -> i32 {
+        self.combo += 1;
+        self.combo_timer = config.combo_window;
+        let multiplier = self.multiplier(config);
+        self.best_multiplier = self.best_multiplier.max(multiplier);
+        let points = config.points(size) * multiplier as i32;
+        self.score += points;
+        points
+    }
+
+    // the combo ends when the window passes without a hit
+    pub fn update(&mut self, delta: f32) {
+        if self.combo_timer > 0.0 {
+            self.combo_timer -= delta;
+            if self.combo_timer <= 0.0 {
+                self.miss();
+            }
+        }
+    }
+
+    // give the extra lives of all thresholds passed, returns how many
+    pub fn award_bonus_lives(&mut self, config: &ScoringConfig) -> u32 {
+        if config.bonus_life_every <= 0 {
+            return 0;
+        }
+        let mut awarded = 0;
+        while self.score >= self.next_bonus {
+            self.next_bonus += config.bonus_life_every;
+            awarded += 1;
+        }
+        self.lives += awarded;
+        self.lives_awarded += awarded;
+        awarded
+    }
+
+    // returns whether a life is left
+    pub fn lose_life(&mut self) -> bool {
+        self.lives = self.lives.saturating_sub(1);
+        self.miss();
+        self.lives > 0
+    }
+}
+
+// asteroids this close to the arena center are removed when the ship respawns
+const RESPAWN_CLEAR_RADIUS: f32 = 40.0;
+
+// apply the game events to the score and the lives, respawn the ship or
+// end the game when it is destroyed
+#[derive(Default)]
+pub struct ScoringSystem {
+    event_reader: Option<ReaderId<GameEvent>>,
+}
+
+impl<'s> System<'s> for ScoringSystem {
+    type SystemData = (
+        Entities<'s>,
+        WriteExpect<'s, Scoring>,
+        ReadExpect<'s, ScoringConfig>,
+        ReadExpect<'s, Arena>,
+        Read<'s, GameMode>,
+        Read<'s, Time>,
+        Read<'s, EventChannel<GameEvent>>,
+        Write<'s, EntityPools>,
+        Write<'s, EventChannel<HudEvent>>,
+        Write<'s, EventChannel<TransEvent<GameData<'static, 'static>, StateEvent>>>,
+        ReadStorage<'s, Ship>,
+        ReadStorage<'s, Asteroid>,
+        WriteStorage<'s, Physical>,
+        WriteStorage<'s, Transform>,
+    );
+
+    fn setup(&mut self, world: &mut World) {
+        Self::SystemData::setup(world);
+        self.event_reader = Some(
+            world
+                .fetch_mut::<EventChannel<GameEvent>>()
+                .register_reader()
+        )
+    }
+
+    fn run(&mut self,
+           (entities,
+            mut scoring,
+            config,
+            arena,
+            mode,
+            time,
+            events,
+            mut pools,
+            mut hud_events,
+            mut trans_events,
+            ships,
+            asteroids,
+            mut physicals,
+            mut transforms): Self::SystemData) {
+        let old = (scoring.score, scoring.lives, scoring.multiplier(&config));
+        scoring.update(time.delta_seconds());
+
+        let mut ship_destroyed = false;
+        for event in events.read(self.event_reader.as_mut().unwrap()) {
+            match *event {
+                GameEvent::ShotMissed => scoring.miss(),
+                GameEvent::AsteroidDestroyed { size, .. } => {
+                    scoring.hit(size, &config);
+                    scoring.award_bonus_lives(&config);
+                },
+                GameEvent::ShipDestroyed { .. } => ship_destroyed = true,
+                _ => {},
+            }
+        }
+
+        if ship_destroyed {
+            if scoring.lose_life() {
+                // back to the center, which is cleared of asteroids
+                let center = Vector3::new(arena.width * 0.5, arena.height * 0.5, 0.0);
+                for (_, physical, transform) in (&ships, &mut physicals, &mut transforms).join() {
+                    transform.set_translation(center);
+                    physical.velocity = Vector2::new(0.0, 0.0);
+                }
+                for (e, _, transform) in (&*entities, &asteroids, &transforms).join() {
+                    if (transform.translation() - center).norm() < RESPAWN_CLEAR_RADIUS {
+                        pools.release(e);
+                    }
+                }
+            } else {
+                let trans: TransEvent<GameData<'static, 'static>, StateEvent> = match *mode {
+                    // the attract mode simply ends and returns to the menu
+                    GameMode::Demo => Box::new(|| Trans::Pop),
+                    GameMode::Normal => Box::new(
+                        || Trans::Switch(Box::new(states::StateOver::new()))),
+                };
+                trans_events.single_write(trans);
+            }
+        }
+
+        if scoring.score != old.0 {
+            hud_events.single_write(HudEvent::Score(scoring.score));
+        }
+        if scoring.lives != old.1 {
+            hud_events.single_write(HudEvent::Lives(scoring.lives));
+        }
+        let multiplier = scoring.multiplier(&config);
+        if multiplier != old.2 {
+            hud_events.single_write(HudEvent::Multiplier(multiplier));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn starts_with_at_least_one_life() {
+        let config = ScoringConfig { starting_lives: 0, ..Default::default() };
+        let scoring = Scoring::new(&config);
+        assert_eq!(scoring.lives, 1);
+        assert_eq!(scoring.best_multiplier, 1);
+        assert_eq!(scoring.multiplier(&config), 1);
+    }
+
+    #[test]
+    fn multiplier_steps_and_cap() {
+        let config = ScoringConfig::default();
+        let mut scoring = Scoring::new(&config);
+        let multipliers: Vec<u32> = [0, 2, 3, 5, 6, 21, 100].iter()
+            .map(|&combo| {
+                scoring.combo = combo;
+                scoring.multiplier(&config)
+            })
+            .collect();
+        assert_eq!(multipliers, vec![1, 1, 2, 2, 3, 8, 8]);
+
+        // a zero step or cap in the config does not break it
+        let config = ScoringConfig { combo_step: 0, max_multiplier: 0, ..Default::default() };
+        scoring.combo = 5;
+        assert_eq!(scoring.multiplier(&config), 1);
+    }
+
+    #[test]
+    fn hits_score_with_the_multiplier() {
+        let config = ScoringConfig::default();
+        let mut scoring = Scoring::new(&config);
+        let points: Vec<i32> = (0..6).map(|_| scoring.hit(AsteroidSize::Small, &config)).collect();
+        assert_eq!(points, vec![100, 100, 200, 200, 200, 300]);
+        assert_eq!(scoring.score, 1100);
+        assert_eq!(scoring.best_multiplier, 3);
+        assert_eq!(scoring.hit(AsteroidSize::Large, &config), 60);
+    }
+
+    #[test]
+    fn miss_resets_the_combo() {
+        let config = ScoringConfig::default();
+        let mut scoring = Scoring::new(&config);
+        for _ in 0..4 {
+            scoring.hit(AsteroidSize::Medium, &config);
+        }
+        assert_eq!(scoring.multiplier(&config), 2);
+        scoring.miss();
+        assert_eq!(scoring.combo, 0);
+        assert_eq!(scoring.multiplier(&config), 1);
+        // the score and the best multiplier stay
+        assert_eq!(scoring.score, 300);
+        assert_eq!(scoring.best_multiplier, 2);
+    }
+
+    #[test]
+    fn combo_ends_after_the_window() {
+        let config = ScoringConfig::default();
+        let mut scoring = Scoring::new(&config);
+        scoring.hit(AsteroidSize::Small, &config);
+        scoring.update(1.5);
+        assert_eq!(scoring.combo, 1);
+        // a hit within the window starts it again
+        scoring.hit(AsteroidSize::Small, &config);
+        scoring.update(1.5);
+        assert_eq!(scoring.combo, 2);
+        scoring.update(0.5);
+        assert_eq!(scoring.combo, 0);
+        assert_eq!(scoring.combo_timer, 0.0);
+    }
+
+    #[test]
+    fn bonus_lives_for_every_threshold() {
+        let config = ScoringConfig::default();
+        let mut scoring = Scoring::new(&config);
+        scoring.score = 9999;
+        assert_eq!(scoring.award_bonus_lives(&config), 0);
+        // one hit may pass several thresholds
+        scoring.score = 25000;
+        assert_eq!(scoring.award_bonus_lives(&config), 2);
+        assert_eq!(scoring.lives, 5);
+        assert_eq!(scoring.lives_awarded, 2);
+        // each only once
+        assert_eq!(scoring.award_bonus_lives(&config), 0);
+        scoring.score = 30000;
+        assert_eq!(scoring.award_bonus_lives(&config), 1);
+        assert_eq!(scoring.lives, 6);
+    }
+
+    #[test]
+    fn bonus_lives_disabled() {
+        let config = ScoringConfig { bonus_life_every: 0, ..Default::default() };
+        let mut scoring = Scoring::new(&config);
+        scoring.score = 1_000_000;
+        assert_eq!(scoring.award_bonus_lives(&config), 0);
+        assert_eq!(scoring.lives, 3);
+    }
+
+    #[test]
+    fn losing_the_last_life() {
+        let config = ScoringConfig { starting_lives: 2, ..Default::default() };
+        let mut scoring = Scoring::new(&config);
+        scoring.hit(AsteroidSize::Small, &config);
+        assert!(scoring.lose_life());
+        assert_eq!(scoring.combo, 0);
+        assert!(!scoring.lose_life());
+        assert!(!scoring.lose_life());
+        assert_eq!(scoring.lives, 0);
+    }
+}
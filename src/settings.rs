@@ -0,0 +1,212 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use amethyst::{
+    utils::application_root_dir,
+    window::{DisplayConfig, Window},
+    winit::dpi::LogicalSize,
+};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+
+// window sizes offered by the settings screen
+pub const WINDOW_SIZES: [(u32, u32); 5] = [
+    (800, 800),
+    (1000, 1000),
+    (1280, 720),
+    (1600, 900),
+    (1920, 1080),
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Difficulty {
+    Easy,
+    Normal,
+    Hard,
+}
+
+impl Difficulty {
+    pub fn name(self) -> &'static str {
+        match self {
+            Difficulty::Easy => "Easy",
+            Difficulty::Normal => "Normal",
+            Difficulty::Hard => "Hard",
+        }
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            Difficulty::Easy => Difficulty::Normal,
+            Difficulty::Normal => Difficulty::Hard,
+            Difficulty::Hard => Difficulty::Easy,
+        }
+    }
+
+    pub fn prev(self) -> Self {
+        self.next().next()
+    }
+
+    // multiplier on the time between two asteroid spawns
+    pub fn spawn_time_scale(self) -> f32 {
+        match self {
+            Difficulty::Easy => 1.5,
+            Difficulty::Normal => 1.0,
+            Difficulty::Hard => 0.6,
+        }
+    }
+
+    // multiplier on the asteroid velocity
+    pub fn velocity_scale(self) -> f32 {
+        match self {
+            Difficulty::Easy => 0.7,
+            Difficulty::Normal => 1.0,
+            Difficulty::Hard => 1.4,
+        }
+    }
+}
+
+// how the game objects are drawn, picked when the renderer is built
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum RenderMode {
+    // the sprites of the texture atlas
+    Raster,
+    // glowing line outlines like the arcade original
+    Vector,
+}
+
+impl RenderMode {
+    pub fn name(self) -> &'static str {
+        match self {
+            RenderMode::Raster => "Sprites",
+            RenderMode::Vector => "Vector",
+        }
+    }
+
+    pub fn toggle(self) -> Self {
+        match self {
+            RenderMode::Raster => RenderMode::Vector,
+            RenderMode::Vector => RenderMode::Raster,
+        }
+    }
+}
+
+// user preferences, stored in config/settings.ron
+// any field missing in the file keeps its default value
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    // window size, None keeps the size in config/display.ron
+    pub dimensions: Option<(u32, u32)>,
+    pub fullscreen: bool,
+    // the renderer of amethyst 0.15 always presents with vsync, kept for
+    // when it can be turned off
+    pub vsync: bool,
+    // volumes in [0, 1]
+    pub master_volume: f32,
+    pub music_volume: f32,
+    pub effects_volume: f32,
+    pub difficulty: Difficulty,
+    // name of the file in levels/, None plays the endless arena
+    pub level: Option<String>,
+    // takes effect on the next start
+    pub render_mode: RenderMode,
+    // camera feedback, each can be turned off for accessibility
+    pub screen_shake: bool,
+    pub hit_stop: bool,
+    pub flash: bool,
+    // frame rate and entity count in the corner
+    pub debug_overlay: bool,
+    // the level editor in the menu, only set in the file
+    pub editor: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            dimensions: None,
+            fullscreen: false,
+            vsync: true,
+            master_volume: 1.0,
+            music_volume: 0.6,
+            effects_volume: 0.8,
+            difficulty: Difficulty::Normal,
+            level: None,
+            render_mode: RenderMode::Raster,
+            screen_shake: true,
+            hit_stop: true,
+            flash: true,
+            debug_overlay: false,
+            editor: false,
+        }
+    }
+}
+
+pub fn user_settings_path() -> amethyst::Result<PathBuf> {
+    Ok(application_root_dir()?.join("config").join("settings.ron"))
+}
+
+impl Settings {
+    // load the user settings, a missing or invalid file gives the defaults
+    pub fn load(path: &Path) -> Self {
+        if !path.exists() {
+            info!("No user settings at {}, using defaults", path.display());
+            return Settings::default();
+        }
+
+        let settings = fs::read_to_string(path)
+            .map_err(|e| e.to_string())
+            .and_then(|content| ron::de::from_str::<Settings>(&content)
+                      .map_err(|e| e.to_string()));
+        match settings {
+            Ok(settings) => settings.sanitized(),
+            Err(e) => {
+                warn!("Invalid user settings {}: {}, using defaults", path.display(), e);
+                Settings::default()
+            }
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> amethyst::Result<()> {
+        let content = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())?;
+        fs::write(path, content)?;
+        Ok(())
+    }
+
+    fn sanitized(mut self) -> Self {
+        let clamp = |v: f32| v.max(0.0).min(1.0);
+        self.master_volume = clamp(self.master_volume);
+        self.music_volume = clamp(self.music_volume);
+        self.effects_volume = clamp(self.effects_volume);
+        if let Some((w, h)) = self.dimensions {
+            if w == 0 || h == 0 {
+                warn!("Invalid window size {}x{} in user settings, ignored", w, h);
+                self.dimensions = None;
+            }
+        }
+        self
+    }
+
+    // override the shipped display config before the window is created
+    pub fn apply_display(&self, config: &mut DisplayConfig) {
+        if let Some(dimensions) = self.dimensions {
+            config.dimensions = Some(dimensions);
+        }
+        if !self.vsync {
+            warn!("The renderer always presents with vsync, the setting is ignored");
+        }
+    }
+
+    // apply the window related settings to a running window
+    pub fn apply_window(&self, window: &Window) {
+        if self.fullscreen {
+            window.set_fullscreen(Some(window.get_current_monitor()));
+        } else {
+            window.set_fullscreen(None);
+            if let Some((width, height)) = self.dimensions {
+                window.set_inner_size(LogicalSize::new(width as f64, height as f64));
+            }
+        }
+    }
+}
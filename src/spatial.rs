@@ -0,0 +1,471 @@
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+
+use amethyst::{
+    core::{
+        math::Vector2,
+        transform::components::Transform,
+    },
+    derive::{SystemDesc},
+    ecs::{Join,
+          ReadStorage,
+          System, SystemData,
+          ReadExpect, Write,
+          Entities},
+    ecs::prelude::{Entity},
+};
+
+use crate::components::{Collider, ColliderType};
+use crate::level::{Level};
+use crate::resources::{Arena};
+
+// something with a collider, indexed by the cell of its center
+#[derive(Clone, Copy, Debug)]
+pub struct GridEntry {
+    pub entity: Entity,
+    pub kind: ColliderType,
+    pub position: Vector2<f32>,
+    // of the bounding circle
+    pub radius: f32,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct RayHit {
+    pub entity: Entity,
+    pub kind: ColliderType,
+    // along the ray from its origin
+    pub distance: f32,
+    pub point: Vector2<f32>,
+}
+
+type Cell = (i32, i32);
+
+// everything with a collider by position, rebuilt at the start of every
+// frame by SpatialGridSystem; on a wrapping axis distances are measured
+// the short way around the arena
+pub struct SpatialGrid {
+    width: f32,
+    height: f32,
+    wrap: (bool, bool),
+    // the arena is split in whole cells so that wrapping maps cell to cell
+    columns: i32,
+    rows: i32,
+    cell: Vector2<f32>,
+    cells: HashMap<Cell, Vec<GridEntry>>,
+    max_radius: f32,
+    len: usize,
+}
+
+impl Default for SpatialGrid {
+    fn default() -> Self {
+        Self::new(300.0, 300.0, (true, true))
+    }
+}
+
+impl SpatialGrid {
+    // wanted cell size, the real one is a bit larger to fill the arena
+    pub const CELL_SIZE: f32 = 32.0;
+
+    pub fn new(width: f32, height: f32, wrap: (bool, bool)) -> Self {
+        let mut grid = Self {
+            width: 0.0,
+            height: 0.0,
+            wrap,
+            columns: 1,
+            rows: 1,
+            cell: Vector2::new(1.0, 1.0),
+            cells: HashMap::new(),
+            max_radius: 0.0,
+            len: 0,
+        };
+        grid.clear(width, height, wrap);
+        grid
+    }
+
+    // empty the grid for an arena of the given size
+    pub fn clear(&mut self, width: f32, height: f32, wrap: (bool, bool)) {
+        self.width = width.max(1.0);
+        self.height = height.max(1.0);
+        self.wrap = wrap;
+        self.columns = ((self.width / Self::CELL_SIZE).floor() as i32).max(1);
+        self.rows = ((self.height / Self::CELL_SIZE).floor() as i32).max(1);
+        self.cell = Vector2::new(self.width / self.columns as f32, self.height / self.rows as f32);
+        // the vectors are kept to be filled again
+        for entries in self.cells.values_mut() {
+            entries.clear();
+        }
+        self.max_radius = 0.0;
+        self.len = 0;
+    }
+
+    pub fn insert(&mut self, entity: Entity, kind: ColliderType, position: Vector2<f32>, radius: f32) {
+        let cell = self.cell_of(position);
+        self.cells.entry(cell).or_default().push(GridEntry { entity, kind, position, radius });
+        self.max_radius = self.max_radius.max(radius);
+        self.len += 1;
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    // whether the x and the y axis wrap around
+    pub fn wraps(&self) -> (bool, bool) {
+        self.wrap
+    }
+
+    // the shortest way from one point to another, across a wrapping edge
+    // if that is shorter
+    pub fn offset(&self, from: Vector2<f32>, to: Vector2<f32>) -> Vector2<f32> {
+        let mut offset = to - from;
+        if self.wrap.0 {
+            offset.x -= self.width * (offset.x / self.width).round();
+        }
+        if self.wrap.1 {
+            offset.y -= self.height * (offset.y / self.height).round();
+        }
+        offset
+    }
+
+    fn wrap_cell(&self, (x, y): Cell) -> Cell {
+        (if self.wrap.0 { x.rem_euclid(self.columns) } else { x },
+         if self.wrap.1 { y.rem_euclid(self.rows) } else { y })
+    }
+
+    fn cell_of(&self, position: Vector2<f32>) -> Cell {
+        self.wrap_cell(((position.x / self.cell.x).floor() as i32,
+                        (position.y / self.cell.y).floor() as i32))
+    }
+
+    // the cells holding the centers of everything within `reach` of a
+    // point on each axis, each cell once
+    fn cells_around(&self, point: Vector2<f32>, reach: f32) -> Vec<Cell> {
+        let range = |center: f32, size: f32, count: i32, wrap: bool| {
+            let low = ((center - reach) / size).floor() as i32;
+            let high = ((center + reach) / size).floor() as i32;
+            // all the cells of a wrapping axis at most
+            if wrap && high - low >= count { (0, count - 1) } else { (low, high) }
+        };
+        let (x0, x1) = range(point.x, self.cell.x, self.columns, self.wrap.0);
+        let (y0, y1) = range(point.y, self.cell.y, self.rows, self.wrap.1);
+        let mut cells = Vec::with_capacity(((x1 - x0 + 1) * (y1 - y0 + 1)) as usize);
+        for x in x0..=x1 {
+            for y in y0..=y1 {
+                let cell = self.wrap_cell((x, y));
+                if self.cells.contains_key(&cell) {
+                    cells.push(cell);
+                }
+            }
+        }
+        cells
+    }
+
+    fn entries_around(&self, point: Vector2<f32>, reach: f32) -> impl Iterator<Item = &GridEntry> {
+        self.cells_around(point, reach).into_iter()
+            .flat_map(move |cell| self.cells[&cell].iter())
+    }
+
+    // everything whose bounding circle touches the circle around a point
+    pub fn query_radius(&self, point: Vector2<f32>, radius: f32) -> Vec<&GridEntry> {
+        self.entries_around(point, radius + self.max_radius)
+            .filter(|entry| self.offset(point, entry.position).norm() <= radius + entry.radius)
+            .collect()
+    }
+
+    // the closest center of the given kind not farther than `max_distance`
+    pub fn nearest(&self, point: Vector2<f32>, kind: ColliderType,
+                   max_distance: f32) -> Option<&GridEntry> {
+        // everything is found once the search covers the whole arena twice
+        let limit = max_distance.min(2.0 * self.width.max(self.height));
+        let mut reach = self.cell.x.min(self.cell.y);
+        loop {
+            let reach_now = reach.min(limit);
+            let best = self.entries_around(point, reach_now)
+                .filter(|entry| entry.kind == kind)
+                .map(|entry| (entry, self.offset(point, entry.position).norm()))
+                .filter(|(_, distance)| *distance <= max_distance)
+                .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(Ordering::Equal));
+            // anything closer would have its center inside the searched cells
+            match best {
+                Some((entry, distance)) if distance <= reach_now => return Some(entry),
+                _ if reach_now >= limit => return best.map(|(entry, _)| entry),
+                _ => reach *= 2.0,
+            }
+        }
+    }
+
+    // the first bounding circle on a ray, `filter` skips what the ray goes
+    // through; a ray crossing a wrapping edge goes on from the other side
+    pub fn ray_cast<F>(&self, origin: Vector2<f32>, direction: Vector2<f32>,
+                       max_distance: f32, filter: F) -> Option<RayHit>
+        where F: Fn(&GridEntry) -> bool {
+        let norm = direction.norm();
+        if norm == 0.0 || max_distance <= 0.0 {
+            return None;
+        }
+        let direction = direction / norm;
+        let step = self.cell.x.min(self.cell.y);
+        let samples = (max_distance / step).ceil() as usize;
+
+        // a circle on a wrapping arena has copies, each is tested once
+        let mut tested = HashSet::new();
+        let mut best: Option<RayHit> = None;
+        for i in 0..=samples {
+            let t = (i as f32 * step).min(max_distance);
+            let sample = origin + direction * t;
+            for entry in self.entries_around(sample, step * 0.5 + self.max_radius) {
+                if !filter(entry) {
+                    continue;
+                }
+                // the copy of the circle closest to this part of the ray
+                let center = sample + self.offset(sample, entry.position);
+                let shift = center - entry.position;
+                let copy = ((shift.x / self.width).round() as i32, (shift.y / self.height).round() as i32);
+                if !tested.insert((entry.entity, copy)) {
+                    continue;
+                }
+                let distance = match ray_circle(origin, direction, center, entry.radius) {
+                    Some(distance) if distance <= max_distance => distance,
+                    _ => continue,
+                };
+                if best.map_or(true, |hit| distance < hit.distance) {
+                    best = Some(RayHit {
+                        entity: entry.entity,
+                        kind: entry.kind,
+                        distance,
+                        point: origin + direction * distance,
+                    });
+                }
+            }
+            // circles found further on are hit after this point
+            if let Some(hit) = best {
+                if hit.distance <= t + step * 0.5 {
+                    break;
+                }
+            }
+        }
+        best
+    }
+}
+
+// distance along a normalized ray to where it enters a circle, 0 when it
+// starts inside
+fn ray_circle(origin: Vector2<f32>, direction: Vector2<f32>,
+              center: Vector2<f32>, radius: f32) -> Option<f32> {
+    let to_origin = origin - center;
+    let c = to_origin.norm_squared() - radius * radius;
+    if c <= 0.0 {
+        return Some(0.0);
+    }
+    let b = to_origin.dot(&direction);
+    let discriminant = b * b - c;
+    if b > 0.0 || discriminant < 0.0 {
+        return None;
+    }
+    Some(-b - discriminant.sqrt())
+}
+
+// refill the grid with every collider, before any system of the frame
+// looks at it
+#[derive(SystemDesc)]
+pub struct SpatialGridSystem;
+
+impl<'s> System<'s> for SpatialGridSystem {
+    type SystemData = (
+        Entities<'s>,
+        ReadStorage<'s, Collider>,
+        ReadStorage<'s, Transform>,
+        ReadExpect<'s, Arena>,
+        ReadExpect<'s, Level>,
+        Write<'s, SpatialGrid>,
+    );
+
+    fn run(&mut self,
+           (entities,
+            colliders,
+            transforms,
+            arena,
+            level,
+            mut grid): Self::SystemData) {
+        grid.clear(arena.width, arena.height, level.edges.wraps());
+        for (e, collider, transform) in (&entities, &colliders, &transforms).join() {
+            let position = Vector2::new(transform.translation().x, transform.translation().y);
+            grid.insert(e, collider.typ, position,
+                        collider.shape.bounding_radius() * transform.scale().x);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use amethyst::ecs::{World, WorldExt};
+    use amethyst::ecs::prelude::Builder;
+
+    fn entities(count: usize) -> Vec<Entity> {
+        let mut world = World::new();
+        (0..count).map(|_| world.create_entity().build()).collect()
+    }
+
+    fn grid(wrap: bool) -> SpatialGrid {
+        SpatialGrid::new(300.0, 300.0, (wrap, wrap))
+    }
+
+    fn found(entries: Vec<&GridEntry>) -> Vec<Entity> {
+        let mut found: Vec<Entity> = entries.iter().map(|entry| entry.entity).collect();
+        found.sort();
+        found
+    }
+
+    #[test]
+    fn offset_takes_the_short_way() {
+        let from = Vector2::new(290.0, 10.0);
+        let to = Vector2::new(10.0, 290.0);
+        assert_eq!(grid(true).offset(from, to), Vector2::new(20.0, -20.0));
+        assert_eq!(grid(false).offset(from, to), Vector2::new(-280.0, 280.0));
+    }
+
+    #[test]
+    fn query_radius_finds_touching_circles() {
+        let e = entities(3);
+        let mut grid = grid(true);
+        grid.insert(e[0], ColliderType::Asteroid, Vector2::new(100.0, 100.0), 5.0);
+        grid.insert(e[1], ColliderType::Asteroid, Vector2::new(112.0, 100.0), 5.0);
+        grid.insert(e[2], ColliderType::Asteroid, Vector2::new(130.0, 100.0), 5.0);
+        assert_eq!(grid.len(), 3);
+        assert_eq!(found(grid.query_radius(Vector2::new(100.0, 100.0), 7.0)), vec![e[0], e[1]]);
+        assert!(grid.query_radius(Vector2::new(200.0, 200.0), 10.0).is_empty());
+    }
+
+    #[test]
+    fn query_radius_across_a_wrapping_edge() {
+        let e = entities(2);
+        for &wrap in [true, false].iter() {
+            let mut grid = grid(wrap);
+            grid.insert(e[0], ColliderType::Asteroid, Vector2::new(295.0, 150.0), 5.0);
+            grid.insert(e[1], ColliderType::Asteroid, Vector2::new(150.0, 297.0), 5.0);
+            let left = grid.query_radius(Vector2::new(3.0, 150.0), 5.0);
+            let bottom = grid.query_radius(Vector2::new(150.0, 2.0), 5.0);
+            if wrap {
+                assert_eq!(found(left), vec![e[0]]);
+                assert_eq!(found(bottom), vec![e[1]]);
+            } else {
+                assert!(left.is_empty());
+                assert!(bottom.is_empty());
+            }
+        }
+    }
+
+    #[test]
+    fn nearest_with_and_without_wrap() {
+        let e = entities(3);
+        for &wrap in [true, false].iter() {
+            let mut grid = grid(wrap);
+            grid.insert(e[0], ColliderType::Asteroid, Vector2::new(290.0, 150.0), 5.0);
+            grid.insert(e[1], ColliderType::Asteroid, Vector2::new(40.0, 150.0), 5.0);
+            // closest of all, but not an asteroid
+            grid.insert(e[2], ColliderType::Bullet, Vector2::new(8.0, 150.0), 2.0);
+            let nearest = grid.nearest(Vector2::new(5.0, 150.0), ColliderType::Asteroid, 1000.0)
+                .map(|entry| entry.entity);
+            assert_eq!(nearest, Some(if wrap { e[0] } else { e[1] }));
+        }
+    }
+
+    #[test]
+    fn nearest_far_away() {
+        let e = entities(1);
+        let mut grid = grid(false);
+        grid.insert(e[0], ColliderType::Asteroid, Vector2::new(280.0, 280.0), 5.0);
+        let nearest = grid.nearest(Vector2::new(10.0, 10.0), ColliderType::Asteroid, 1000.0);
+        assert_eq!(nearest.map(|entry| entry.entity), Some(e[0]));
+        assert!(grid.nearest(Vector2::new(10.0, 10.0), ColliderType::Ship, 1000.0).is_none());
+    }
+
+    #[test]
+    fn nearest_within_max_distance() {
+        let e = entities(1);
+        let mut grid = grid(true);
+        grid.insert(e[0], ColliderType::Asteroid, Vector2::new(150.0, 100.0), 5.0);
+        let point = Vector2::new(100.0, 100.0);
+        assert!(grid.nearest(point, ColliderType::Asteroid, 40.0).is_none());
+        assert_eq!(grid.nearest(point, ColliderType::Asteroid, 50.0).map(|entry| entry.entity),
+                   Some(e[0]));
+    }
+
+    #[test]
+    fn ray_hits_the_first_circle() {
+        let e = entities(3);
+        let mut grid = grid(true);
+        grid.insert(e[0], ColliderType::Asteroid, Vector2::new(100.0, 150.0), 5.0);
+        grid.insert(e[1], ColliderType::Asteroid, Vector2::new(60.0, 150.0), 5.0);
+        grid.insert(e[2], ColliderType::Bullet, Vector2::new(30.0, 150.0), 2.0);
+        let origin = Vector2::new(10.0, 150.0);
+        let direction = Vector2::new(2.0, 0.0);
+
+        let hit = grid.ray_cast(origin, direction, 200.0,
+                                |entry| entry.kind == ColliderType::Asteroid).unwrap();
+        assert_eq!(hit.entity, e[1]);
+        assert_eq!(hit.kind, ColliderType::Asteroid);
+        assert_eq!(hit.distance, 45.0);
+        assert_eq!(hit.point, Vector2::new(55.0, 150.0));
+
+        // the bullet is not skipped without a filter
+        assert_eq!(grid.ray_cast(origin, direction, 200.0, |_| true).unwrap().entity, e[2]);
+        // too short to reach it
+        assert!(grid.ray_cast(origin, direction, 40.0,
+                              |entry| entry.kind == ColliderType::Asteroid).is_none());
+        // going the other way
+        assert!(grid.ray_cast(origin, -direction, 200.0,
+                              |entry| entry.kind == ColliderType::Asteroid).is_none());
+    }
+
+    #[test]
+    fn ray_starting_inside_a_circle() {
+        let e = entities(1);
+        let mut grid = grid(true);
+        grid.insert(e[0], ColliderType::Asteroid, Vector2::new(100.0, 100.0), 5.0);
+        let hit = grid.ray_cast(Vector2::new(102.0, 100.0), Vector2::new(1.0, 0.0), 50.0, |_| true);
+        assert_eq!(hit.map(|hit| hit.distance), Some(0.0));
+    }
+
+    #[test]
+    fn ray_goes_on_through_a_wrapping_edge() {
+        let e = entities(1);
+        let mut grid = grid(true);
+        grid.insert(e[0], ColliderType::Asteroid, Vector2::new(30.0, 150.0), 5.0);
+        let hit = grid.ray_cast(Vector2::new(280.0, 150.0), Vector2::new(1.0, 0.0), 100.0, |_| true)
+            .unwrap();
+        assert_eq!(hit.entity, e[0]);
+        assert!((hit.distance - 45.0).abs() < 1e-3);
+        // along the ray, not wrapped back into the arena
+        assert!((hit.point.x - 325.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn ray_stops_at_a_non_wrapping_edge() {
+        let e = entities(1);
+        let mut grid = grid(false);
+        grid.insert(e[0], ColliderType::Asteroid, Vector2::new(30.0, 150.0), 5.0);
+        let origin = Vector2::new(280.0, 150.0);
+        assert!(grid.ray_cast(origin, Vector2::new(1.0, 0.0), 100.0, |_| true).is_none());
+        // the circle is still there the other way round
+        let hit = grid.ray_cast(origin, Vector2::new(-1.0, 0.0), 300.0, |_| true).unwrap();
+        assert_eq!(hit.entity, e[0]);
+        assert!((hit.distance - 245.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn clear_empties_the_grid() {
+        let e = entities(1);
+        let mut grid = grid(true);
+        grid.insert(e[0], ColliderType::Asteroid, Vector2::new(30.0, 150.0), 5.0);
+        grid.clear(200.0, 100.0, (false, true));
+        assert!(grid.is_empty());
+        assert_eq!(grid.wraps(), (false, true));
+        assert!(grid.query_radius(Vector2::new(30.0, 150.0), 10.0).is_empty());
+    }
+}
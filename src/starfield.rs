@@ -0,0 +1,243 @@
+use std::f32::consts::PI;
+
+use amethyst::{
+    core::{
+        math::{Vector2, Vector3},
+        transform::components::Transform,
+        timing::Time,
+    },
+    derive::{SystemDesc},
+    ecs::{Join,
+          ReadStorage, WriteStorage,
+          System, SystemData,
+          Read, Write, ReadExpect,
+          Entities,
+          World},
+    ecs::prelude::{Entity},
+    renderer::{SpriteRender, palette::Srgba, resources::Tint, transparent::Transparent},
+};
+use log::{error};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+use crate::components::{Physical, Ship};
+use crate::resources::{Arena};
+use crate::textures::{SpriteRegistry};
+
+// one layer of stars, farther layers scroll slower
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct StarLayer {
+    // stars per 100 x 100 units of arena
+    pub stars: f32,
+    // fraction of the ship velocity the layer scrolls with
+    pub parallax: f32,
+    // scale of the particle sprite
+    pub size: f32,
+    pub brightness: f32,
+}
+
+// the background as configured in config/starfield.ron
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StarfieldConfig {
+    pub seed: u64,
+    // multiplier on the number of stars of every layer
+    pub density: f32,
+    pub layers: Vec<StarLayer>,
+}
+
+impl Default for StarfieldConfig {
+    fn default() -> Self {
+        Self {
+            seed: 1979,
+            density: 1.0,
+            layers: vec![
+                StarLayer { stars: 6.0, parallax: 0.02, size: 0.25, brightness: 0.5 },
+                StarLayer { stars: 3.0, parallax: 0.05, size: 0.35, brightness: 0.75 },
+                StarLayer { stars: 1.5, parallax: 0.1, size: 0.5, brightness: 1.0 },
+            ],
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Star {
+    // position without scrolling
+    pub base: Vector2<f32>,
+    pub parallax: f32,
+    pub size: f32,
+    pub brightness: f32,
+    // twinkle phase (rad) and speed (rad / s)
+    phase: f32,
+    rate: f32,
+}
+
+// the stars behind the arena, regenerated when the arena size changes
+#[derive(Default)]
+pub struct Starfield {
+    pub stars: Vec<Star>,
+    // accumulated scroll, scaled by the parallax of each star
+    pub offset: Vector2<f32>,
+    // arena size the stars were generated for
+    size: (f32, f32),
+}
+
+impl Starfield {
+    // the same config and size always give the same sky
+    pub fn generate(config: &StarfieldConfig, width: f32, height: f32) -> Self {
+        let mut rng = StdRng::seed_from_u64(config.seed);
+        let area = width * height / 10000.0;
+        let mut stars = vec![];
+        for layer in &config.layers {
+            let count = (layer.stars * config.density.max(0.0) * area).round() as usize;
+            for _ in 0..count {
+                stars.push(Star {
+                    base: Vector2::new(rng.gen::<f32>() * width, rng.gen::<f32>() * height),
+                    parallax: layer.parallax,
+                    size: layer.size,
+                    brightness: layer.brightness,
+                    phase: rng.gen::<f32>() * 2.0 * PI,
+                    rate: rng.gen_range(1.0, 4.0),
+                });
+            }
+        }
+
+        Self {
+            stars,
+            offset: Vector2::new(0.0, 0.0),
+            size: (width, height),
+        }
+    }
+
+    pub fn fits(&self, arena: &Arena) -> bool {
+        self.size == (arena.width, arena.height)
+    }
+
+    // move opposite to the given velocity
+    pub fn scroll(&mut self, velocity: Vector2<f32>, delta: f32) {
+        self.offset -= velocity * delta;
+    }
+
+    // scrolled position, wrapped into the arena
+    pub fn position(&self, star: &Star) -> Vector2<f32> {
+        let p = star.base + self.offset * star.parallax;
+        Vector2::new(p.x.rem_euclid(self.size.0), p.y.rem_euclid(self.size.1))
+    }
+
+    // brightness in [0, 1] at the given time
+    pub fn brightness(star: &Star, time: f32) -> f32 {
+        star.brightness * (0.75 + 0.25 * (time * star.rate + star.phase).sin())
+    }
+}
+
+// regenerate the stars for the current arena
+#[derive(SystemDesc)]
+pub struct StarfieldSystem;
+
+impl<'s> System<'s> for StarfieldSystem {
+    type SystemData = (
+        Write<'s, Starfield>,
+        ReadExpect<'s, StarfieldConfig>,
+        ReadExpect<'s, Arena>,
+    );
+
+    fn run(&mut self,
+           (mut starfield,
+            config,
+            arena): Self::SystemData) {
+        if !starfield.fits(&arena) {
+            let offset = starfield.offset;
+            *starfield = Starfield::generate(&config, arena.width, arena.height);
+            starfield.offset = offset;
+        }
+    }
+}
+
+// scroll the stars with the ship, runs only while playing
+#[derive(SystemDesc)]
+pub struct StarfieldScrollSystem;
+
+impl<'s> System<'s> for StarfieldScrollSystem {
+    type SystemData = (
+        Write<'s, Starfield>,
+        ReadStorage<'s, Ship>,
+        ReadStorage<'s, Physical>,
+        Read<'s, Time>,
+    );
+
+    fn run(&mut self,
+           (mut starfield,
+            ships,
+            physicals,
+            time): Self::SystemData) {
+        let delta = time.delta_seconds();
+        for (_, physical) in (&ships, &physicals).join() {
+            starfield.scroll(physical.velocity, delta);
+        }
+    }
+}
+
+// show the stars with a pool of sprite entities behind everything else
+#[derive(Default)]
+pub struct StarfieldRenderSystem {
+    sprites: Vec<Entity>,
+}
+
+impl<'s> System<'s> for StarfieldRenderSystem {
+    type SystemData = (
+        Entities<'s>,
+        Read<'s, Starfield>,
+        Option<Read<'s, SpriteRegistry>>,
+        Read<'s, Time>,
+        WriteStorage<'s, Transform>,
+        WriteStorage<'s, SpriteRender>,
+        WriteStorage<'s, Tint>,
+        WriteStorage<'s, Transparent>,
+    );
+
+    fn run(&mut self,
+           (entities,
+            starfield,
+            registry,
+            time,
+            mut transforms,
+            mut sprites,
+            mut tints,
+            mut transparents): Self::SystemData) {
+        let registry = match registry {
+            Some(registry) => registry,
+            None => return,
+        };
+
+        let stars = &starfield.stars;
+        while self.sprites.len() < stars.len() {
+            let e = entities.create();
+            let result = transforms.insert(e, Transform::default())
+                .and(sprites.insert(e, registry.sprite_render("particle")))
+                .and(tints.insert(e, Tint(Srgba::new(1.0, 1.0, 1.0, 1.0))))
+                .and(transparents.insert(e, Transparent));
+            if let Err(e) = result {
+                error!("Failed to create star: {}", e);
+            }
+            self.sprites.push(e);
+        }
+        for e in self.sprites.split_off(stars.len()) {
+            if let Err(e) = entities.delete(e) {
+                error!("Failed to destroy star: {}", e);
+            }
+        }
+
+        let now = time.absolute_time_seconds() as f32;
+        for (star, e) in stars.iter().zip(self.sprites.iter()) {
+            if let Some(transform) = transforms.get_mut(*e) {
+                let position = starfield.position(star);
+                // behind the particles
+                transform.set_translation_xyz(position.x, position.y, -0.5);
+                transform.set_scale(Vector3::new(star.size, star.size, 1.0));
+            }
+            if let Some(tint) = tints.get_mut(*e) {
+                tint.0 = Srgba::new(1.0, 1.0, 1.0, Starfield::brightness(star, now));
+            }
+        }
+    }
+}
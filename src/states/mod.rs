@@ -2,11 +2,18 @@ mod state_play;
 mod state_pause;
 mod state_menu;
 mod state_over;
+mod state_highscore;
+mod state_settings;
+mod state_loading;
+mod state_error;
+mod state_editor;
 
-pub const ARENA_HEIGHT: f32 = 300.0;
-pub const ARENA_WIDTH: f32 = 300.0;
-
-pub use state_play::StatePlay;
+pub use state_play::{StatePlay, clear_play_field, play_dispatcher};
 pub use state_pause::StatePause;
 pub use state_menu::StateMenu;
 pub use state_over::StateOver;
+pub use state_highscore::StateHighScore;
+pub use state_settings::StateSettings;
+pub use state_loading::StateLoading;
+pub use state_error::StateLoadError;
+pub use state_editor::StateEditor;
@@ -0,0 +1,412 @@
+use std::fs;
+
+use amethyst::{
+    core::math::Vector2,
+    ecs::prelude::{Entity},
+    input::{InputHandler, StringBindings, VirtualKeyCode, is_key_down},
+    prelude::*,
+    renderer::debug_drawing::DebugLines,
+    ui::{Anchor, UiText, UiTransform},
+    window::ScreenDimensions,
+    winit::MouseButton,
+};
+
+use crate::components::AsteroidSize;
+use crate::editor::{Editor, Item, screen_to_arena};
+use crate::hazards::{self, HazardKind, SOFTENING};
+use crate::level::{Level, LevelError, levels_dir};
+use crate::resources::{Arena, ArenaConfig, FontRes, Viewport};
+use crate::settings::Settings;
+use crate::states::StatePlay;
+use crate::system::ASTEROID_RADIUS;
+use crate::vector::{self, Outline};
+
+const COLOR_TEXT: [f32; 4] = [0., 0., 0., 1.];
+const COLOR_ITEM: [f32; 4] = [0.9, 0.95, 1.0, 1.0];
+const COLOR_SELECTED: [f32; 4] = [1.0, 0.85, 0.2, 1.0];
+const COLOR_VELOCITY: [f32; 4] = [0.4, 1.0, 0.5, 1.0];
+const COLOR_BORDER: [f32; 4] = [0.1, 0.1, 0.2, 1.0];
+
+// degrees per key press
+const ROTATE_STEP: f32 = 15.0;
+// arena units per key press
+const RESIZE_STEP: f32 = 20.0;
+
+const HELP: [&str; 4] = [
+    "Click: select  Drag: move  Drag the green handle: velocity",
+    "1 2 3: asteroid  O: obstacle  G: gravity well  B: black hole",
+    "Q E: rotate  Delete: remove  Arrows: arena size",
+    "Ctrl+Z: undo  Ctrl+Y: redo  Ctrl+S: save  P: play-test  Escape: back",
+];
+
+#[derive(Clone, Copy)]
+enum DragTarget {
+    // the offset keeps the grabbed point under the mouse
+    Move(Item, Vector2<f32>),
+    Velocity(usize),
+}
+
+struct Drag {
+    target: DragTarget,
+    start: Vector2<f32>,
+    // the undo step is taken when the mouse first moves
+    moved: bool,
+}
+
+// place the things of a level with the mouse and save it to levels/,
+// shown in the menu with `editor: true` in config/settings.ron
+#[derive(Default)]
+pub struct StateEditor {
+    editor: Editor,
+    // name of the file in levels/
+    file: String,
+    texts: Vec<Entity>,
+    status: Option<Entity>,
+    drag: Option<Drag>,
+    mouse_down: bool,
+    // the file exists but could not be loaded, it is only overwritten
+    // after a second Ctrl+S
+    load_error: Option<LevelError>,
+    confirm_overwrite: bool,
+}
+
+fn create_text(world: &mut World, i: usize, text: &str) -> Entity {
+    let font = world.read_resource::<FontRes>().font();
+    let transform = UiTransform::new(
+        format!("editor_{}", i), Anchor::TopLeft, Anchor::TopLeft,
+        20., -20. - 28. * i as f32, 5., 1400., 25.);
+    let mut text = UiText::new(font, text.to_string(), COLOR_TEXT, 22.);
+    text.align = Anchor::MiddleLeft;
+    world
+        .create_entity()
+        .with(transform)
+        .with(text)
+        .build()
+}
+
+fn item_name(item: Item) -> &'static str {
+    match item {
+        Item::Asteroid(_) => "asteroid",
+        Item::Obstacle(_) => "obstacle",
+        Item::Hazard(_) => "hazard",
+    }
+}
+
+impl StateEditor {
+    // edit the level chosen in the settings, or a new one
+    fn load(&mut self, world: &mut World) {
+        self.file = world.read_resource::<Settings>().level.clone()
+            .unwrap_or_else(|| "custom".to_string());
+        let exists = levels_dir()
+            .map(|dir| dir.join(format!("{}.ron", self.file)).exists())
+            .unwrap_or(false);
+        let new_level = Level {
+            name: self.file.clone(),
+            ..Default::default()
+        };
+        self.load_error = None;
+        self.confirm_overwrite = false;
+        let level = if exists {
+            match Level::load_named(&self.file) {
+                Ok(level) => level,
+                Err(e) => {
+                    log::error!("Failed to load level: {}", e);
+                    self.load_error = Some(e);
+                    new_level
+                }
+            }
+        } else {
+            new_level
+        };
+        self.editor = Editor::new(level);
+    }
+
+    fn show(&mut self, world: &mut World) {
+        self.texts = HELP.iter().enumerate().map(|(i, line)| create_text(world, i, line)).collect();
+        let status = match &self.load_error {
+            Some(e) => format!("Cannot load {}, editing an empty level", e),
+            None => format!("Editing {}.ron", self.file),
+        };
+        self.status = Some(create_text(world, HELP.len(), &status));
+    }
+
+    fn hide(&mut self, world: &mut World) {
+        let mut entities = self.texts.split_off(0);
+        entities.extend(self.status.take());
+        if let Err(e) = world.delete_entities(&entities) {
+            log::error!("Cannot delete editor text: {}", e);
+        }
+    }
+
+    fn set_status(&self, world: &mut World, status: String) {
+        if let Some(e) = self.status {
+            if let Some(text) = world.write_storage::<UiText>().get_mut(e) {
+                text.text = status;
+            }
+        }
+    }
+
+    // the level being edited sets the arena size
+    fn sync(&self, world: &mut World) {
+        world.insert(self.editor.level.clone());
+    }
+
+    // the mouse in arena units
+    fn mouse_position(world: &World) -> Option<Vector2<f32>> {
+        let mouse = world.read_resource::<InputHandler<StringBindings>>().mouse_position()?;
+        let dimensions = world.read_resource::<ScreenDimensions>();
+        let viewport = world.read_resource::<Viewport>();
+        if viewport.width <= 0.0 {
+            return None;
+        }
+        Some(screen_to_arena(mouse, (dimensions.width(), dimensions.height()),
+                             (viewport.width, viewport.height), &world.read_resource::<Arena>()))
+    }
+
+    // the mouse as a fraction of the arena size, the center without a mouse
+    fn mouse_fraction(world: &World) -> (f32, f32) {
+        let arena = *world.read_resource::<Arena>();
+        Self::mouse_position(world)
+            .map(|p| (p.x / arena.width, p.y / arena.height))
+            .unwrap_or((0.5, 0.5))
+    }
+
+    fn update_mouse(&mut self, world: &mut World) {
+        let down = world.read_resource::<InputHandler<StringBindings>>()
+            .mouse_button_is_down(MouseButton::Left);
+        let pressed = down && !self.mouse_down;
+        self.mouse_down = down;
+        if !down {
+            self.drag = None;
+            return;
+        }
+        let point = match Self::mouse_position(world) {
+            Some(point) => point,
+            None => return,
+        };
+        let arena = *world.read_resource::<Arena>();
+
+        if pressed {
+            let target = match self.editor.pick_velocity(point, &arena) {
+                Some(Item::Asteroid(i)) => Some((Item::Asteroid(i), DragTarget::Velocity(i))),
+                _ => self.editor.pick(point, &arena).map(|item| {
+                    let offset = self.editor.world_position(item, &arena) - point;
+                    (item, DragTarget::Move(item, offset))
+                }),
+            };
+            self.editor.selected = target.map(|(item, _)| item);
+            self.drag = target.map(|(_, target)| Drag { target, start: point, moved: false });
+            return;
+        }
+
+        if let Some(drag) = self.drag.as_mut() {
+            if !drag.moved {
+                if point == drag.start {
+                    return;
+                }
+                drag.moved = true;
+                self.editor.checkpoint();
+            }
+            match drag.target {
+                DragTarget::Move(item, offset) => {
+                    let p = point + offset;
+                    self.editor.set_position(item, (p.x / arena.width, p.y / arena.height));
+                },
+                DragTarget::Velocity(i) => self.editor.set_velocity_handle(i, point, &arena),
+            }
+        }
+    }
+
+    fn draw(&self, world: &mut World) {
+        let arena = *world.read_resource::<Arena>();
+        let mut lines = world.write_resource::<DebugLines>();
+        let color = |item: Item| if self.editor.selected == Some(item) { COLOR_SELECTED } else { COLOR_ITEM };
+
+        let corners = [
+            Vector2::new(0.0, 0.0),
+            Vector2::new(arena.width, 0.0),
+            Vector2::new(arena.width, arena.height),
+            Vector2::new(0.0, arena.height),
+        ];
+        vector::draw_polyline(&mut lines, &corners, true, COLOR_BORDER);
+
+        // the ship starts and respawns in the center
+        let center = Vector2::new(arena.width * 0.5, arena.height * 0.5);
+        let ship: Vec<Vector2<f32>> = Outline::ship().points.iter().map(|p| p + center).collect();
+        vector::draw_polyline(&mut lines, &ship, true, COLOR_BORDER);
+
+        let level = &self.editor.level;
+        for i in 0..level.obstacles.len() {
+            let outline = self.editor.obstacle_outline(i, &arena);
+            vector::draw_polyline(&mut lines, &outline, true, color(Item::Obstacle(i)));
+        }
+        for (i, hazard) in level.hazards.iter().enumerate() {
+            let position = self.editor.world_position(Item::Hazard(i), &arena);
+            hazards::draw_circle(&mut lines, position, SOFTENING, color(Item::Hazard(i)));
+            if hazard.kind == HazardKind::BlackHole {
+                hazards::draw_circle(&mut lines, position, hazard.horizon, color(Item::Hazard(i)));
+            }
+        }
+        for (i, asteroid) in level.asteroids.iter().enumerate() {
+            let position = self.editor.world_position(Item::Asteroid(i), &arena);
+            hazards::draw_circle(&mut lines, position, ASTEROID_RADIUS * asteroid.size.scale(),
+                                 color(Item::Asteroid(i)));
+            let handle = self.editor.velocity_handle(i, &arena);
+            vector::draw_polyline(&mut lines, &[position, handle], false, COLOR_VELOCITY);
+            hazards::draw_circle(&mut lines, handle, 2.0, COLOR_VELOCITY);
+        }
+    }
+
+    fn save(&mut self, world: &mut World) {
+        let status = match self.editor.level.validate() {
+            Err(e) => format!("Not saved: {}", e),
+            Ok(()) if self.load_error.is_some() && !self.confirm_overwrite => {
+                self.confirm_overwrite = true;
+                format!("Not saved: {}.ron did not load, Ctrl+S again replaces it", self.file)
+            },
+            Ok(()) => {
+                let result = levels_dir().and_then(|dir| {
+                    fs::create_dir_all(&dir)?;
+                    let path = dir.join(format!("{}.ron", self.file));
+                    self.editor.level.save(&path)?;
+                    Ok(path)
+                });
+                match result {
+                    Ok(path) => {
+                        self.load_error = None;
+                        format!("Saved {}", path.display())
+                    },
+                    Err(e) => {
+                        log::error!("Failed to save level: {}", e);
+                        "Save failed".to_string()
+                    }
+                }
+            }
+        };
+        self.set_status(world, status);
+    }
+
+    fn add(&mut self, world: &mut World, add: impl FnOnce(&mut Editor, (f32, f32)) -> Item) {
+        let position = Self::mouse_fraction(world);
+        self.editor.checkpoint();
+        let item = add(&mut self.editor, position);
+        self.editor.selected = Some(item);
+        self.set_status(world, format!("Added {}", item_name(item)));
+    }
+}
+
+impl SimpleState for StateEditor {
+    fn on_start(&mut self, data: StateData<'_, GameData<'_, '_>>) {
+        self.load(data.world);
+        self.show(data.world);
+        self.sync(data.world);
+    }
+
+    fn on_stop(&mut self, data: StateData<'_, GameData<'_, '_>>) {
+        self.hide(data.world);
+        let endless = Level::endless(&data.world.read_resource::<ArenaConfig>());
+        data.world.insert(endless);
+    }
+
+    fn on_pause(&mut self, data: StateData<'_, GameData<'_, '_>>) {
+        self.hide(data.world);
+        self.drag = None;
+    }
+
+    // back from a play-test, which reset the level
+    fn on_resume(&mut self, data: StateData<'_, GameData<'_, '_>>) {
+        self.show(data.world);
+        self.sync(data.world);
+    }
+
+    fn handle_event(&mut self,
+                    data: StateData<'_, GameData<'_, '_>>,
+                    event: StateEvent) -> SimpleTrans {
+        let world = data.world;
+        if let StateEvent::Window(event) = event {
+            let ctrl = {
+                let input = world.read_resource::<InputHandler<StringBindings>>();
+                input.key_is_down(VirtualKeyCode::LControl) || input.key_is_down(VirtualKeyCode::RControl)
+            };
+            if ctrl {
+                if is_key_down(&event, VirtualKeyCode::Z) {
+                    // the dragged item may be gone
+                    self.drag = None;
+                    let status = if self.editor.undo() { "Undone" } else { "Nothing to undo" };
+                    self.set_status(world, status.to_string());
+                    self.sync(world);
+                } else if is_key_down(&event, VirtualKeyCode::Y) {
+                    self.drag = None;
+                    let status = if self.editor.redo() { "Redone" } else { "Nothing to redo" };
+                    self.set_status(world, status.to_string());
+                    self.sync(world);
+                } else if is_key_down(&event, VirtualKeyCode::S) {
+                    self.save(world);
+                }
+                return Trans::None;
+            }
+
+            if is_key_down(&event, VirtualKeyCode::Escape) {
+                return Trans::Pop;
+            } else if is_key_down(&event, VirtualKeyCode::P) {
+                if let Err(e) = self.editor.level.validate() {
+                    self.set_status(world, format!("Cannot play: {}", e));
+                    return Trans::None;
+                }
+                return Trans::Push(Box::new(StatePlay::with_level(self.editor.level.clone())));
+            } else if is_key_down(&event, VirtualKeyCode::Key1) {
+                self.add(world, |editor, p| editor.add_asteroid(AsteroidSize::Small, p));
+            } else if is_key_down(&event, VirtualKeyCode::Key2) {
+                self.add(world, |editor, p| editor.add_asteroid(AsteroidSize::Medium, p));
+            } else if is_key_down(&event, VirtualKeyCode::Key3) {
+                self.add(world, |editor, p| editor.add_asteroid(AsteroidSize::Large, p));
+            } else if is_key_down(&event, VirtualKeyCode::O) {
+                self.add(world, |editor, p| editor.add_obstacle(p));
+            } else if is_key_down(&event, VirtualKeyCode::G) {
+                self.add(world, |editor, p| editor.add_hazard(HazardKind::GravityWell, p));
+            } else if is_key_down(&event, VirtualKeyCode::B) {
+                self.add(world, |editor, p| editor.add_hazard(HazardKind::BlackHole, p));
+            } else if is_key_down(&event, VirtualKeyCode::Q) || is_key_down(&event, VirtualKeyCode::E) {
+                if let Some(item) = self.editor.selected {
+                    let step = if is_key_down(&event, VirtualKeyCode::Q) { ROTATE_STEP } else { -ROTATE_STEP };
+                    self.editor.checkpoint();
+                    self.editor.rotate(item, step);
+                }
+            } else if is_key_down(&event, VirtualKeyCode::Delete) || is_key_down(&event, VirtualKeyCode::Back) {
+                if let Some(item) = self.editor.selected {
+                    self.drag = None;
+                    self.editor.checkpoint();
+                    self.editor.delete(item);
+                    self.set_status(world, format!("Removed {}", item_name(item)));
+                }
+            } else {
+                let resize = if is_key_down(&event, VirtualKeyCode::Left) {
+                    Some((-RESIZE_STEP, 0.0))
+                } else if is_key_down(&event, VirtualKeyCode::Right) {
+                    Some((RESIZE_STEP, 0.0))
+                } else if is_key_down(&event, VirtualKeyCode::Down) {
+                    Some((0.0, -RESIZE_STEP))
+                } else if is_key_down(&event, VirtualKeyCode::Up) {
+                    Some((0.0, RESIZE_STEP))
+                } else {
+                    None
+                };
+                if let Some((width, height)) = resize {
+                    self.editor.checkpoint();
+                    self.editor.resize(width, height);
+                    let status = format!("Arena {}x{}", self.editor.level.width, self.editor.level.height);
+                    self.set_status(world, status);
+                    self.sync(world);
+                }
+            }
+        }
+
+        Trans::None
+    }
+
+    fn update(&mut self, data: &mut StateData<'_, GameData<'_, '_>>) -> SimpleTrans {
+        self.update_mouse(data.world);
+        self.draw(data.world);
+        Trans::None
+    }
+}
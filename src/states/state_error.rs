@@ -0,0 +1,61 @@
+use amethyst::{
+    assets::{AssetStorage, Loader},
+    ecs::prelude::{Entity},
+    input::{VirtualKeyCode, is_key_down},
+    prelude::*,
+    ui::{Anchor, FontAsset, UiText, UiTransform, get_default_font},
+};
+
+// lists the assets that failed to load, the game cannot start
+pub struct StateLoadError {
+    errors: Vec<String>,
+    texts: Vec<Entity>,
+}
+
+impl StateLoadError {
+    pub fn new(errors: Vec<String>) -> Self {
+        Self {
+            errors,
+            texts: vec![],
+        }
+    }
+}
+
+impl SimpleState for StateLoadError {
+    fn on_start(&mut self, data: StateData<'_, GameData<'_, '_>>) {
+        let world = data.world;
+        // the game font may be what failed, use the built-in one
+        let font = get_default_font(
+            &world.read_resource::<Loader>(),
+            &world.read_resource::<AssetStorage<FontAsset>>());
+
+        let mut lines = vec!["Failed to load the game assets:".to_string()];
+        lines.extend(self.errors.iter().cloned());
+        lines.push("Press Escape to quit".to_string());
+
+        self.texts = lines.iter().enumerate().map(|(i, line)| {
+            let transform = UiTransform::new(
+                format!("load_error_{}", i), Anchor::TopLeft, Anchor::TopLeft,
+                20., -20. - 30. * i as f32, 1., 2000., 30.);
+            let mut text = UiText::new(font.clone(), line.clone(), [1., 1., 1., 1.], 20.);
+            text.align = Anchor::MiddleLeft;
+            world
+                .create_entity()
+                .with(transform)
+                .with(text)
+                .build()
+        }).collect();
+    }
+
+    fn handle_event(&mut self,
+                    _data: StateData<'_, GameData<'_, '_>>,
+                    event: StateEvent) -> SimpleTrans {
+        if let StateEvent::Window(event) = event {
+            if is_key_down(&event, VirtualKeyCode::Escape) {
+                return Trans::Quit;
+            }
+        }
+
+        Trans::None
+    }
+}
@@ -0,0 +1,64 @@
+use amethyst::{
+    ecs::prelude::{Entity},
+    input::{get_key},
+    prelude::*,
+    ui::{Anchor, UiText, UiTransform},
+    winit::ElementState,
+};
+
+use crate::resources::{FontRes, HighScores};
+
+#[derive(Default)]
+pub struct StateHighScore {
+    texts: Vec<Entity>,
+}
+
+fn initialize_text(world: &mut World) -> Vec<Entity> {
+    let font = world.read_resource::<FontRes>().font();
+    let mut lines = vec!["High Scores".to_string()];
+    {
+        let highscores = world.read_resource::<HighScores>();
+        if highscores.scores.is_empty() {
+            lines.push("No games played yet".to_string());
+        }
+        for (rank, score) in highscores.scores.iter().enumerate() {
+            lines.push(format!("{:>2}. {:>8}", rank + 1, score));
+        }
+    }
+
+    lines.iter().enumerate().map(|(i, line)| {
+        let transform = UiTransform::new(
+            format!("highscore_{}", i), Anchor::TopMiddle, Anchor::TopMiddle,
+            0., -100. - 60. * i as f32, 1., 1000., 50.);
+        world
+            .create_entity()
+            .with(transform)
+            .with(UiText::new(font.clone(), line.clone(), [0., 0., 0., 1.], 40.))
+            .build()
+    }).collect()
+}
+
+impl SimpleState for StateHighScore {
+    fn on_start(&mut self, data: StateData<'_, GameData<'_, '_>>) {
+        self.texts = initialize_text(data.world);
+    }
+
+    fn on_stop(&mut self, data: StateData<'_, GameData<'_, '_>>) {
+        if let Err(e) = data.world.delete_entities(&self.texts) {
+            log::error!("Cannot delete high score text: {}", e);
+        }
+        self.texts.clear();
+    }
+
+    fn handle_event(&mut self,
+                    _data: StateData<'_, GameData<'_, '_>>,
+                    event: StateEvent) -> SimpleTrans {
+        if let StateEvent::Window(event) = event {
+            if let Some((_, ElementState::Pressed)) = get_key(&event) {
+                return Trans::Pop;
+            }
+        }
+
+        Trans::None
+    }
+}
@@ -0,0 +1,163 @@
+use amethyst::{
+    assets::{Completion, ProgressCounter},
+    core::transform::{Transform},
+    ecs::prelude::{Entity},
+    prelude::*,
+    renderer::{Camera},
+    ui::{Anchor, UiImage, UiTransform},
+    window::Window,
+};
+
+use crate::animation::{Animation, Animations};
+use crate::audio::Sounds;
+use crate::components::{
+    Ship,
+    Physical,
+    Asteroid,
+    Bullet,
+    Collider,
+    ShipCommand,
+    AutoPilot,
+};
+use crate::hazards::Hazard;
+use crate::level::Obstacle;
+use crate::particles::Emitter;
+use crate::pool::Pooled;
+use crate::resources::{
+    RandomGen,
+    FontRes,
+    HighScores,
+    Arena,
+};
+use crate::settings::Settings;
+use crate::vector::Outline;
+use crate::weapons::Weapons;
+use crate::states::{
+    StateMenu,
+    StateLoadError,
+};
+use crate::textures::SpriteRegistry;
+
+const BAR_WIDTH: f32 = 600.;
+const BAR_HEIGHT: f32 = 30.;
+
+// the projection is adjusted to the window by ViewportSystem
+fn initialize_camera(world: &mut World) {
+    let arena = *world.read_resource::<Arena>();
+    let mut transform = Transform::default();
+    transform.set_translation_xyz(arena.width * 0.5, arena.height * 0.5, 1.0);
+
+    world
+        .create_entity()
+        .with(transform)
+        .with(Camera::standard_2d(arena.width, arena.height))
+        .build();
+}
+
+fn initialize_bar(world: &mut World, id: &str, width: f32, color: [f32; 4]) -> Entity {
+    let transform = UiTransform::new(
+        id.to_string(), Anchor::Middle, Anchor::MiddleLeft,
+        -BAR_WIDTH * 0.5, 0., 1., width, BAR_HEIGHT);
+    world
+        .create_entity()
+        .with(transform)
+        .with(UiImage::SolidColor(color))
+        .build()
+}
+
+// load every asset of the game, show the progress and report failures
+#[derive(Default)]
+pub struct StateLoading {
+    progress: ProgressCounter,
+    // failures found while loading, outside of the asset storages
+    errors: Vec<String>,
+    bars: Vec<Entity>,
+}
+
+impl StateLoading {
+    fn update_bar(&self, world: &mut World) {
+        let total = self.progress.num_assets().max(1) as f32;
+        let done = self.progress.num_finished() as f32;
+        if let Some(fill) = self.bars.get(1) {
+            if let Some(transform) = world.write_storage::<UiTransform>().get_mut(*fill) {
+                transform.width = BAR_WIDTH * done / total;
+            }
+        }
+    }
+}
+
+impl SimpleState for StateLoading {
+    fn on_start(&mut self, data: StateData<'_, GameData<'_, '_>>) {
+        let world = data.world;
+
+        if world.read_resource::<Settings>().fullscreen {
+            world.read_resource::<Settings>().apply_window(&world.read_resource::<Window>());
+        }
+
+        if let Err(e) = SpriteRegistry::initialize(world, &mut self.progress) {
+            self.errors.push(e);
+        }
+        Sounds::initialize(world, &mut self.progress);
+        FontRes::initialize(world, &mut self.progress);
+        world.insert(RandomGen);
+        world.insert(HighScores::default());
+
+        world.register::<Physical>();
+        world.register::<Ship>();
+        world.register::<Bullet>();
+        world.register::<Asteroid>();
+        world.register::<Collider>();
+        world.register::<ShipCommand>();
+        world.register::<AutoPilot>();
+        world.register::<Emitter>();
+        world.register::<Animation>();
+        world.register::<Outline>();
+        world.register::<Hazard>();
+        world.register::<Obstacle>();
+        world.register::<Pooled>();
+        world.register::<Weapons>();
+
+        let mut animations = Animations::default();
+        for sheet in &["explosion", "flame"] {
+            if let Err(e) = animations.load(sheet, &world.read_resource::<SpriteRegistry>()) {
+                self.errors.push(e);
+            }
+        }
+        world.insert(animations);
+
+        initialize_camera(world);
+        self.bars = vec![
+            initialize_bar(world, "loading_background", BAR_WIDTH, [0., 0., 0., 1.]),
+            initialize_bar(world, "loading_fill", 0., [1., 1., 1., 1.]),
+        ];
+    }
+
+    fn on_stop(&mut self, data: StateData<'_, GameData<'_, '_>>) {
+        if let Err(e) = data.world.delete_entities(&self.bars) {
+            log::error!("Cannot delete loading bar: {}", e);
+        }
+        self.bars.clear();
+    }
+
+    fn update(&mut self, data: &mut StateData<'_, GameData<'_, '_>>) -> SimpleTrans {
+        self.update_bar(data.world);
+
+        match self.progress.complete() {
+            Completion::Loading => Trans::None,
+            Completion::Complete if self.errors.is_empty() => {
+                log::info!("Loaded {} assets", self.progress.num_assets());
+                Trans::Switch(Box::new(StateMenu::default()))
+            },
+            _ => {
+                let mut errors: Vec<String> = self.progress.errors().iter()
+                    .map(|meta| format!("{}: {}", meta.asset_name, meta.error))
+                    .collect();
+                errors.extend(self.errors.drain(..));
+                for e in &errors {
+                    log::error!("Failed to load {}", e);
+                }
+                Trans::Switch(Box::new(StateLoadError::new(errors)))
+            },
+        }
+    }
+}
@@ -1,106 +1,222 @@
 use amethyst::{
-    core::transform::{Transform},
+    core::timing::Time,
     ecs::prelude::{Entity},
-    input::{VirtualKeyCode, is_key_down},
+    input::{VirtualKeyCode, is_key_down, get_key},
     prelude::*,
-    renderer::{Camera},
+    shrev::EventChannel,
     ui::{Anchor, UiText, UiTransform},
+    winit::ElementState,
 };
 
-use crate::components::{
-    Ship,
-    Physical,
-    Asteroid,
-    Bullet,
-    Collider,
-};
+use crate::level::{Level};
 use crate::resources::{
-    ShipRes,
-    BulletRes,
-    AsteroidRes,
-    RandomGen,
-    ExplosionRes,
     FontRes,
 };
+use crate::settings::Settings;
+use crate::system::GameEvent;
 use crate::states::{
-    ARENA_WIDTH, ARENA_HEIGHT,
     StatePlay,
+    StateHighScore,
+    StateSettings,
+    StateEditor,
 };
 
-fn initialize_camera(world: &mut World) {
-    let mut transform = Transform::default();
-    transform.set_translation_xyz(ARENA_WIDTH * 0.5, ARENA_HEIGHT * 0.5, 1.0);
+// seconds without input before the demo game starts
+const ATTRACT_DELAY: f32 = 10.0;
 
-    world
-        .create_entity()
-        .with(transform)
-        .with(Camera::standard_2d(ARENA_WIDTH, ARENA_HEIGHT))
-        .build();
+const COLOR_NORMAL: [f32; 4] = [0., 0., 0., 1.];
+const COLOR_SELECTED: [f32; 4] = [1., 1., 1., 1.];
+
+#[derive(Clone, Copy, PartialEq)]
+enum MenuItem {
+    Start,
+    Editor,
+    HighScores,
+    Settings,
+    Quit,
+}
+
+impl MenuItem {
+    // the editor is only offered when enabled in the settings
+    fn entries(editor: bool) -> Vec<MenuItem> {
+        let mut entries = vec![MenuItem::Start];
+        if editor {
+            entries.push(MenuItem::Editor);
+        }
+        entries.extend(&[MenuItem::HighScores, MenuItem::Settings, MenuItem::Quit]);
+        entries
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            MenuItem::Start => "Start",
+            MenuItem::Editor => "Level Editor",
+            MenuItem::HighScores => "High Scores",
+            MenuItem::Settings => "Settings",
+            MenuItem::Quit => "Quit",
+        }
+    }
 }
 
-fn initialize_text(world: &mut World) -> Entity {
+fn initialize_logo(world: &mut World) -> Entity {
     let font = world.read_resource::<FontRes>().font();
-    let score_transform = UiTransform::new(
-        "score".to_string(), Anchor::Middle, Anchor::Middle,
-        0., 0., 1., 1000., 50.);
-    let text = world
+    let logo_transform = UiTransform::new(
+        "logo".to_string(), Anchor::Middle, Anchor::Middle,
+        0., 250., 1., 1000., 120.);
+    world
         .create_entity()
-        .with(score_transform)
+        .with(logo_transform)
         .with(UiText::new(font,
-                          "Press Space to Start".to_string(),
-                          [0.,0.,0.,1.], 50.))
-        .build();
-    text
+                          "ROCKET".to_string(),
+                          COLOR_NORMAL, 120.))
+        .build()
+}
+
+fn initialize_items(world: &mut World, entries: &[MenuItem]) -> Vec<Entity> {
+    let font = world.read_resource::<FontRes>().font();
+    entries.iter().enumerate().map(|(i, item)| {
+        let transform = UiTransform::new(
+            format!("menu_{}", i), Anchor::Middle, Anchor::Middle,
+            0., 20. - 70. * i as f32, 1., 1000., 50.);
+        world
+            .create_entity()
+            .with(transform)
+            .with(UiText::new(font.clone(),
+                              item.label().to_string(),
+                              COLOR_NORMAL, 50.))
+            .build()
+    }).collect()
 }
 
 #[derive(Default)]
 pub struct StateMenu {
-    text: Option<Entity>,
+    logo: Option<Entity>,
+    entries: Vec<MenuItem>,
+    items: Vec<Entity>,
+    selected: usize,
+    // time since the last key press
+    idle_time: f32,
+    // the attract mode is running on top of this state
+    in_demo: bool,
+    // why the chosen level could not be started
+    error: Option<Entity>,
+}
+
+impl StateMenu {
+    fn show(&mut self, world: &mut World) {
+        self.logo = Some(initialize_logo(world));
+        self.entries = MenuItem::entries(world.read_resource::<Settings>().editor);
+        self.selected = self.selected.min(self.entries.len() - 1);
+        self.items = initialize_items(world, &self.entries);
+        self.refresh(world);
+    }
+
+    fn hide(&mut self, world: &mut World) {
+        let mut entities = self.items.split_off(0);
+        entities.extend(self.logo.take());
+        entities.extend(self.error.take());
+        if let Err(e) = world.delete_entities(&entities) {
+            log::error!("Cannot delete menu ui text: {}", e);
+        }
+    }
+
+    // highlight the selected item
+    fn refresh(&self, world: &mut World) {
+        let mut texts = world.write_storage::<UiText>();
+        for (i, e) in self.items.iter().enumerate() {
+            if let Some(text) = texts.get_mut(*e) {
+                text.color = if i == self.selected { COLOR_SELECTED } else { COLOR_NORMAL };
+            }
+        }
+    }
+
+    fn select(&mut self, world: &mut World, step: isize) {
+        let count = self.entries.len() as isize;
+        self.selected = ((self.selected as isize + step + count) % count) as usize;
+        self.refresh(world);
+        world.write_resource::<EventChannel<GameEvent>>().single_write(GameEvent::MenuMoved);
+    }
+
+    fn show_error(&mut self, world: &mut World, message: String) {
+        if let Some(e) = self.error.take() {
+            if let Err(e) = world.delete_entity(e) {
+                log::error!("Cannot delete menu ui text: {}", e);
+            }
+        }
+        let font = world.read_resource::<FontRes>().font();
+        let transform = UiTransform::new(
+            "menu_error".to_string(), Anchor::BottomMiddle, Anchor::BottomMiddle,
+            0., 40., 1., 1400., 25.);
+        self.error = Some(world
+            .create_entity()
+            .with(transform)
+            .with(UiText::new(font, message, COLOR_NORMAL, 25.))
+            .build());
+    }
+
+    // the game on the level chosen in the settings
+    fn start(&mut self, world: &mut World) -> SimpleTrans {
+        let name = world.read_resource::<Settings>().level.clone();
+        match name {
+            None => Trans::Push(Box::new(StatePlay::default())),
+            Some(name) => match Level::load_named(&name) {
+                Ok(level) => Trans::Push(Box::new(StatePlay::with_level(level))),
+                Err(e) => {
+                    log::error!("Failed to load level: {}", e);
+                    self.show_error(world, e.to_string());
+                    Trans::None
+                }
+            },
+        }
+    }
+
+    fn activate(&mut self, world: &mut World) -> SimpleTrans {
+        world.write_resource::<EventChannel<GameEvent>>().single_write(GameEvent::MenuSelected);
+        match self.entries[self.selected] {
+            MenuItem::Start => self.start(world),
+            MenuItem::Editor => Trans::Push(Box::new(StateEditor::default())),
+            MenuItem::HighScores => Trans::Push(Box::new(StateHighScore::default())),
+            MenuItem::Settings => Trans::Push(Box::new(StateSettings::default())),
+            MenuItem::Quit => Trans::Quit,
+        }
+    }
 }
 
 impl SimpleState for StateMenu {
     fn on_start(&mut self, data: StateData<'_, GameData<'_, '_>>) {
-        let world = data.world;
-
-        ShipRes::initialize(world);
-        BulletRes::initialize(world);
-        AsteroidRes::initialize(world);
-        ExplosionRes::initialize(world);
-        world.insert(RandomGen);
-        FontRes::initialize(world);
-
-        world.register::<Physical>();
-        world.register::<Ship>();
-        world.register::<Bullet>();
-        world.register::<Asteroid>();
-        world.register::<Collider>();
-
-        initialize_camera(world);
-        let text = initialize_text(world);
-        self.text = Some(text);
+        self.show(data.world);
     }
 
     fn on_pause(&mut self, data: StateData<'_, GameData<'_, '_>>) {
-        let world = data.world;
-        if let Some(text) = self.text {
-            if let Err(e) = world.delete_entity(text) {
-                log::error!("Cannot delete menu ui text: {}", e);
-            }
+        // the menu stays visible on top of the demo game
+        if !self.in_demo {
+            self.hide(data.world);
         }
     }
 
     fn on_resume(&mut self, data: StateData<'_, GameData<'_, '_>>) {
-        let world = data.world;
-        let text = initialize_text(world);
-        self.text = Some(text);
+        if !self.in_demo {
+            self.show(data.world);
+        }
+        self.in_demo = false;
+        self.idle_time = 0.0;
     }
 
     fn handle_event(&mut self,
-                    _data: StateData<'_, GameData<'_, '_>>,
+                    data: StateData<'_, GameData<'_, '_>>,
                     event: StateEvent) -> SimpleTrans {
         if let StateEvent::Window(event) = event {
-            if is_key_down(&event, VirtualKeyCode::Space) {
-                return Trans::Push(Box::new(StatePlay::default()));
+            if let Some((_, ElementState::Pressed)) = get_key(&event) {
+                self.idle_time = 0.0;
+            }
+
+            if is_key_down(&event, VirtualKeyCode::Up) {
+                self.select(data.world, -1);
+            } else if is_key_down(&event, VirtualKeyCode::Down) {
+                self.select(data.world, 1);
+            } else if is_key_down(&event, VirtualKeyCode::Space) ||
+                      is_key_down(&event, VirtualKeyCode::Return) {
+                return self.activate(data.world);
             } else if is_key_down(&event, VirtualKeyCode::Escape) {
                 return Trans::Quit;
             }
@@ -108,4 +224,14 @@ impl SimpleState for StateMenu {
 
         Trans::None
     }
+
+    fn update(&mut self, data: &mut StateData<'_, GameData<'_, '_>>) -> SimpleTrans {
+        self.idle_time += data.world.read_resource::<Time>().delta_seconds();
+        if self.idle_time > ATTRACT_DELAY {
+            self.in_demo = true;
+            return Trans::Push(Box::new(StatePlay::demo()));
+        }
+
+        Trans::None
+    }
 }
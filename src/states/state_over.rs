@@ -3,62 +3,126 @@ use amethyst::{
     input::{VirtualKeyCode, is_key_down},
     prelude::*,
     ui::{Anchor, UiText, UiTransform},
-    renderer::{Camera},
 };
-use crate::resources::{FontRes, ScoreRes};
+use crate::resources::{FontRes, HighScores};
+use crate::scoring::Scoring;
+use crate::stats::{SessionStats, PersonalBests, personal_bests_path, stat_lines};
+use crate::states::clear_play_field;
+
+const COLOR_TEXT: [f32; 4] = [0., 0., 0., 1.];
 
 #[derive(Default)]
 pub struct StateOver {
-    text: Option<Entity>,
+    texts: Vec<Entity>,
+    // the last line, the export hint or the export result
+    status: Option<Entity>,
+    stats: SessionStats,
+    // the win condition of the level was met
+    complete: bool,
 }
 
 impl StateOver {
     pub fn new() -> Self {
         Default::default()
     }
+
+    pub fn level_complete() -> Self {
+        Self {
+            complete: true,
+            ..Default::default()
+        }
+    }
+
+    fn set_status(&self, world: &mut World, status: String) {
+        if let Some(e) = self.status {
+            if let Some(text) = world.write_storage::<UiText>().get_mut(e) {
+                text.text = status;
+            }
+        }
+    }
 }
 
-fn initialize_text(world: &mut World) -> Entity {
+fn create_line(world: &mut World, i: usize, line: String, size: f32) -> Entity {
     let font = world.read_resource::<FontRes>().font();
-    let score = world.read_resource::<ScoreRes>().score;
-    let score_transform = UiTransform::new(
-        "score".to_string(), Anchor::Middle, Anchor::Middle,
-        0., 0., 1., 1000., 50.);
-    let text = world
+    let transform = UiTransform::new(
+        format!("over_{}", i), Anchor::Middle, Anchor::Middle,
+        0., 220. - 50. * i as f32, 1., 1200., size);
+    world
         .create_entity()
-        .with(score_transform)
-        .with(UiText::new(font,
-                          format!("Game Over. Your Score {}", score),
-                          [0., 0., 0., 1.], 50.))
-        .build();
-    text
+        .with(transform)
+        .with(UiText::new(font, line, COLOR_TEXT, size))
+        .build()
+}
+
+// compare the game to the personal bests and store the new ones
+fn update_bests(stats: &SessionStats) -> PersonalBests {
+    let path = match personal_bests_path() {
+        Ok(path) => path,
+        Err(e) => {
+            log::error!("Failed to find personal bests: {}", e);
+            return PersonalBests::default();
+        }
+    };
+    let old = PersonalBests::load(&path);
+    let mut bests = old.clone();
+    bests.update(stats);
+    if let Err(e) = bests.save(&path) {
+        log::error!("Failed to save personal bests: {}", e);
+    }
+    old
 }
 
 impl SimpleState for StateOver {
     fn on_start(&mut self, data: StateData<'_, GameData<'_, '_>>) {
         let world = data.world;
 
-        let text = initialize_text(world);
-        self.text = Some(text)
+        let scoring = world.read_resource::<Scoring>().clone();
+        world.write_resource::<HighScores>().record(scoring.score);
+
+        let mut stats = world.read_resource::<SessionStats>().clone();
+        stats.score = scoring.score;
+        let bests = update_bests(&stats);
+
+        let title = if self.complete { "Level Complete" } else { "Game Over" };
+        let mut lines = vec![title.to_string()];
+        lines.extend(stat_lines(&stats, &bests));
+        lines.push(format!("Best Multiplier x{}  Extra Lives {}",
+                           scoring.best_multiplier, scoring.lives_awarded));
+        self.texts = lines.into_iter().enumerate().map(|(i, line)| {
+            let size = if i == 0 { 50. } else { 30. };
+            create_line(world, i, line, size)
+        }).collect();
+        let status = create_line(world, self.texts.len() + 1,
+                                 "Space to continue, E to export the statistics".to_string(), 25.);
+        self.status = Some(status);
+        self.stats = stats;
     }
 
     fn on_stop(&mut self, data: StateData<'_, GameData<'_, '_>>) {
         let world = data.world;
-        world.exec(|(entities, cameras) : (Entities, ReadStorage<Camera>)| {
-            for (e,_) in (&entities, !&cameras).join() {
-                if let Err(e) = entities.delete(e) {
-                    log::error!("Failed to destroy entity: {}", e);
-                }
-            }
-        });
+        clear_play_field(world);
+        self.texts.extend(self.status.take());
+        if let Err(e) = world.delete_entities(&self.texts) {
+            log::error!("Failed to destroy entity: {}", e);
+        }
+        self.texts.clear();
     }
 
     fn handle_event(&mut self,
-                    _data: StateData<'_, GameData<'_, '_>>,
+                    data: StateData<'_, GameData<'_, '_>>,
                     event: StateEvent) -> SimpleTrans {
         if let StateEvent::Window(event) = event {
             if is_key_down(&event, VirtualKeyCode::Space) {
                 return Trans::Pop;
+            } else if is_key_down(&event, VirtualKeyCode::E) {
+                let status = match self.stats.export() {
+                    Ok(path) => format!("Exported to {}", path.display()),
+                    Err(e) => {
+                        log::error!("Failed to export statistics: {}", e);
+                        "Export failed".to_string()
+                    }
+                };
+                self.set_status(data.world, status);
             }
         }
 
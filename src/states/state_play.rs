@@ -2,61 +2,209 @@ use amethyst::{
     core::transform::{Transform},
     core::math::{zero},
     core::ArcThreadPool,
-    input::{VirtualKeyCode, is_key_down},
+    core::{Hidden, Parent},
+    ecs::prelude::*,
+    input::{VirtualKeyCode, is_key_down, get_key},
     prelude::*,
     shred::{Dispatcher, DispatcherBuilder},
+    shrev::EventChannel,
+    winit::ElementState,
 };
 
 use crate::components::{
     Ship,
     Physical,
     Collider,
-    ColliderType
+    ColliderType,
+    ColliderShape,
+    ShipCommand,
+    AutoPilot,
 };
 use crate::resources::{
-    ShipRes,
-    ScoreRes,
+    GameMode,
+    Arena,
+    ArenaConfig,
 };
+use crate::level::{self, Level, LevelSystem, Obstacle};
+use crate::animation::{Animation, Animations, AnimationSystem};
+use crate::hazards::{Hazard, HazardSystem};
+use crate::hud::{Hud, HudEvent};
+use crate::physics::ShipPhysicsConfig;
+use crate::particles::{Emitter, ParticlePool, ParticleSystem};
+use crate::pool::{EntityPools, PoolSystem, Pooled};
+use crate::scoring::{Scoring, ScoringConfig, ScoringSystem};
+use crate::settings::{Difficulty, Settings};
+use crate::spatial::SpatialGridSystem;
+use crate::stats::{SessionStats, StatsSystem};
+use crate::starfield::StarfieldScrollSystem;
+use crate::textures::SpriteRegistry;
+use crate::weapons::{Beams, Cannon, Laser, LaserConfig, Weapons};
+use crate::vector::Outline;
 use crate::system::{
+    PlayerInputSystem,
+    AutoPilotSystem,
     ShipControlSystem,
     PhysicalSystem,
     BoundarySystem,
     SpawnAsteroidSystem,
     CollisionSystem,
     DeletionSystem,
-    ExplosionSystem,
 };
 use crate::states::{
-    ARENA_WIDTH, ARENA_HEIGHT,
     StatePause
 };
 
-fn initialize_ship(world: &mut World) {
+fn initialize_ship(world: &mut World, demo: bool) {
+    let arena = *world.read_resource::<Arena>();
     let mut transform = Transform::default();
-    transform.set_translation_xyz(ARENA_WIDTH * 0.5, ARENA_HEIGHT * 0.5, 0.0);
+    transform.set_translation_xyz(arena.width * 0.5, arena.height * 0.5, 0.0);
 
-    let sprite_render = {
-        let resource = world.read_resource::<ShipRes>();
-        resource.sprite_render()
-    };
+    let sprite_render = world.read_resource::<SpriteRegistry>().sprite_render("ship");
+    let max_speed = world.read_resource::<ShipPhysicsConfig>().max_speed;
+    let laser = Laser::new(&world.read_resource::<LaserConfig>());
 
-    world
+    let mut builder = world
         .create_entity()
         .with(transform)
         .with(sprite_render.clone())
-        .with(Ship::new())
+        .with(ShipCommand::default())
+        .with(Emitter::exhaust())
         .with(Physical {
             velocity: zero(),
-            max_velocity: 100.0,
+            max_velocity: max_speed,
             rotation: 0.0
         })
-        .with(Collider { typ: ColliderType::Ship })
-        .build();
+        .with(Collider { typ: ColliderType::Ship, shape: ColliderShape::Circle(5.0) })
+        .with(Outline::ship())
+        .with(Weapons::new(vec![Box::new(Cannon::new()), Box::new(laser)]));
+    if demo {
+        builder = builder.with(AutoPilot::new());
+    }
+    let ship = builder.build();
+
+    // the thrust flame follows the ship, hidden until it accelerates
+    let mut ship_component = Ship::new();
+    let animation = world.read_resource::<Animations>().get("flame.thrust");
+    if let Some(animation) = animation {
+        let mut flame_transform = Transform::default();
+        flame_transform.set_translation_xyz(0.0, -10.0, 0.0);
+        let flame_render = world.read_resource::<SpriteRegistry>().sprite_render("flame.0");
+        let flame = world
+            .create_entity()
+            .with(flame_transform)
+            .with(flame_render)
+            .with(animation)
+            .with(Parent { entity: ship })
+            .with(Hidden)
+            .build();
+        ship_component.flame = Some(flame);
+    }
+    if let Err(e) = world.write_storage::<Ship>().insert(ship, ship_component) {
+        log::error!("Failed to create ship: {}", e);
+    }
+}
+
+// remove everything the game put into the world
+pub fn clear_play_field(world: &mut World) {
+    world.exec(|(entities, physicals, animations, hazards, obstacles, pooled) :
+               (Entities, ReadStorage<Physical>, ReadStorage<Animation>, ReadStorage<Hazard>,
+                ReadStorage<Obstacle>, ReadStorage<Pooled>)| {
+        for (e, _) in (&entities, &physicals).join() {
+            if let Err(e) = entities.delete(e) {
+                log::error!("Failed to destroy entity: {}", e);
+            }
+        }
+        for (e, _) in (&entities, &hazards).join() {
+            if let Err(e) = entities.delete(e) {
+                log::error!("Failed to destroy entity: {}", e);
+            }
+        }
+        for (e, _) in (&entities, &obstacles).join() {
+            if let Err(e) = entities.delete(e) {
+                log::error!("Failed to destroy entity: {}", e);
+            }
+        }
+        for (e, _) in (&entities, &animations).join() {
+            if let Err(e) = entities.delete(e) {
+                log::error!("Failed to destroy entity: {}", e);
+            }
+        }
+        // parked entities have none of the components above
+        for (e, _) in (&entities, &pooled).join() {
+            if let Err(e) = entities.delete(e) {
+                log::error!("Failed to destroy entity: {}", e);
+            }
+        }
+    });
+    world.insert(EntityPools::default());
+    world.insert(Beams::default());
+    world.write_resource::<ParticlePool>().clear();
+    Hud::hide(world);
+    // back to the arena of the menu
+    let endless = Level::endless(&world.read_resource::<ArenaConfig>());
+    world.insert(endless);
+}
+
+// the gameplay systems of a level, also run by the benchmarks; a stage
+// starts once every system of the stage before it is done
+pub fn play_dispatcher<'a, 'b>(level: &Level, difficulty: Difficulty) -> DispatcherBuilder<'a, 'b> {
+    DispatcherBuilder::new()
+        // input: the commands of the ships and their thrust, turn and shots
+        .with(SpatialGridSystem, "spatial_grid_system", &[])
+        .with(PlayerInputSystem, "player_input_system", &[])
+        .with(AutoPilotSystem, "autopilot_system", &["spatial_grid_system"])
+        .with(ShipControlSystem, "ship_control_system",
+              &["spatial_grid_system", "player_input_system", "autopilot_system"])
+        .with_barrier()
+        // physics: everything moves
+        .with(PhysicalSystem, "physical_system", &[])
+        .with(StarfieldScrollSystem, "starfield_scroll_system", &[])
+        .with_barrier()
+        // boundary: the arena edges and the hazards act on the new positions
+        .with(BoundarySystem, "boundary_system", &[])
+        .with(HazardSystem, "hazard_system", &["boundary_system"])
+        .with_barrier()
+        // collision
+        .with(CollisionSystem::default(), "collision_system", &[])
+        .with_barrier()
+        // resolution: hits, score and the next asteroids
+        .with(DeletionSystem::default(), "deletion_system", &[])
+        .with(SpawnAsteroidSystem::from_rules(&level.spawn, difficulty), "spawn_system", &[])
+        .with(ScoringSystem::default(), "scoring_system", &["deletion_system"])
+        .with(StatsSystem::default(), "stats_system", &["deletion_system"])
+        .with(LevelSystem::default(), "level_system", &["scoring_system", "stats_system"])
+        .with_barrier()
+        // effects, then the spawns and releases queued by all stages
+        .with(AnimationSystem, "animation_system", &[])
+        .with(ParticleSystem::default(), "particle_system", &[])
+        .with(PoolSystem::default(), "pool_system", &["animation_system"])
 }
 
 #[derive(Default)]
 pub struct StatePlay<'a, 'b> {
     pub dispatcher: Option<Dispatcher<'a, 'b>>,
+    demo: bool,
+    // None plays the endless arena
+    level: Option<Level>,
+}
+
+impl<'a, 'b> StatePlay<'a, 'b> {
+    // a game played by the autopilot, shown behind the menu
+    pub fn demo() -> Self {
+        Self {
+            dispatcher: None,
+            demo: true,
+            level: None,
+        }
+    }
+
+    pub fn with_level(level: Level) -> Self {
+        Self {
+            dispatcher: None,
+            demo: false,
+            level: Some(level),
+        }
+    }
 }
 
 impl<'a, 'b> SimpleState for StatePlay<'a, 'b> {
@@ -64,18 +212,33 @@ impl<'a, 'b> SimpleState for StatePlay<'a, 'b> {
         let world = data.world;
 
         log::info!("Game Started");
-        initialize_ship(world);
-        ScoreRes::initialize(world);
+        world.insert(if self.demo { GameMode::Demo } else { GameMode::Normal });
+        let level = self.level.clone()
+            .unwrap_or_else(|| Level::endless(&world.read_resource::<ArenaConfig>()));
+        log::info!("Level: {}", level.name);
+        world.insert(EntityPools::default());
+        level::build(world, &level);
+        initialize_ship(world, self.demo);
+        let mut scoring = Scoring::new(&world.read_resource::<ScoringConfig>());
+        if self.demo {
+            // the demo ends with the first lost ship
+            scoring.lives = 1;
+        }
+        let lives = scoring.lives;
+        world.insert(scoring);
+        world.insert(SessionStats::default());
+        // the menu stays on top of the demo, it gets no HUD
+        if !self.demo {
+            Hud::show(world);
+            let mut hud_events = world.write_resource::<EventChannel<HudEvent>>();
+            hud_events.single_write(HudEvent::Lives(lives));
+            hud_events.single_write(HudEvent::Wave(1));
+        }
+
+        let difficulty = world.read_resource::<Settings>().difficulty;
 
         // create dispatcher
-        let mut dispatcher = DispatcherBuilder::new()
-            .with(ShipControlSystem, "ship_control_system", &[])
-            .with(PhysicalSystem, "physical_system", &["ship_control_system"])
-            .with(BoundarySystem, "boundary_system", &["physical_system"])
-            .with(SpawnAsteroidSystem::new(), "spawn_system", &[])
-            .with(CollisionSystem, "collision_system", &[])
-            .with(DeletionSystem::default(),  "deletion_system", &["collision_system"])
-            .with(ExplosionSystem, "explosion_system", &[])
+        let mut dispatcher = play_dispatcher(&level, difficulty)
             .with_pool((*world.read_resource::<ArcThreadPool>()).clone())
             .build();
         dispatcher.setup(world);
@@ -83,11 +246,23 @@ impl<'a, 'b> SimpleState for StatePlay<'a, 'b> {
         self.dispatcher = Some(dispatcher);
     }
 
+    fn on_stop(&mut self, data: StateData<'_, GameData<'_, '_>>) {
+        // a real game is cleaned up by StateOver after showing the result
+        if self.demo {
+            clear_play_field(data.world);
+        }
+    }
+
     fn handle_event(&mut self,
                     _data: StateData<'_, GameData<'_, '_>>,
                     event: StateEvent) -> SimpleTrans {
         if let StateEvent::Window(event) = event {
-            if is_key_down(&event, VirtualKeyCode::Escape) {
+            if self.demo {
+                // any key leaves the attract mode
+                if let Some((_, ElementState::Pressed)) = get_key(&event) {
+                    return Trans::Pop;
+                }
+            } else if is_key_down(&event, VirtualKeyCode::Escape) {
                 println!("Escape pressed");
                 return Trans::Push(Box::new(StatePause));
             }
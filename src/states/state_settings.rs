@@ -0,0 +1,227 @@
+use amethyst::{
+    ecs::prelude::{Entity},
+    input::{VirtualKeyCode, is_key_down},
+    prelude::*,
+    shrev::EventChannel,
+    ui::{Anchor, UiText, UiTransform},
+    window::Window,
+};
+
+use crate::level::{level_names};
+use crate::resources::{FontRes};
+use crate::settings::{Settings, WINDOW_SIZES, user_settings_path};
+use crate::system::GameEvent;
+
+const COLOR_NORMAL: [f32; 4] = [0., 0., 0., 1.];
+const COLOR_SELECTED: [f32; 4] = [1., 1., 1., 1.];
+
+// volume change of one key press
+const VOLUME_STEP: f32 = 0.1;
+
+#[derive(Clone, Copy, PartialEq)]
+enum SettingsRow {
+    WindowSize,
+    Fullscreen,
+    VSync,
+    MasterVolume,
+    MusicVolume,
+    EffectsVolume,
+    Difficulty,
+    Level,
+    Graphics,
+    ScreenShake,
+    HitStop,
+    Flash,
+    DebugOverlay,
+    Back,
+}
+
+impl SettingsRow {
+    const ALL: [SettingsRow; 14] = [
+        SettingsRow::WindowSize,
+        SettingsRow::Fullscreen,
+        SettingsRow::VSync,
+        SettingsRow::MasterVolume,
+        SettingsRow::MusicVolume,
+        SettingsRow::EffectsVolume,
+        SettingsRow::Difficulty,
+        SettingsRow::Level,
+        SettingsRow::Graphics,
+        SettingsRow::ScreenShake,
+        SettingsRow::HitStop,
+        SettingsRow::Flash,
+        SettingsRow::DebugOverlay,
+        SettingsRow::Back,
+    ];
+
+    fn label(self, settings: &Settings) -> String {
+        let on_off = |b: bool| if b { "On" } else { "Off" };
+        let percent = |v: f32| format!("{}%", (v * 100.0).round());
+        match self {
+            SettingsRow::WindowSize => match settings.dimensions {
+                Some((w, h)) => format!("Window Size: {}x{}", w, h),
+                None => "Window Size: Default".to_string(),
+            },
+            SettingsRow::Fullscreen => format!("Fullscreen: {}", on_off(settings.fullscreen)),
+            // the renderer cannot turn it off, see Settings::vsync
+            SettingsRow::VSync => "VSync: On (fixed by the renderer)".to_string(),
+            SettingsRow::MasterVolume => format!("Master Volume: {}", percent(settings.master_volume)),
+            SettingsRow::MusicVolume => format!("Music Volume: {}", percent(settings.music_volume)),
+            SettingsRow::EffectsVolume => format!("Effects Volume: {}", percent(settings.effects_volume)),
+            SettingsRow::Difficulty => format!("Difficulty: {}", settings.difficulty.name()),
+            SettingsRow::Level => format!("Level: {}", settings.level.as_deref().unwrap_or("Endless")),
+            SettingsRow::Graphics => format!("Graphics: {} (restart)", settings.render_mode.name()),
+            SettingsRow::ScreenShake => format!("Screen Shake: {}", on_off(settings.screen_shake)),
+            SettingsRow::HitStop => format!("Hit-Stop: {}", on_off(settings.hit_stop)),
+            SettingsRow::Flash => format!("Flash: {}", on_off(settings.flash)),
+            SettingsRow::DebugOverlay => format!("Debug Overlay: {}", on_off(settings.debug_overlay)),
+            SettingsRow::Back => "Back".to_string(),
+        }
+    }
+
+    // change the value of this row, step is -1 or 1
+    fn change(self, settings: &mut Settings, step: i32) {
+        let volume = |v: f32| (v + VOLUME_STEP * step as f32).max(0.0).min(1.0);
+        match self {
+            SettingsRow::WindowSize => {
+                let count = WINDOW_SIZES.len() as i32;
+                let current = settings.dimensions
+                    .and_then(|d| WINDOW_SIZES.iter().position(|&s| s == d))
+                    .map(|i| i as i32)
+                    .unwrap_or(if step > 0 { -1 } else { 0 });
+                let next = (current + step + count) % count;
+                settings.dimensions = Some(WINDOW_SIZES[next as usize]);
+            },
+            SettingsRow::Fullscreen => settings.fullscreen = !settings.fullscreen,
+            SettingsRow::VSync => {},
+            SettingsRow::MasterVolume => settings.master_volume = volume(settings.master_volume),
+            SettingsRow::MusicVolume => settings.music_volume = volume(settings.music_volume),
+            SettingsRow::EffectsVolume => settings.effects_volume = volume(settings.effects_volume),
+            SettingsRow::Difficulty => settings.difficulty = if step > 0 {
+                settings.difficulty.next()
+            } else {
+                settings.difficulty.prev()
+            },
+            SettingsRow::Level => {
+                // the endless arena first, then the level files
+                let mut choices: Vec<Option<String>> = vec![None];
+                choices.extend(level_names().into_iter().map(Some));
+                let count = choices.len() as i32;
+                let current = choices.iter().position(|c| *c == settings.level).unwrap_or(0) as i32;
+                settings.level = choices[((current + step + count) % count) as usize].clone();
+            },
+            SettingsRow::Graphics => settings.render_mode = settings.render_mode.toggle(),
+            SettingsRow::ScreenShake => settings.screen_shake = !settings.screen_shake,
+            SettingsRow::HitStop => settings.hit_stop = !settings.hit_stop,
+            SettingsRow::Flash => settings.flash = !settings.flash,
+            SettingsRow::DebugOverlay => settings.debug_overlay = !settings.debug_overlay,
+            SettingsRow::Back => {},
+        }
+    }
+
+    // whether the row changes the window
+    fn is_window(self) -> bool {
+        match self {
+            SettingsRow::WindowSize | SettingsRow::Fullscreen => true,
+            _ => false,
+        }
+    }
+}
+
+fn menu_event(world: &mut World, event: GameEvent) {
+    world.write_resource::<EventChannel<GameEvent>>().single_write(event);
+}
+
+#[derive(Default)]
+pub struct StateSettings {
+    rows: Vec<Entity>,
+    selected: usize,
+}
+
+impl StateSettings {
+    fn refresh(&self, world: &mut World) {
+        let settings = world.read_resource::<Settings>().clone();
+        let mut texts = world.write_storage::<UiText>();
+        for (i, (row, e)) in SettingsRow::ALL.iter().zip(self.rows.iter()).enumerate() {
+            if let Some(text) = texts.get_mut(*e) {
+                text.text = row.label(&settings);
+                text.color = if i == self.selected { COLOR_SELECTED } else { COLOR_NORMAL };
+            }
+        }
+    }
+
+    fn change(&mut self, world: &mut World, step: i32) {
+        let row = SettingsRow::ALL[self.selected];
+        row.change(&mut world.write_resource::<Settings>(), step);
+        menu_event(world, GameEvent::MenuSelected);
+        if row.is_window() {
+            world.read_resource::<Settings>().apply_window(&world.read_resource::<Window>());
+        }
+        self.refresh(world);
+    }
+
+    fn save(&self, world: &mut World) {
+        let result = user_settings_path()
+            .and_then(|path| world.read_resource::<Settings>().save(&path));
+        if let Err(e) = result {
+            log::error!("Failed to save user settings: {}", e);
+        }
+    }
+}
+
+impl SimpleState for StateSettings {
+    fn on_start(&mut self, data: StateData<'_, GameData<'_, '_>>) {
+        let world = data.world;
+        let font = world.read_resource::<FontRes>().font();
+        self.rows = (0..SettingsRow::ALL.len()).map(|i| {
+            let transform = UiTransform::new(
+                format!("settings_{}", i), Anchor::TopMiddle, Anchor::TopMiddle,
+                0., -70. - 45. * i as f32, 1., 1000., 42.);
+            world
+                .create_entity()
+                .with(transform)
+                .with(UiText::new(font.clone(), "".to_string(), COLOR_NORMAL, 33.))
+                .build()
+        }).collect();
+        self.refresh(world);
+    }
+
+    fn on_stop(&mut self, data: StateData<'_, GameData<'_, '_>>) {
+        self.save(data.world);
+        if let Err(e) = data.world.delete_entities(&self.rows) {
+            log::error!("Cannot delete settings text: {}", e);
+        }
+        self.rows.clear();
+    }
+
+    fn handle_event(&mut self,
+                    data: StateData<'_, GameData<'_, '_>>,
+                    event: StateEvent) -> SimpleTrans {
+        if let StateEvent::Window(event) = event {
+            let count = SettingsRow::ALL.len();
+            if is_key_down(&event, VirtualKeyCode::Up) {
+                self.selected = (self.selected + count - 1) % count;
+                self.refresh(data.world);
+                menu_event(data.world, GameEvent::MenuMoved);
+            } else if is_key_down(&event, VirtualKeyCode::Down) {
+                self.selected = (self.selected + 1) % count;
+                self.refresh(data.world);
+                menu_event(data.world, GameEvent::MenuMoved);
+            } else if is_key_down(&event, VirtualKeyCode::Left) {
+                self.change(data.world, -1);
+            } else if is_key_down(&event, VirtualKeyCode::Right) {
+                self.change(data.world, 1);
+            } else if is_key_down(&event, VirtualKeyCode::Escape) {
+                return Trans::Pop;
+            } else if is_key_down(&event, VirtualKeyCode::Space) ||
+                      is_key_down(&event, VirtualKeyCode::Return) {
+                if SettingsRow::ALL[self.selected] == SettingsRow::Back {
+                    return Trans::Pop;
+                }
+                self.change(data.world, 1);
+            }
+        }
+
+        Trans::None
+    }
+}
@@ -0,0 +1,214 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use amethyst::{
+    core::timing::Time,
+    ecs::{System, SystemData,
+          Read, Write,
+          World},
+    shrev::{EventChannel, ReaderId},
+    utils::application_root_dir,
+};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::components::{AsteroidSize};
+use crate::hud::{format_score};
+use crate::system::{GameEvent};
+
+// what happened during one game, collected by StatsSystem
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct SessionStats {
+    pub score: i32,
+    // seconds
+    pub time_survived: f32,
+    pub shots_fired: u32,
+    pub hits: u32,
+    // [small, medium, large]
+    pub asteroids_destroyed: [u32; 3],
+    pub distance_flown: f32,
+    pub max_speed: f32,
+    // the game has no UFOs yet, always 0
+    pub ufos_killed: u32,
+}
+
+impl SessionStats {
+    pub fn accuracy(&self) -> f32 {
+        if self.shots_fired == 0 {
+            0.0
+        } else {
+            self.hits as f32 / self.shots_fired as f32
+        }
+    }
+
+    pub fn asteroids_total(&self) -> u32 {
+        self.asteroids_destroyed.iter().sum()
+    }
+
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string_pretty(self)
+    }
+
+    // write the stats to stats/session-<unix time>.json
+    pub fn export(&self) -> amethyst::Result<PathBuf> {
+        let dir = application_root_dir()?.join("stats");
+        fs::create_dir_all(&dir)?;
+        let seconds = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        let path = dir.join(format!("session-{}.json", seconds));
+        fs::write(&path, self.to_json()?)?;
+        info!("Exported game statistics to {}", path.display());
+        Ok(path)
+    }
+}
+
+fn size_index(size: AsteroidSize) -> usize {
+    match size {
+        AsteroidSize::Small => 0,
+        AsteroidSize::Medium => 1,
+        AsteroidSize::Large => 2,
+    }
+}
+
+// the best values of all games, stored in config/personal_bests.ron
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PersonalBests {
+    pub score: i32,
+    pub time_survived: f32,
+    pub accuracy: f32,
+    pub asteroids_destroyed: u32,
+    pub distance_flown: f32,
+    pub max_speed: f32,
+}
+
+pub fn personal_bests_path() -> amethyst::Result<PathBuf> {
+    Ok(application_root_dir()?.join("config").join("personal_bests.ron"))
+}
+
+impl PersonalBests {
+    // a missing or invalid file starts over
+    pub fn load(path: &Path) -> Self {
+        if !path.exists() {
+            return PersonalBests::default();
+        }
+
+        let bests = fs::read_to_string(path)
+            .map_err(|e| e.to_string())
+            .and_then(|content| ron::de::from_str::<PersonalBests>(&content)
+                      .map_err(|e| e.to_string()));
+        match bests {
+            Ok(bests) => bests,
+            Err(e) => {
+                warn!("Invalid personal bests {}: {}, starting over", path.display(), e);
+                PersonalBests::default()
+            }
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> amethyst::Result<()> {
+        let content = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())?;
+        fs::write(path, content)?;
+        Ok(())
+    }
+
+    // take the better values of a finished game
+    pub fn update(&mut self, stats: &SessionStats) {
+        self.score = self.score.max(stats.score);
+        self.time_survived = self.time_survived.max(stats.time_survived);
+        self.accuracy = self.accuracy.max(stats.accuracy());
+        self.asteroids_destroyed = self.asteroids_destroyed.max(stats.asteroids_total());
+        self.distance_flown = self.distance_flown.max(stats.distance_flown);
+        self.max_speed = self.max_speed.max(stats.max_speed);
+    }
+}
+
+// 154.3 -> "2:34"
+pub fn format_time(seconds: f32) -> String {
+    let seconds = seconds.max(0.0) as u32;
+    format!("{}:{:02}", seconds / 60, seconds % 60)
+}
+
+// "value", with the old best or a new best mark behind it
+fn compare<T: PartialOrd>(value: T, best: T, text: String, best_text: String) -> String {
+    if value > best {
+        format!("{}  NEW BEST", text)
+    } else {
+        format!("{}  (best {})", text, best_text)
+    }
+}
+
+// the lines of the game over screen, compared to the bests before this game
+pub fn stat_lines(stats: &SessionStats, bests: &PersonalBests) -> Vec<String> {
+    let percent = |v: f32| format!("{:.0}%", v * 100.0);
+    let [small, medium, large] = stats.asteroids_destroyed;
+    vec![
+        compare(stats.score, bests.score,
+                format!("Score {}", format_score(stats.score)), format_score(bests.score)),
+        compare(stats.time_survived, bests.time_survived,
+                format!("Time {}", format_time(stats.time_survived)),
+                format_time(bests.time_survived)),
+        compare(stats.accuracy(), bests.accuracy,
+                format!("Accuracy {} ({} / {})", percent(stats.accuracy()),
+                        stats.hits, stats.shots_fired),
+                percent(bests.accuracy)),
+        compare(stats.asteroids_total(), bests.asteroids_destroyed,
+                format!("Asteroids {} ({} small, {} medium, {} large)",
+                        stats.asteroids_total(), small, medium, large),
+                bests.asteroids_destroyed.to_string()),
+        compare(stats.distance_flown, bests.distance_flown,
+                format!("Distance {:.0}", stats.distance_flown),
+                format!("{:.0}", bests.distance_flown)),
+        compare(stats.max_speed, bests.max_speed,
+                format!("Max Speed {:.0}", stats.max_speed),
+                format!("{:.0}", bests.max_speed)),
+        format!("UFOs {}", stats.ufos_killed),
+    ]
+}
+
+// collect the session statistics from the game events
+#[derive(Default)]
+pub struct StatsSystem {
+    event_reader: Option<ReaderId<GameEvent>>,
+}
+
+impl<'s> System<'s> for StatsSystem {
+    type SystemData = (
+        Write<'s, SessionStats>,
+        Read<'s, EventChannel<GameEvent>>,
+        Read<'s, Time>,
+    );
+
+    fn setup(&mut self, world: &mut World) {
+        Self::SystemData::setup(world);
+        self.event_reader = Some(
+            world
+                .fetch_mut::<EventChannel<GameEvent>>()
+                .register_reader()
+        )
+    }
+
+    fn run(&mut self,
+           (mut stats,
+            events,
+            time): Self::SystemData) {
+        stats.time_survived += time.delta_seconds();
+
+        for event in events.read(self.event_reader.as_mut().unwrap()) {
+            match *event {
+                GameEvent::ShotFired => stats.shots_fired += 1,
+                GameEvent::ShotHit => stats.hits += 1,
+                GameEvent::AsteroidDestroyed { size, .. } => {
+                    stats.asteroids_destroyed[size_index(size)] += 1;
+                },
+                GameEvent::ShipMoved { distance, speed } => {
+                    stats.distance_flown += distance;
+                    stats.max_speed = stats.max_speed.max(speed);
+                },
+                _ => {},
+            }
+        }
+    }
+}
@@ -1,34 +1,126 @@
+use std::f32::consts::FRAC_PI_2;
+
 use amethyst::{
     core::{
-        math::{Vector3, Vector2, zero, Isometry2},
+        math::{Vector3, Vector2, zero},
         transform::components::Transform,
         timing::Time,
+        Hidden,
     },
     derive::{SystemDesc},
     ecs::{Join,
           ReadStorage, WriteStorage,
           System, SystemData,
           Read, ReadExpect,
-          Entities, LazyUpdate,
+          Entities,
           Write, WriteExpect,
           World},
-    ecs::prelude::{Entity},
-    prelude::{Trans, TransEvent, GameData, StateEvent},
-    renderer::{SpriteRender},
+    ecs::prelude::{Builder, Entity},
+    renderer::{SpriteRender, Camera},
     shrev::{EventChannel, ReaderId},
     input::{InputHandler, StringBindings},
-    ui::{UiText},
+    ui::{UiTransform, UiImage, Anchor},
+    window::ScreenDimensions,
 };
 
 use log::{error};
-use ncollide2d::{
-    bounding_volume,
-    shape::Ball,
-    broad_phase::{DBVTBroadPhase, BroadPhase, BroadPhaseInterferenceHandler}};
+use rayon::prelude::*;
+
+use crate::broad_phase::{self, Bounds};
+use crate::components::{Physical, Ship, Bullet, Asteroid, AsteroidSize, Collider, ColliderType,
+                        ColliderShape, ShipCommand, AutoPilot};
+use crate::geometry;
+use crate::hazards::{self, Hazard};
+use crate::hud::{HudEvent};
+use crate::level::{EdgeHit, Level, SpawnRules};
+use crate::particles::{Emitter};
+use crate::physics::{self, AsteroidPhysicsConfig, Body, ShipMotion, ShipPhysicsConfig};
+use crate::pool::{EntityPools, Spawn};
+use crate::resources::{RandomGen,
+                       Arena, ArenaConfig, Viewport, ViewMode, fit_view};
+use crate::settings::{Difficulty};
+use crate::spatial::{SpatialGrid};
+use crate::textures::{SpriteRegistry};
+use crate::vector::{Outline};
+use crate::weapons::{Beams, Muzzle, WeaponContext, Weapons};
+
+#[derive(SystemDesc)]
+pub struct PlayerInputSystem;
+
+impl<'s> System<'s> for PlayerInputSystem {
+    type SystemData = (
+        WriteStorage<'s, ShipCommand>,
+        ReadStorage<'s, AutoPilot>,
+        Read<'s, InputHandler::<StringBindings>>,
+    );
+
+    fn run(&mut self,
+           (mut commands,
+            autopilots,
+            input): Self::SystemData) {
+        for (command, _) in (&mut commands, !&autopilots).join() {
+            command.accelerate = input.axis_value("accelerate").unwrap_or_default();
+            command.rotate = input.axis_value("rotate").unwrap_or_default();
+            command.shoot = input.action_is_down("shoot").unwrap_or(false);
+            command.switch_weapon = input.action_is_down("switch_weapon").unwrap_or(false);
+        }
+    }
+}
+
+#[derive(SystemDesc)]
+pub struct AutoPilotSystem;
+
+impl<'s> System<'s> for AutoPilotSystem {
+    type SystemData = (
+        WriteStorage<'s, ShipCommand>,
+        ReadStorage<'s, AutoPilot>,
+        ReadStorage<'s, Transform>,
+        ReadStorage<'s, Physical>,
+        Read<'s, SpatialGrid>,
+    );
+
+    fn run(&mut self,
+           (mut commands,
+            autopilots,
+            transforms,
+            physicals,
+            grid): Self::SystemData) {
+        for (command, pilot, transform, physical) in
+            (&mut commands, &autopilots, &transforms, &physicals).join() {
+            let position = Vector2::new(transform.translation().x, transform.translation().y);
+
+            // aim at the nearest asteroid, across the edges if they wrap
+            let target = grid.nearest(position, ColliderType::Asteroid, std::f32::INFINITY)
+                .map(|asteroid| grid.offset(position, asteroid.position));
 
-use crate::components::{Physical, Ship, Bullet, Asteroid, Explosion, Collider, ColliderType};
-use crate::resources::{BulletRes, AsteroidRes, RandomGen, ExplosionRes, ScoreRes};
-use crate::states::{ARENA_WIDTH, ARENA_HEIGHT, self};
+            match target {
+                Some(target) => {
+                    let heading = transform.rotation() * Vector3::y();
+                    let cross = heading.x * target.y - heading.y * target.x;
+                    let dot = heading.x * target.x + heading.y * target.y;
+                    let angle = cross.atan2(dot);
+
+                    // damped by the angular velocity so the nose does not overshoot
+                    command.rotate = (angle * 2.0 - physical.rotation * 0.5).max(-1.0).min(1.0);
+                    command.shoot = angle.abs() < pilot.aim_tolerance;
+                    command.accelerate = if target.norm() > pilot.keep_distance * 1.5 {
+                        0.3
+                    } else if target.norm() < pilot.keep_distance * 0.5 {
+                        -0.5
+                    } else {
+                        0.0
+                    };
+                },
+                None => {
+                    // nothing to shoot, cruise around slowly
+                    command.accelerate = 0.0;
+                    command.rotate = 0.3;
+                    command.shoot = false;
+                }
+            }
+        }
+    }
+}
 
 #[derive(SystemDesc)]
 pub struct ShipControlSystem;
@@ -36,69 +128,98 @@ pub struct ShipControlSystem;
 impl<'s> System<'s> for ShipControlSystem {
     type SystemData = (
         WriteStorage<'s, Physical>,
-        WriteStorage<'s, Ship>,
+        ReadStorage<'s, Ship>,
+        ReadStorage<'s, ShipCommand>,
         ReadStorage<'s, Transform>,
-        ReadExpect<'s, BulletRes>,
+        WriteStorage<'s, Weapons>,
+        WriteStorage<'s, Emitter>,
+        WriteStorage<'s, Hidden>,
+        ReadExpect<'s, ShipPhysicsConfig>,
+        ReadExpect<'s, Arena>,
+        Read<'s, SpatialGrid>,
         Entities<'s>,
-        Read<'s, LazyUpdate>,
-        Read<'s, InputHandler::<StringBindings>>,
+        Write<'s, EntityPools>,
+        Write<'s, Beams>,
         Read<'s, Time>,
+        Write<'s, EventChannel<CollisionEvent>>,
+        Write<'s, EventChannel<GameEvent>>,
     );
 
     fn run(&mut self,
            (mut physicals,
-            mut ships,
+            ships,
+            commands,
             transforms,
-            bullet_resources,
+            mut weapons,
+            mut emitters,
+            mut hiddens,
+            config,
+            arena,
+            grid,
             entities,
-            lazy,
-            input,
-            time): Self::SystemData) {
+            mut pools,
+            mut beams,
+            time,
+            mut collisions,
+            mut game_events): Self::SystemData) {
         let delta = time.delta_seconds();
 
-        for (physical, ship, transform) in (&mut physicals, &mut ships, &transforms).join() {
-            let acceleration = input.axis_value("accelerate");
-            let rotate = input.axis_value("rotate");
-            let shoot = input.action_is_down("shoot").unwrap_or(false);
-
+        for (e, physical, ship, command, transform, weapons) in
+            (&entities, &mut physicals, &ships, &commands, &transforms, (&mut weapons).maybe()).join() {
             // handle acceleration -> velocity
-            let acc = acceleration.unwrap_or_default();
-            let added = Vector3::y() * delta * acc * ship.acceleration;
-            let added = transform.rotation() * added;
-            physical.velocity += Vector2::new(added.x, added.y);
-
-            let magnitude = physical.velocity.magnitude();
-            if magnitude > physical.max_velocity {
-                physical.velocity *= physical.max_velocity / magnitude;
+            let acc = command.accelerate;
+            if acc > 0.0 {
+                game_events.single_write(GameEvent::Thrusting);
+            }
+            if let Some(emitter) = emitters.get_mut(e) {
+                emitter.active = acc > 0.0;
             }
+            if let Some(flame) = ship.flame {
+                let result = if acc > 0.0 {
+                    hiddens.remove(flame);
+                    Ok(None)
+                } else {
+                    hiddens.insert(flame, Hidden)
+                };
+                if let Err(e) = result {
+                    error!("Failed to update thrust flame: {}", e);
+                }
+            }
+            // thrust, drag and rotation -> velocity and angular velocity
+            let heading = transform.rotation() * Vector3::y();
+            let heading = Vector2::new(heading.x, heading.y);
+            let motion = physics::step(
+                ShipMotion { velocity: physical.velocity, angular_velocity: physical.rotation },
+                heading, acc, command.rotate, &config, delta);
+            physical.velocity = motion.velocity;
+            physical.rotation = motion.angular_velocity;
 
-            // handle rotation -> rotate
-            physical.rotation = rotate.unwrap_or_default() * delta * ship.rotate;
-
-            // handle shoot
-            if ship.reload_timer <= 0.0f32 {
-                if shoot {
-                    ship.reload_timer = ship.time_to_reload;
-
-                    let bullet_transform = transform.clone();
-                    let velocity = transform.rotation() * Vector3::y() * 150f32;
-                    let velocity = physical.velocity + Vector2::new(velocity.x, velocity.y);
-                    let bullet_physical = Physical {
-                        velocity: velocity,
-                        max_velocity: 200f32,
-                        rotation: 0f32,
+            // handle weapon switch and shoot, a ship without weapons still flies
+            let weapons = match weapons {
+                Some(weapons) => weapons,
+                None => continue,
+            };
+            weapons.switch(command.switch_weapon);
+            weapons.update(delta);
+            if command.shoot {
+                if let Some(weapon) = weapons.selected_mut() {
+                    let mut muzzle = Muzzle {
+                        transform,
+                        position: Vector2::new(transform.translation().x, transform.translation().y),
+                        heading,
+                        velocity: &mut physical.velocity,
                     };
-
-                    let e = entities.create();
-
-                    lazy.insert(e, Bullet {} );
-                    lazy.insert(e, bullet_transform);
-                    lazy.insert(e, bullet_physical);
-                    lazy.insert(e, Collider { typ: ColliderType::Bullet } );
-                    lazy.insert(e, bullet_resources.sprite_render());
+                    let mut context = WeaponContext {
+                        arena: *arena,
+                        grid: &grid,
+                        ship_config: &config,
+                        pools: &mut pools,
+                        collisions: &mut collisions,
+                        game_events: &mut game_events,
+                        beams: &mut beams,
+                    };
+                    weapon.fire(&mut muzzle, &mut context);
                 }
-            } else {
-                ship.reload_timer = (ship.reload_timer - delta).max(0.0f32);
             }
         }
     }
@@ -109,18 +230,47 @@ pub struct PhysicalSystem;
 
 impl<'s> System<'s> for PhysicalSystem {
     type SystemData = (
-        ReadStorage<'s, Physical>,
+        WriteStorage<'s, Physical>,
+        ReadStorage<'s, Ship>,
+        ReadStorage<'s, Hazard>,
         WriteStorage<'s, Transform>,
         Read<'s, Time>,
+        Write<'s, EventChannel<GameEvent>>,
     );
 
     fn run(&mut self,
-           (physicals,
+           (mut physicals,
+            ships,
+            hazards,
             mut transforms,
-            time): Self::SystemData) {
+            time,
+            mut game_events): Self::SystemData) {
         let delta = time.delta_seconds();
-        for (physical, transform) in (&physicals, &mut transforms).join() {
+        let wells: Vec<(Vector2<f32>, f32)> = (&hazards, &transforms).join()
+            .map(|(hazard, transform)| {
+                let t = transform.translation();
+                (Vector2::new(t.x, t.y), hazard.strength)
+            })
+            .collect();
+        for (physical, transform, ship) in (&mut physicals, &mut transforms, (&ships).maybe()).join() {
+            if !wells.is_empty() {
+                let position = Vector2::new(transform.translation().x, transform.translation().y);
+                let pull = wells.iter()
+                    .map(|&(center, strength)| hazards::gravity(position, center, strength))
+                    .fold(Vector2::new(0.0, 0.0), |sum, a| sum + a);
+                physical.velocity += pull * delta;
+                // only ships have a top speed, asteroids keep their bounce velocities
+                if ship.is_some() {
+                    physical.velocity = physics::clamp_speed(physical.velocity, physical.max_velocity);
+                }
+            }
             let movement = physical.velocity * delta;
+            if ship.is_some() && delta > 0.0 {
+                game_events.single_write(GameEvent::ShipMoved {
+                    distance: movement.norm(),
+                    speed: physical.velocity.norm(),
+                });
+            }
             let rotation = physical.rotation * delta;
             transform.prepend_translation(Vector3::new(movement.x, movement.y, 0.0));
             transform.rotate_2d(rotation);
@@ -134,47 +284,113 @@ pub struct BoundarySystem;
 
 impl<'s> System<'s> for BoundarySystem {
     type SystemData = (
-        WriteStorage<'s, Transform>,
-        ReadStorage<'s, Physical>,
-        ReadStorage<'s, Bullet>,
         Entities<'s>,
+        WriteStorage<'s, Transform>,
+        WriteStorage<'s, Physical>,
+        WriteStorage<'s, Bullet>,
+        ReadStorage<'s, Ship>,
+        ReadExpect<'s, Arena>,
+        ReadExpect<'s, Level>,
+        Write<'s, EntityPools>,
+        Write<'s, EventChannel<GameEvent>>,
     );
 
     fn run(&mut self,
-           (mut transforms,
-            physicals,
-            bullets,
-            entities): Self::SystemData) {
-        for (_, _, transform) in (&physicals, !&bullets, &mut transforms).join() {
-            let ship_x = transform.translation().x;
-            let ship_y = transform.translation().y;
-            if ship_x < 0.0 {
-                transform.set_translation_x(ARENA_WIDTH-0.5);
-            } else if ship_x > ARENA_WIDTH {
-                transform.set_translation_x(0.5);
+           (entities,
+            mut transforms,
+            mut physicals,
+            mut bullets,
+            ships,
+            arena,
+            level,
+            mut pools,
+            mut game_events): Self::SystemData) {
+        let edges = &level.edges;
+        for (e, physical, transform, bullet, ship) in (&*entities, &mut physicals, &mut transforms,
+                                                       (&mut bullets).maybe(), ships.maybe()).join() {
+            let position = Vector2::new(transform.translation().x, transform.translation().y);
+            let result = edges.apply(position, physical.velocity, arena.width, arena.height);
+            if result.hit == EdgeHit::None {
+                continue;
             }
+            transform.set_translation_x(result.position.x);
+            transform.set_translation_y(result.position.y);
+            physical.velocity = result.velocity;
 
-            if ship_y < 0.0 {
-                transform.set_translation_y(ARENA_HEIGHT-0.5);
-            } else if ship_y > ARENA_HEIGHT {
-                transform.set_translation_y(0.5);
+            if let Some(bullet) = bullet {
+                // bullets never wrap, they ricochet off walls a few times
+                if edges.ricochet(result.hit, bullet.ricochets) {
+                    bullet.ricochets += 1;
+                    transform.set_rotation_2d(result.velocity.y.atan2(result.velocity.x) - FRAC_PI_2);
+                } else {
+                    game_events.single_write(GameEvent::ShotMissed);
+                    pools.release(e);
+                }
+            } else if ship.is_some() {
+                if result.hit >= EdgeHit::Damaged {
+                    game_events.single_write(GameEvent::ShipDestroyed { position: result.position });
+                }
+            } else if result.hit == EdgeHit::Killed {
+                pools.release(e);
             }
         }
+    }
+}
 
-        for (e, _, transform) in (&*entities, &bullets, &mut transforms).join() {
-            let x = transform.translation().x;
-            let y = transform.translation().y;
-            if x < 0.0 || y < 0.0 || x > ARENA_WIDTH || y > ARENA_HEIGHT {
-                if let Err(e) = entities.delete(e) {
-                    error!("Failed to destroy entity: {}", e);
-                }
+// outline radius of an asteroid at scale 1, matching its 16px sprite
+pub const ASTEROID_RADIUS: f32 = 7.0;
 
-                continue;
-            }
-        }
+// every asteroid gets its own outline from the seed, drawn in vector mode
+// and used as the collision shape in both modes; there is no sprite
+// without a registry, e.g. in the benchmarks
+pub fn asteroid_components(sprites: Option<&SpriteRegistry>, size: AsteroidSize,
+                           position: Vector2<f32>, velocity: Vector2<f32>,
+                           rotation: f32, seed: u64)
+                           -> (Asteroid, Transform, Physical, Collider, Outline, Option<SpriteRender>) {
+    let mut transform = Transform::default();
+    transform.set_translation_xyz(position.x, position.y, 0.0);
+    transform.set_scale(Vector3::new(size.scale(), size.scale(), 1.0));
+    let outline = geometry::asteroid_outline(seed, ASTEROID_RADIUS);
+    let sprite = sprites.map(|sprites| {
+        let variant = seed as usize % sprites.variant_count("asteroid").max(1);
+        sprites.sprite_render(&format!("asteroid.{}", variant))
+    });
+    (Asteroid { size },
+     transform,
+     Physical {
+         velocity,
+         max_velocity: 100f32,
+         rotation,
+     },
+     Collider {
+         typ: ColliderType::Asteroid,
+         shape: ColliderShape::Polygon(outline.clone()),
+     },
+     Outline::polygon(outline),
+     sprite)
+}
+
+// an asteroid outside of any pool, e.g. placed by a level
+pub fn create_asteroid<B: Builder>(builder: B, sprites: &SpriteRegistry, size: AsteroidSize,
+                                   position: Vector2<f32>, velocity: Vector2<f32>,
+                                   rotation: f32, seed: u64) -> Entity {
+    let (asteroid, transform, physical, collider, outline, sprite) =
+        asteroid_components(Some(sprites), size, position, velocity, rotation, seed);
+    let mut builder = builder
+        .with(asteroid)
+        .with(transform)
+        .with(physical)
+        .with(collider)
+        .with(outline);
+    if let Some(sprite) = sprite {
+        builder = builder.with(sprite);
     }
+    builder.build()
 }
 
+// a new wave starts every this many spawned asteroids
+const ASTEROIDS_PER_WAVE: u32 = 20;
+
 #[derive(SystemDesc)]
 pub struct SpawnAsteroidSystem {
     pub time_to_spawn: f32,
@@ -182,77 +398,94 @@ pub struct SpawnAsteroidSystem {
     pub max_rotation: f32,
     pub distance_to_ship: f32,
     pub average_spawn_time: f32,
+    pub enabled: bool,
+    // 0 is unlimited
+    pub max_asteroids: usize,
+    // asteroids spawned so far, counts the waves
+    spawned: u32,
 }
 
 impl SpawnAsteroidSystem {
-    pub fn new() -> Self {
+    pub fn from_rules(rules: &SpawnRules, difficulty: Difficulty) -> Self {
         Self {
             time_to_spawn: 2f32,
-            max_velocity: 60f32,
+            max_velocity: rules.max_velocity * difficulty.velocity_scale(),
             max_rotation: 5f32,
             distance_to_ship: 200f32,
-            average_spawn_time: 0.5f32,
+            average_spawn_time: rules.interval * difficulty.spawn_time_scale(),
+            enabled: rules.enabled,
+            max_asteroids: rules.max_asteroids as usize,
+            spawned: 0,
         }
     }
 }
 
 impl<'s> System<'s> for SpawnAsteroidSystem {
     type SystemData = (
-        Entities<'s>,
         ReadStorage<'s, Ship>,
+        ReadStorage<'s, Asteroid>,
         ReadStorage<'s, Transform>,
-        ReadExpect<'s, AsteroidRes>,
         ReadExpect<'s, RandomGen>,
-        Read<'s, LazyUpdate>,
+        ReadExpect<'s, Arena>,
+        Read<'s, SpatialGrid>,
+        Write<'s, EntityPools>,
         Read<'s, Time>,
+        Write<'s, EventChannel<HudEvent>>,
     );
 
     fn run(&mut self,
-           (entities,
-            ships,
+           (ships,
+            asteroids,
             transforms,
-            asteroidres,
             rand,
-            lazy,
-            time): Self::SystemData) {
+            arena,
+            grid,
+            mut pools,
+            time,
+            mut hud_events): Self::SystemData) {
+        if !self.enabled {
+            return;
+        }
         let delta = time.delta_seconds();
         self.time_to_spawn -= delta;
 
+        // a small arena may not have a point that far away from the ship
+        let distance_to_ship = self.distance_to_ship.min(0.4 * arena.width.min(arena.height));
+
         if self.time_to_spawn <= 0.0f32 {
+            if self.max_asteroids > 0 && asteroids.join().count() >= self.max_asteroids {
+                return;
+            }
             for (_, ship_transform) in (&ships, &transforms).join()  {
-                let ship_translation = ship_transform.translation();
+                let ship_translation = Vector2::new(ship_transform.translation().x,
+                                                    ship_transform.translation().y);
 
-                let mut transform = Transform::default();
-                let mut create_point: Vector3<f32> = zero();
-                // generate creation point
+                let mut create_point: Vector2<f32> = zero();
+                // generate creation point, not just across a wrapping edge
                 loop {
-                    create_point.x = rand.next_f32() * ARENA_WIDTH;
-                    create_point.y = rand.next_f32() * ARENA_HEIGHT;
-                    if (ship_translation-create_point).norm() > self.distance_to_ship {
+                    create_point.x = rand.next_f32() * arena.width;
+                    create_point.y = rand.next_f32() * arena.height;
+                    if grid.offset(ship_translation, create_point).norm() > distance_to_ship {
                         break;
                     }
                 }
-                transform.set_translation_x(create_point.x);
-                transform.set_translation_y(create_point.y);
-                // scale, velocity, rotation
-                transform.set_scale(Vector3::new(1.0, 1.0, 1.0));
+                // size, velocity, rotation
+                let size = AsteroidSize::ALL[(rand.next_u32() % 3) as usize];
                 let gen = || (rand.next_f32() - 0.5) * 2.0 * self.max_velocity;
                 let velocity = Vector2::new(gen(), gen());
+                let rotation = self.max_rotation * 2.0 * (rand.next_f32() - 0.5);
 
-                let physical = Physical {
-                    velocity: velocity,
-                    max_velocity: 100f32,
-                    rotation: self.max_rotation * 2.0 * (rand.next_f32() - 0.5),
-                };
-
-                let e = entities.create();
-
-                lazy.insert(e, Asteroid {} );
-                lazy.insert(e, transform);
-                lazy.insert(e, physical);
-                lazy.insert(e, Collider { typ: ColliderType::Asteroid } );
-                lazy.insert(e, asteroidres.sprite_render(
-                        (rand.next_u32() % 3) as usize));
+                pools.spawn(Spawn::Asteroid {
+                    size,
+                    position: create_point,
+                    velocity,
+                    rotation,
+                    seed: rand.next_u32() as u64,
+                });
+                self.spawned += 1;
+                if self.spawned % ASTEROIDS_PER_WAVE == 0 {
+                    hud_events.single_write(HudEvent::Wave(self.spawned / ASTEROIDS_PER_WAVE + 1));
+                }
                 self.time_to_spawn = self.average_spawn_time + rand.next_f32();
             }
         }
@@ -261,41 +494,110 @@ impl<'s> System<'s> for SpawnAsteroidSystem {
 
 struct CollisionHandler {
     collide_entity: Vec<Entity>,
-    ship_hit: bool,
+    // bullets that hit an asteroid and bullets stopped by an obstacle
+    shots_hit: Vec<Entity>,
+    shots_blocked: Vec<Entity>,
+    ship_hit: Option<Entity>,
+    // asteroids bounce off each other instead of passing through
+    asteroid_bounce: bool,
+    bounces: Vec<(Entity, Entity)>,
+    // asteroid and obstacle
+    obstacle_bounces: Vec<(Entity, Entity)>,
 }
 
 impl CollisionHandler {
-    pub fn new() -> Self {
+    pub fn new(asteroid_bounce: bool) -> Self {
         Self {
             collide_entity: vec![],
-            ship_hit: false,
+            shots_hit: vec![],
+            shots_blocked: vec![],
+            ship_hit: None,
+            asteroid_bounce,
+            bounces: vec![],
+            obstacle_bounces: vec![],
         }
     }
-}
-
-type ColliderEntity = (ColliderType, Entity);
 
-impl BroadPhaseInterferenceHandler<ColliderEntity> for CollisionHandler {
-    fn is_interference_allowed(&mut self, a: &ColliderEntity, b: &ColliderEntity) -> bool {
-
-        a.0 != b.0
+    // colliders of the same type pass through each other
+    fn allows(&self, a: &ColliderEntity, b: &ColliderEntity) -> bool {
+        a.0 != b.0 || (self.asteroid_bounce && a.0 == ColliderType::Asteroid)
     }
-    fn interference_started(&mut self, a: &ColliderEntity, b: &ColliderEntity) {
+
+    fn hit(&mut self, a: &ColliderEntity, b: &ColliderEntity) {
         match (a.0, b.0) {
-            (ColliderType::Asteroid, ColliderType::Bullet) |
+            (ColliderType::Asteroid, ColliderType::Bullet) => {
+                self.collide_entity.push(a.1);
+                self.collide_entity.push(b.1);
+                self.shots_hit.push(b.1);
+            },
             (ColliderType::Bullet, ColliderType::Asteroid) => {
                 self.collide_entity.push(a.1);
                 self.collide_entity.push(b.1);
+                self.shots_hit.push(a.1);
+            },
+            (ColliderType::Asteroid, ColliderType::Ship) => {
+                self.ship_hit = Some(b.1);
             },
-            (ColliderType::Asteroid, ColliderType::Ship) |
             (ColliderType::Ship, ColliderType::Asteroid) => {
-                self.ship_hit = true;
+                self.ship_hit = Some(a.1);
+            },
+            (ColliderType::Asteroid, ColliderType::Asteroid) => {
+                self.bounces.push((a.1, b.1));
+            },
+            // obstacles are solid for everything
+            (ColliderType::Ship, ColliderType::Obstacle) => {
+                self.ship_hit = Some(a.1);
+            },
+            (ColliderType::Obstacle, ColliderType::Ship) => {
+                self.ship_hit = Some(b.1);
+            },
+            (ColliderType::Bullet, ColliderType::Obstacle) => {
+                self.collide_entity.push(a.1);
+                self.shots_blocked.push(a.1);
+            },
+            (ColliderType::Obstacle, ColliderType::Bullet) => {
+                self.collide_entity.push(b.1);
+                self.shots_blocked.push(b.1);
+            },
+            (ColliderType::Asteroid, ColliderType::Obstacle) => {
+                self.obstacle_bounces.push((a.1, b.1));
+            },
+            (ColliderType::Obstacle, ColliderType::Asteroid) => {
+                self.obstacle_bounces.push((b.1, a.1));
             },
             (_, _) => {
             }
         }
     }
-    fn interference_stopped(&mut self, _a: &ColliderEntity, _b: &ColliderEntity) {
+}
+
+type ColliderEntity = (ColliderType, Entity);
+
+// a collider shape placed in the world
+enum WorldShape {
+    Circle(Vector2<f32>, f32),
+    Polygon(Vec<Vector2<f32>>),
+}
+
+impl WorldShape {
+    fn new(collider: &Collider, transform: &Transform) -> Self {
+        match &collider.shape {
+            ColliderShape::Circle(radius) => WorldShape::Circle(
+                Vector2::new(transform.translation().x, transform.translation().y),
+                radius * transform.scale().x),
+            ColliderShape::Polygon(points) => WorldShape::Polygon(
+                geometry::to_world(transform, points)),
+        }
+    }
+
+    fn overlaps(&self, other: &WorldShape) -> bool {
+        match (self, other) {
+            (WorldShape::Circle(a, ra), WorldShape::Circle(b, rb)) => (a - b).norm() <= ra + rb,
+            (WorldShape::Circle(center, radius), WorldShape::Polygon(points)) |
+            (WorldShape::Polygon(points), WorldShape::Circle(center, radius)) =>
+                geometry::circle_intersects_polygon(*center, *radius, points),
+            (WorldShape::Polygon(a), WorldShape::Polygon(b)) => geometry::polygons_intersect(a, b),
+        }
     }
 }
 
@@ -312,41 +614,160 @@ impl CollisionEvent {
     }
 }
 
-#[derive(SystemDesc)]
-pub struct CollisionSystem;
+// things happening in the game that other parts (audio, effects) react on
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GameEvent {
+    ShotFired,
+    // a shot hit at least one asteroid, once per bullet or beam
+    ShotHit,
+    // a shot ended without a hit, it left the arena or hit an obstacle
+    ShotMissed,
+    // the ship moved this frame, distance in units and speed in units / s
+    ShipMoved { distance: f32, speed: f32 },
+    Thrusting,
+    AsteroidDestroyed { size: AsteroidSize, position: Vector2<f32> },
+    ShipDestroyed { position: Vector2<f32> },
+    MenuMoved,
+    MenuSelected,
+}
+
+// the broad and the narrow phase are spread over the thread pool of the
+// dispatcher, the hits are handled in the same order as on one thread
+pub struct CollisionSystem {
+    pub parallel: bool,
+}
+
+impl Default for CollisionSystem {
+    fn default() -> Self {
+        Self {
+            parallel: true,
+        }
+    }
+}
+
+impl CollisionSystem {
+    pub fn serial() -> Self {
+        Self {
+            parallel: false,
+        }
+    }
+}
 
 impl<'s> System<'s> for CollisionSystem {
     type SystemData = (
         Entities<'s>,
         ReadStorage<'s, Collider>,
-        ReadStorage<'s, Transform>,
-        Write<'s, EventChannel<TransEvent<GameData<'static, 'static>, StateEvent>>>,
+        WriteStorage<'s, Transform>,
+        WriteStorage<'s, Physical>,
+        ReadStorage<'s, Asteroid>,
+        ReadExpect<'s, AsteroidPhysicsConfig>,
         Write<'s, EventChannel<CollisionEvent>>,
+        Write<'s, EventChannel<GameEvent>>,
     );
     fn run(&mut self,
            (entities,
             colliders,
-            transforms,
-            mut trans_events,
-            mut collision_channel): Self::SystemData) {
+            mut transforms,
+            mut physicals,
+            asteroids,
+            asteroid_physics,
+            mut collision_channel,
+            mut game_events): Self::SystemData) {
 
         // collect collider
-        let mut broad_phase = DBVTBroadPhase::new(0f32);
-        let mut handler = CollisionHandler::new();
-        //let mut vec = vec![];
+        let mut handler = CollisionHandler::new(asteroid_physics.collisions);
+        let mut proxies = vec![];
+        let mut bounds = vec![];
+        let mut shapes = vec![];
         for (e, collider, transform) in (&entities, &colliders, &transforms).join()  {
             let pos = transform.translation();
-            let pos = Isometry2::new(Vector2::new(pos.x, pos.y), zero());
-            let vol = bounding_volume::bounding_sphere( &Ball::new(5.0), &pos );
-            broad_phase.create_proxy(vol, (collider.typ, e));
+            proxies.push((collider.typ, e));
+            bounds.push(Bounds {
+                center: Vector2::new(pos.x, pos.y),
+                radius: collider.shape.bounding_radius() * transform.scale().x,
+            });
+            shapes.push(WorldShape::new(collider, transform));
         }
 
-        broad_phase.update(&mut handler);
+        // bounding circles first, then the real shapes
+        let candidates = broad_phase::overlapping_pairs(
+            &bounds, |i, j| handler.allows(&proxies[i], &proxies[j]), self.parallel);
+        let overlapping = |&(i, j): &(usize, usize)| shapes[i].overlaps(&shapes[j]);
+        let hits: Vec<(usize, usize)> = if self.parallel {
+            candidates.into_par_iter().filter(&overlapping).collect()
+        } else {
+            candidates.into_iter().filter(&overlapping).collect()
+        };
+        for (i, j) in hits {
+            handler.hit(&proxies[i], &proxies[j]);
+        }
 
-        if handler.ship_hit {
-            let trans = Box::new(
-                move || Trans::Switch(Box::new(states::StateOver::new())));
-            trans_events.single_write(trans);
+        for (a, b) in handler.bounces.split_off(0) {
+            let body = |e| match (colliders.get(e), asteroids.get(e), transforms.get(e), physicals.get(e)) {
+                (Some(collider), Some(asteroid), Some(transform), Some(physical)) => Some(Body {
+                    position: Vector2::new(transform.translation().x, transform.translation().y),
+                    velocity: physical.velocity,
+                    angular_velocity: physical.rotation,
+                    mass: asteroid.size.mass(),
+                    radius: collider.shape.bounding_radius() * transform.scale().x,
+                }),
+                _ => None,
+            };
+            if let (Some(mut body_a), Some(mut body_b)) = (body(a), body(b)) {
+                physics::resolve_collision(&mut body_a, &mut body_b, &asteroid_physics);
+                for (e, resolved) in [(a, body_a), (b, body_b)].iter() {
+                    if let Some(transform) = transforms.get_mut(*e) {
+                        transform.set_translation_x(resolved.position.x);
+                        transform.set_translation_y(resolved.position.y);
+                    }
+                    if let Some(physical) = physicals.get_mut(*e) {
+                        // not clamped, that would break the momentum the bounce conserves
+                        physical.velocity = resolved.velocity;
+                        physical.rotation = resolved.angular_velocity;
+                    }
+                }
+            }
+        }
+
+        // obstacles do not move, the asteroid is reflected off the closest edge
+        for (asteroid, obstacle) in handler.obstacle_bounces.split_off(0) {
+            let polygon = match (colliders.get(obstacle), transforms.get(obstacle)) {
+                (Some(collider), Some(transform)) => match WorldShape::new(collider, transform) {
+                    WorldShape::Polygon(points) => points,
+                    WorldShape::Circle(..) => continue,
+                },
+                _ => continue,
+            };
+            if let (Some(transform), Some(physical)) = (transforms.get(asteroid), physicals.get_mut(asteroid)) {
+                let center = Vector2::new(transform.translation().x, transform.translation().y);
+                let normal = geometry::closest_edge_normal(&polygon, center);
+                physical.velocity = physics::reflect(physical.velocity, normal,
+                                                     asteroid_physics.restitution);
+            }
+        }
+
+        if let Some(ship) = handler.ship_hit {
+            if let Some(transform) = transforms.get(ship) {
+                game_events.single_write(GameEvent::ShipDestroyed {
+                    position: Vector2::new(transform.translation().x, transform.translation().y),
+                });
+            }
+        }
+
+        // one bullet touching several asteroids is still one hit
+        let mut shots_hit = handler.shots_hit.split_off(0);
+        shots_hit.sort();
+        shots_hit.dedup();
+        for _ in &shots_hit {
+            game_events.single_write(GameEvent::ShotHit);
+        }
+        let mut shots_blocked = handler.shots_blocked.split_off(0);
+        shots_blocked.sort();
+        shots_blocked.dedup();
+        for e in shots_blocked {
+            if shots_hit.binary_search(&e).is_err() {
+                game_events.single_write(GameEvent::ShotMissed);
+            }
         }
 
         for e in handler.collide_entity {
@@ -362,14 +783,12 @@ pub struct DeletionSystem {
 
 impl<'s> System<'s> for DeletionSystem {
     type SystemData = (
-        Entities<'s>,
         ReadStorage<'s, Collider>,
+        ReadStorage<'s, Asteroid>,
         ReadStorage<'s, Transform>,
         Read<'s, EventChannel<CollisionEvent>>,
-        ReadExpect<'s, ExplosionRes>,
-        WriteStorage<'s, UiText>,
-        WriteExpect<'s, ScoreRes>,
-        Read<'s, LazyUpdate>,
+        Write<'s, EntityPools>,
+        Write<'s, EventChannel<GameEvent>>,
     );
 
     fn setup(&mut self, world: &mut World) {
@@ -382,73 +801,252 @@ impl<'s> System<'s> for DeletionSystem {
     }
 
     fn run(&mut self,
-           (entities,
-            colliders,
+           (colliders,
+            asteroids,
             transforms,
             collision_channel,
-            explosionres,
-            mut uitext,
-            mut scoretexts,
-            lazy): Self::SystemData) {
-        for event in collision_channel.read(self.event_reader.as_mut().unwrap()) {
-            let e = event.entity;
+            mut pools,
+            mut game_events): Self::SystemData) {
+        // an asteroid hit by several shots in one frame is destroyed once
+        let mut hit: Vec<Entity> = collision_channel.read(self.event_reader.as_mut().unwrap())
+            .map(|event| event.entity)
+            .collect();
+        hit.sort();
+        hit.dedup();
+        for e in hit {
+            if let (Some(asteroid), Some(transform)) = (asteroids.get(e), transforms.get(e)) {
+                game_events.single_write(GameEvent::AsteroidDestroyed {
+                    size: asteroid.size,
+                    position: Vector2::new(transform.translation().x, transform.translation().y),
+                });
+            }
             if let Some(c) = colliders.get(e) {
                 if c.typ == ColliderType::Bullet {
                     // create explosion
                     if let Some(trans) = transforms.get(e) {
-                        let e = entities.create();
-                        lazy.insert(e, Explosion::new() );
-                        lazy.insert(e, trans.clone());
-                        lazy.insert(e, explosionres.sprite_render());
-                    }
-
-                    // change score
-                    scoretexts.score = scoretexts.score + 1;
-                    if let Some(text) = uitext.get_mut(scoretexts.text) {
-                        text.text = scoretexts.score.to_string()
+                        pools.spawn(Spawn::Explosion { transform: trans.clone() });
                     }
                 }
             }
 
             // delete the bullet and asteroid
-            if let Err(e) = entities.delete(e) {
-                error!("Failed to destroy collide entity: {}", e)
-            }
+            pools.release(e);
         }
     }
 }
 
-#[derive(SystemDesc)]
-pub struct ExplosionSystem;
+// keep the camera and the HUD in sync with the window and the arena size
+#[derive(Default)]
+pub struct ViewportSystem {
+    screen: (f32, f32),
+    // arena size of the level the view was fitted to
+    size: (f32, f32),
+    // letterbox bars, [left or top, right or bottom]
+    bars: Vec<Entity>,
+}
 
-impl<'s> System<'s> for ExplosionSystem {
+impl<'s> System<'s> for ViewportSystem {
     type SystemData = (
-        WriteStorage<'s, Explosion>,
-        WriteStorage<'s, SpriteRender>,
         Entities<'s>,
-        Read<'s, Time>,
+        ReadExpect<'s, ScreenDimensions>,
+        ReadExpect<'s, ArenaConfig>,
+        ReadExpect<'s, Level>,
+        WriteExpect<'s, Arena>,
+        Write<'s, Viewport>,
+        WriteStorage<'s, Camera>,
+        WriteStorage<'s, Transform>,
+        WriteStorage<'s, UiTransform>,
+        WriteStorage<'s, UiImage>,
     );
 
     fn run(&mut self,
-           (mut explosions,
-            mut spriterenders,
-            entities,
-            time): Self::SystemData) {
-        let delta = time.delta_seconds();
+           (entities,
+            dimensions,
+            config,
+            level,
+            mut arena,
+            mut viewport,
+            mut cameras,
+            mut transforms,
+            mut ui_transforms,
+            mut ui_images): Self::SystemData) {
+        let screen = (dimensions.width(), dimensions.height());
+        let size = (level.width, level.height);
+        if (screen == self.screen && size == self.size) || screen.0 <= 0.0 || screen.1 <= 0.0 {
+            return;
+        }
 
-        for (e, explosion, spriterender) in (&*entities, &mut explosions, &mut spriterenders).join() {
-            if explosion.time_to_update <= 0.0 {
-                if explosion.frame_count == Explosion::FRAME_LIMIT {
-                    if let Err(e) = entities.delete(e) {
-                        error!("Failed to destroy explosion: {}", e)
-                    }
-                } else {
-                    spriterender.sprite_number += 1;
-                    explosion.frame_count += 1;
+        let config = level.arena_config(&config);
+        let (new_arena, view, viewport_size) = fit_view(screen, &config);
+        *arena = new_arena;
+
+        let mut has_camera = false;
+        for (camera, transform) in (&mut cameras, &mut transforms).join() {
+            transform.set_translation_x(arena.width * 0.5);
+            transform.set_translation_y(arena.height * 0.5);
+            *camera = Camera::standard_2d(view.0, view.1);
+            has_camera = true;
+        }
+        // retry next frame until the camera is created
+        if !has_camera {
+            return;
+        }
+        self.screen = screen;
+        self.size = size;
+
+        // the HUD covers exactly the arena on the screen
+        let hud = *viewport.hud.get_or_insert_with(|| {
+            let e = entities.create();
+            if let Err(e) = ui_transforms.insert(e, UiTransform::new(
+                "hud".to_string(), Anchor::Middle, Anchor::Middle,
+                0., 0., 0., 0., 0.)) {
+                error!("Failed to create HUD root: {}", e);
+            }
+            e
+        });
+        if let Some(transform) = ui_transforms.get_mut(hud) {
+            transform.width = viewport_size.0;
+            transform.height = viewport_size.1;
+        }
+        viewport.width = viewport_size.0;
+        viewport.height = viewport_size.1;
+
+        if self.bars.is_empty() {
+            for i in 0..2 {
+                let e = entities.create();
+                let result = ui_transforms.insert(e, UiTransform::new(
+                    format!("letterbox_{}", i), Anchor::Middle, Anchor::Middle,
+                    0., 0., 0., 0., 0.))
+                    .and(ui_images.insert(e, UiImage::SolidColor([0., 0., 0., 1.])));
+                if let Err(e) = result {
+                    error!("Failed to create letterbox bar: {}", e);
                 }
-            } else {
-                explosion.time_to_update -= delta;
+                self.bars.push(e);
+            }
+        }
+
+        // the bars fill the screen space on both sides of the viewport
+        let margin = ((screen.0 - viewport_size.0) * 0.5, (screen.1 - viewport_size.1) * 0.5);
+        let horizontal = margin.0 > margin.1;
+        let show = config.view_mode == ViewMode::Letterbox;
+        for (i, e) in self.bars.iter().enumerate() {
+            if let Some(transform) = ui_transforms.get_mut(*e) {
+                let (anchor, width, height) = match (horizontal, i) {
+                    (true, 0) => (Anchor::MiddleLeft, margin.0, screen.1),
+                    (true, _) => (Anchor::MiddleRight, margin.0, screen.1),
+                    (false, 0) => (Anchor::TopMiddle, screen.0, margin.1),
+                    (false, _) => (Anchor::BottomMiddle, screen.0, margin.1),
+                };
+                transform.anchor = anchor;
+                transform.pivot = anchor;
+                transform.width = if show { width } else { 0.0 };
+                transform.height = if show { height } else { 0.0 };
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use amethyst::ecs::{RunNow, WorldExt};
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    // bouncing asteroids, bullets and a ship crowded into a small arena,
+    // the same world for the same seed
+    fn collision_world(seed: u64) -> World {
+        let mut world = World::new();
+        world.register::<Transform>();
+        world.register::<Physical>();
+        world.register::<Asteroid>();
+        world.register::<Collider>();
+        world.insert(AsteroidPhysicsConfig { collisions: true, ..Default::default() });
+        world.insert(EventChannel::<CollisionEvent>::new());
+        world.insert(EventChannel::<GameEvent>::new());
+
+        let mut rng = StdRng::seed_from_u64(seed);
+        for i in 0..150 {
+            // the first one on top of the ship
+            let position = if i == 0 {
+                Vector2::new(150.0, 150.0)
+            } else {
+                Vector2::new(rng.gen_range(0.0, 300.0), rng.gen_range(0.0, 300.0))
+            };
+            let velocity = Vector2::new(rng.gen_range(-50.0, 50.0), rng.gen_range(-50.0, 50.0));
+            let (asteroid, transform, physical, collider, _, _) =
+                asteroid_components(None, AsteroidSize::Medium, position, velocity, 0.0, i);
+            world.create_entity()
+                .with(asteroid)
+                .with(transform)
+                .with(physical)
+                .with(collider)
+                .build();
+        }
+        for _ in 0..150 {
+            let mut transform = Transform::default();
+            transform.set_translation_xyz(rng.gen_range(0.0, 300.0), rng.gen_range(0.0, 300.0), 0.0);
+            world.create_entity()
+                .with(transform)
+                .with(Collider { typ: ColliderType::Bullet, shape: ColliderShape::Circle(2.0) })
+                .build();
+        }
+        let mut transform = Transform::default();
+        transform.set_translation_xyz(150.0, 150.0, 0.0);
+        world.create_entity()
+            .with(transform)
+            .with(Collider { typ: ColliderType::Ship, shape: ColliderShape::Circle(8.0) })
+            .build();
+        world
+    }
+
+    type Outcome = (Vec<u32>, Vec<GameEvent>, Vec<(f32, f32, f32, f32)>);
+
+    // the destroyed entities, the game events and where every asteroid
+    // ends up with which velocity
+    fn collide(mut system: CollisionSystem, seed: u64) -> Outcome {
+        let world = collision_world(seed);
+        let mut collision_reader = world.fetch_mut::<EventChannel<CollisionEvent>>().register_reader();
+        let mut game_reader = world.fetch_mut::<EventChannel<GameEvent>>().register_reader();
+        system.run_now(&world);
+
+        let hits = world.fetch::<EventChannel<CollisionEvent>>()
+            .read(&mut collision_reader)
+            .map(|event| event.entity.id())
+            .collect();
+        let events = world.fetch::<EventChannel<GameEvent>>()
+            .read(&mut game_reader)
+            .cloned()
+            .collect();
+        let transforms = world.read_storage::<Transform>();
+        let physicals = world.read_storage::<Physical>();
+        let bodies = (&transforms, &physicals).join()
+            .map(|(transform, physical)| (transform.translation().x, transform.translation().y,
+                                          physical.velocity.x, physical.velocity.y))
+            .collect();
+        (hits, events, bodies)
+    }
+
+    #[test]
+    fn parallel_and_serial_agree() {
+        for seed in 0..4 {
+            let parallel = collide(CollisionSystem::default(), seed);
+            let serial = collide(CollisionSystem::serial(), seed);
+            // the world is crowded enough to exercise every kind of hit
+            assert!(!serial.0.is_empty());
+            assert!(!serial.1.is_empty());
+            assert_eq!(parallel, serial, "seed {}", seed);
+        }
+    }
+
+    #[test]
+    fn bounces_move_the_asteroids() {
+        let before = collision_world(1);
+        let transforms = before.read_storage::<Transform>();
+        let physicals = before.read_storage::<Physical>();
+        let start: Vec<(f32, f32, f32, f32)> = (&transforms, &physicals).join()
+            .map(|(transform, physical)| (transform.translation().x, transform.translation().y,
+                                          physical.velocity.x, physical.velocity.y))
+            .collect();
+        assert_ne!(collide(CollisionSystem::serial(), 1).2, start);
+    }
+}
@@ -1,22 +1,29 @@
+use std::{
+    collections::HashMap,
+    fs,
+};
+
 use amethyst::{
-    assets::{AssetStorage, Loader, Handle},
+    assets::{AssetStorage, Loader, Handle, ProgressCounter},
     prelude::*,
     renderer::{ImageFormat, Texture, SpriteSheetFormat, SpriteRender, SpriteSheet},
+    utils::application_root_dir,
 };
+use log::{error};
 
 pub struct SpriteStore {
     handle: Handle<SpriteSheet>
 }
 
 impl SpriteStore {
-    pub fn from_path(world: &World, name: &str) -> SpriteStore {
+    pub fn from_path(world: &World, name: &str, progress: &mut ProgressCounter) -> SpriteStore {
         let texture_handle = {
             let loader = world.read_resource::<Loader>();
             let texture_storage = world.read_resource::<AssetStorage<Texture>>();
             loader.load(
                 &format!("texture/{}.png", name),
                 ImageFormat::default(),
-                (),
+                &mut *progress,
                 &texture_storage,
             )
         };
@@ -27,7 +34,7 @@ impl SpriteStore {
             loader.load(
                 &format!("texture/{}.ron", name),
                 SpriteSheetFormat(texture_handle),
-                (),
+                &mut *progress,
                 &sprite_sheet_store,
             )
         };
@@ -44,3 +51,75 @@ impl SpriteStore {
     }
 }
 
+
+// sprite names every part of the game relies on, checked at load
+pub const REQUIRED_SPRITES: [&str; 5] = [
+    "ship",
+    "bullet",
+    "asteroid.0",
+    "explosion.0",
+    "particle",
+];
+
+// all sprites of the game, packed in texture/atlas.png by the pack_atlas
+// tool and looked up by name, e.g. "asteroid.2"
+pub struct SpriteRegistry {
+    store: SpriteStore,
+    names: HashMap<String, usize>,
+}
+
+impl SpriteRegistry {
+    // the registry is inserted even when the names are broken, so that the
+    // error can be reported instead of panicking on a missing resource
+    pub fn initialize(world: &mut World, progress: &mut ProgressCounter) -> Result<(), String> {
+        let store = SpriteStore::from_path(world, "atlas", progress);
+        let (names, result) = match Self::load_names() {
+            Ok(names) => (names, Ok(())),
+            Err(e) => (HashMap::new(), Err(e)),
+        };
+
+        let registry = SpriteRegistry { store, names };
+        let missing = registry.missing(&REQUIRED_SPRITES);
+        world.insert(registry);
+
+        result?;
+        if !missing.is_empty() {
+            return Err(format!("sprites missing from the atlas: {}", missing.join(", ")));
+        }
+        Ok(())
+    }
+
+    fn load_names() -> Result<HashMap<String, usize>, String> {
+        let path = application_root_dir()
+            .map_err(|e| e.to_string())?
+            .join("assets")
+            .join("texture")
+            .join("atlas_names.ron");
+        let content = fs::read_to_string(&path)
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+        ron::de::from_str(&content)
+            .map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    // the names in the list that are not in the atlas
+    pub fn missing<'a>(&self, names: &[&'a str]) -> Vec<&'a str> {
+        names.iter().filter(|n| !self.names.contains_key(**n)).cloned().collect()
+    }
+
+    pub fn index(&self, name: &str) -> Option<usize> {
+        self.names.get(name).cloned()
+    }
+
+    // number of sprites named <prefix>.0, <prefix>.1, ...
+    pub fn variant_count(&self, prefix: &str) -> usize {
+        (0..).take_while(|i| self.names.contains_key(&format!("{}.{}", prefix, i))).count()
+    }
+
+    pub fn sprite_render(&self, name: &str) -> SpriteRender {
+        let index = self.index(name).unwrap_or_else(|| {
+            error!("Unknown sprite {}", name);
+            0
+        });
+        self.store.sprite_renderer(index)
+    }
+}
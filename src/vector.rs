@@ -0,0 +1,160 @@
+use amethyst::{
+    core::{
+        math::{Point3, Vector2},
+        transform::components::Transform,
+        timing::Time,
+    },
+    derive::{SystemDesc},
+    ecs::{Join,
+          ReadStorage,
+          System, SystemData,
+          Read, WriteExpect,
+          World},
+    ecs::prelude::{Component, DenseVecStorage},
+    renderer::{debug_drawing::DebugLines, palette::Srgba},
+};
+
+use crate::geometry;
+use crate::particles::{Emitter, ParticlePool};
+use crate::starfield::{Starfield};
+
+const COLOR_LINE: [f32; 4] = [0.9, 0.95, 1.0, 1.0];
+const COLOR_FLAME: [f32; 4] = [1.0, 0.6, 0.2, 1.0];
+
+// distance of the dim copies drawn beside every line for the glow
+const GLOW_OFFSET: f32 = 0.6;
+const GLOW_ALPHA: f32 = 0.3;
+
+// line outline of an entity in its local space, drawn in vector mode
+pub struct Outline {
+    pub points: Vec<Vector2<f32>>,
+    // connect the last point back to the first
+    pub closed: bool,
+    pub color: [f32; 4],
+}
+
+impl Outline {
+    pub fn ship() -> Self {
+        Self {
+            points: vec![
+                Vector2::new(0.0, 8.0),
+                Vector2::new(-6.0, -7.0),
+                Vector2::new(0.0, -4.0),
+                Vector2::new(6.0, -7.0),
+            ],
+            closed: true,
+            color: COLOR_LINE,
+        }
+    }
+
+    pub fn bullet() -> Self {
+        Self {
+            points: vec![Vector2::new(0.0, -2.0), Vector2::new(0.0, 2.0)],
+            closed: false,
+            color: COLOR_LINE,
+        }
+    }
+
+    pub fn polygon(points: Vec<Vector2<f32>>) -> Self {
+        Self {
+            points,
+            closed: true,
+            color: COLOR_LINE,
+        }
+    }
+}
+
+impl Component for Outline {
+    type Storage = DenseVecStorage<Self>;
+}
+
+fn srgba([r, g, b, a]: [f32; 4]) -> Srgba {
+    Srgba::new(r, g, b, a)
+}
+
+fn draw_glow_line(lines: &mut DebugLines, a: Vector2<f32>, b: Vector2<f32>, color: [f32; 4]) {
+    let point = |p: Vector2<f32>| Point3::new(p.x, p.y, 0.0);
+    lines.draw_line(point(a), point(b), srgba(color));
+
+    let direction = b - a;
+    if direction.norm() == 0.0 {
+        return;
+    }
+    let normal = Vector2::new(-direction.y, direction.x).normalize() * GLOW_OFFSET;
+    let glow = [color[0], color[1], color[2], color[3] * GLOW_ALPHA];
+    for offset in [normal, -normal].iter() {
+        lines.draw_line(point(a + offset), point(b + offset), srgba(glow));
+    }
+}
+
+pub fn draw_polyline(lines: &mut DebugLines, points: &[Vector2<f32>], closed: bool, color: [f32; 4]) {
+    for pair in points.windows(2) {
+        draw_glow_line(lines, pair[0], pair[1], color);
+    }
+    if closed && points.len() > 2 {
+        draw_glow_line(lines, points[points.len() - 1], points[0], color);
+    }
+}
+
+// draw outlines, thrust flames and particles as lines, used instead of
+// the sprite renderer when the game runs in vector mode
+#[derive(SystemDesc)]
+pub struct VectorRenderSystem;
+
+impl<'s> System<'s> for VectorRenderSystem {
+    type SystemData = (
+        ReadStorage<'s, Outline>,
+        ReadStorage<'s, Transform>,
+        ReadStorage<'s, Emitter>,
+        Read<'s, ParticlePool>,
+        Read<'s, Starfield>,
+        Read<'s, Time>,
+        WriteExpect<'s, DebugLines>,
+    );
+
+    fn run(&mut self,
+           (outlines,
+            transforms,
+            emitters,
+            pool,
+            starfield,
+            time,
+            mut lines): Self::SystemData) {
+        // stars are short dashes, without glow to keep them cheap
+        let now = time.absolute_time_seconds() as f32;
+        for star in &starfield.stars {
+            let position = starfield.position(star);
+            let color = [1.0, 1.0, 1.0, Starfield::brightness(star, now)];
+            lines.draw_line(Point3::new(position.x, position.y, -0.5),
+                            Point3::new(position.x + star.size * 2.0, position.y, -0.5),
+                            srgba(color));
+        }
+
+        for (outline, transform) in (&outlines, &transforms).join() {
+            let points = geometry::to_world(transform, &outline.points);
+            draw_polyline(&mut lines, &points, outline.closed, outline.color);
+        }
+
+        // a flickering flame behind every thrusting ship
+        let flicker = 6.0 + 3.0 * (now * 40.0).sin().abs();
+        for (_, emitter, transform) in (&outlines, &emitters, &transforms).join() {
+            if emitter.active {
+                let flame = [
+                    Vector2::new(-3.0, -5.0),
+                    Vector2::new(0.0, -5.0 - flicker),
+                    Vector2::new(3.0, -5.0),
+                ];
+                let points = geometry::to_world(transform, &flame);
+                draw_polyline(&mut lines, &points, false, COLOR_FLAME);
+            }
+        }
+
+        // particles become short streaks along their motion
+        for particle in pool.particles() {
+            let tail = particle.position - particle.velocity * 0.03;
+            lines.draw_line(Point3::new(particle.position.x, particle.position.y, 0.0),
+                            Point3::new(tail.x, tail.y, 0.0),
+                            srgba(particle.color()));
+        }
+    }
+}
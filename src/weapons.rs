@@ -0,0 +1,656 @@
+use amethyst::{
+    core::{
+        math::{Vector2},
+        transform::components::Transform,
+        timing::Time,
+    },
+    derive::{SystemDesc},
+    ecs::{Join,
+          ReadStorage,
+          System, SystemData,
+          Read, ReadExpect, Write, WriteExpect},
+    ecs::prelude::{Component, DenseVecStorage},
+    renderer::debug_drawing::DebugLines,
+    shrev::EventChannel,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::components::{Physical, ColliderType};
+use crate::physics::{self, ShipPhysicsConfig};
+use crate::pool::{EntityPools, Spawn};
+use crate::resources::{Arena};
+use crate::spatial::{SpatialGrid};
+use crate::system::{CollisionEvent, GameEvent};
+use crate::vector;
+
+// distance from the center of a ship to its nose
+const NOSE: f32 = 8.0;
+
+// where and how a ship fires, the weapon may push the ship back
+pub struct Muzzle<'a> {
+    pub transform: &'a Transform,
+    pub position: Vector2<f32>,
+    // normalized
+    pub heading: Vector2<f32>,
+    pub velocity: &'a mut Vector2<f32>,
+}
+
+impl<'a> Muzzle<'a> {
+    pub fn nose(&self) -> Vector2<f32> {
+        self.position + self.heading * NOSE
+    }
+}
+
+// what a weapon may touch when it fires
+pub struct WeaponContext<'a> {
+    pub arena: Arena,
+    pub grid: &'a SpatialGrid,
+    pub ship_config: &'a ShipPhysicsConfig,
+    pub pools: &'a mut EntityPools,
+    pub collisions: &'a mut EventChannel<CollisionEvent>,
+    pub game_events: &'a mut EventChannel<GameEvent>,
+    pub beams: &'a mut Beams,
+}
+
+// one weapon of a ship; new weapons implement this and are added to the
+// Weapons of a ship, ShipControlSystem fires the selected one
+pub trait Weapon: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    // every frame, also while another weapon is selected
+    fn update(&mut self, _delta: f32) {}
+
+    // called every frame the fire button is held
+    fn fire(&mut self, muzzle: &mut Muzzle, context: &mut WeaponContext);
+
+    // share of the energy left, [0, 1], for weapons running on energy
+    fn charge(&self) -> Option<f32> {
+        None
+    }
+}
+
+// the weapons of a ship, switched through with the "switch_weapon" action
+pub struct Weapons {
+    pub weapons: Vec<Box<dyn Weapon>>,
+    pub selected: usize,
+    // the switch is held down, it switches once per press
+    switch_held: bool,
+}
+
+impl Weapons {
+    pub fn new(weapons: Vec<Box<dyn Weapon>>) -> Self {
+        Self {
+            weapons,
+            selected: 0,
+            switch_held: false,
+        }
+    }
+
+    pub fn selected(&self) -> Option<&dyn Weapon> {
+        self.weapons.get(self.selected).map(|weapon| weapon.as_ref())
+    }
+
+    pub fn selected_mut(&mut self) -> Option<&mut Box<dyn Weapon>> {
+        self.weapons.get_mut(self.selected)
+    }
+
+    // selects the next weapon when the switch goes down
+    pub fn switch(&mut self, down: bool) {
+        if down && !self.switch_held && !self.weapons.is_empty() {
+            self.selected = (self.selected + 1) % self.weapons.len();
+        }
+        self.switch_held = down;
+    }
+
+    pub fn update(&mut self, delta: f32) {
+        for weapon in &mut self.weapons {
+            weapon.update(delta);
+        }
+    }
+}
+
+impl Component for Weapons {
+    type Storage = DenseVecStorage<Self>;
+}
+
+// the bullet gun every ship starts with
+pub struct Cannon {
+    reload_timer: f32,
+    time_to_reload: f32,
+}
+
+impl Cannon {
+    pub fn new() -> Self {
+        Self {
+            reload_timer: 0.0,
+            time_to_reload: 0.5,
+        }
+    }
+}
+
+impl Default for Cannon {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Weapon for Cannon {
+    fn name(&self) -> &'static str {
+        "Cannon"
+    }
+
+    fn update(&mut self, delta: f32) {
+        self.reload_timer = (self.reload_timer - delta).max(0.0);
+    }
+
+    fn fire(&mut self, muzzle: &mut Muzzle, context: &mut WeaponContext) {
+        if self.reload_timer > 0.0 {
+            return;
+        }
+        self.reload_timer = self.time_to_reload;
+
+        let velocity = *muzzle.velocity + muzzle.heading * 150.0;
+        context.pools.spawn(Spawn::Bullet {
+            transform: muzzle.transform.clone(),
+            physical: Physical {
+                velocity,
+                max_velocity: 200.0,
+                rotation: 0.0,
+            },
+        });
+        context.game_events.single_write(GameEvent::ShotFired);
+        *muzzle.velocity = physics::apply_recoil(*muzzle.velocity, muzzle.heading, context.ship_config);
+    }
+}
+
+// the laser as configured in config/laser.ron
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LaserConfig {
+    // length of the beam
+    pub range: f32,
+    pub max_energy: f32,
+    // energy gained per second
+    pub regeneration: f32,
+    // energy used by a shot
+    pub cost: f32,
+    // seconds between two shots
+    pub cooldown: f32,
+    // the beam goes on across wrapping edges, false stops it at the edge
+    pub wrap: bool,
+}
+
+impl Default for LaserConfig {
+    fn default() -> Self {
+        Self {
+            range: 200.0,
+            max_energy: 100.0,
+            regeneration: 20.0,
+            cost: 25.0,
+            cooldown: 0.25,
+            wrap: true,
+        }
+    }
+}
+
+// hits the first asteroid in front of the ship at once, unless an
+// obstacle is in the way
+pub struct Laser {
+    config: LaserConfig,
+    energy: f32,
+    cooldown_timer: f32,
+}
+
+impl Laser {
+    pub fn new(config: &LaserConfig) -> Self {
+        Self {
+            config: config.clone(),
+            energy: config.max_energy,
+            cooldown_timer: 0.0,
+        }
+    }
+}
+
+impl Weapon for Laser {
+    fn name(&self) -> &'static str {
+        "Laser"
+    }
+
+    fn update(&mut self, delta: f32) {
+        self.energy = (self.energy + self.config.regeneration * delta).min(self.config.max_energy);
+        self.cooldown_timer = (self.cooldown_timer - delta).max(0.0);
+    }
+
+    fn fire(&mut self, muzzle: &mut Muzzle, context: &mut WeaponContext) {
+        if self.cooldown_timer > 0.0 || self.energy < self.config.cost {
+            return;
+        }
+
+        // the beam stops at the edges it may not cross
+        let origin = muzzle.nose();
+        let (wrap_x, wrap_y) = context.grid.wraps();
+        let crosses = (wrap_x && self.config.wrap, wrap_y && self.config.wrap);
+        let (to_x, to_y) = edge_distances(origin, muzzle.heading, context.arena.width, context.arena.height);
+        let mut range = self.config.range;
+        if !crosses.0 {
+            range = range.min(to_x);
+        }
+        if !crosses.1 {
+            range = range.min(to_y);
+        }
+        // the nose is past an edge the beam may not cross, nothing to shoot at
+        if range <= 0.0 {
+            return;
+        }
+        self.energy -= self.config.cost;
+        self.cooldown_timer = self.config.cooldown;
+
+        // obstacles are solid, the beam ends on them without harm
+        let hit = context.grid.ray_cast(origin, muzzle.heading, range, |entry| {
+            entry.kind == ColliderType::Asteroid || entry.kind == ColliderType::Obstacle
+        });
+        context.game_events.single_write(GameEvent::ShotFired);
+        let length = match hit {
+            // like a bullet hitting an obstacle
+            Some(hit) if hit.kind == ColliderType::Obstacle => {
+                context.game_events.single_write(GameEvent::ShotMissed);
+                hit.distance
+            },
+            Some(hit) => {
+                context.game_events.single_write(GameEvent::ShotHit);
+                // DeletionSystem destroys and scores it like a bullet hit
+                context.collisions.single_write(CollisionEvent::new(hit.entity));
+                let point = wrap_point(hit.point, context.arena.width, context.arena.height);
+                let mut transform = Transform::default();
+                transform.set_translation_xyz(point.x, point.y, 0.0);
+                context.pools.spawn(Spawn::Explosion { transform });
+                hit.distance
+            },
+            None => {
+                context.game_events.single_write(GameEvent::ShotMissed);
+                range
+            },
+        };
+        context.beams.beams.push(Beam {
+            origin,
+            direction: muzzle.heading,
+            length,
+            age: 0.0,
+        });
+    }
+
+    fn charge(&self) -> Option<f32> {
+        Some(self.energy / self.config.max_energy.max(std::f32::EPSILON))
+    }
+}
+
+// distance along a normalized direction to the x and the y edge of the
+// arena it leaves through
+pub fn edge_distances(point: Vector2<f32>, direction: Vector2<f32>,
+                      width: f32, height: f32) -> (f32, f32) {
+    let distance = |p: f32, d: f32, size: f32| {
+        if d > 0.0 {
+            ((size - p) / d).max(0.0)
+        } else if d < 0.0 {
+            (-p / d).max(0.0)
+        } else {
+            std::f32::INFINITY
+        }
+    };
+    (distance(point.x, direction.x, width), distance(point.y, direction.y, height))
+}
+
+fn wrap_point(point: Vector2<f32>, width: f32, height: f32) -> Vector2<f32> {
+    Vector2::new(point.x.rem_euclid(width), point.y.rem_euclid(height))
+}
+
+// the parts of a beam inside the arena, a new part starts where it
+// crosses an edge and comes in from the other side
+pub fn beam_segments(origin: Vector2<f32>, direction: Vector2<f32>, length: f32,
+                     width: f32, height: f32) -> Vec<(Vector2<f32>, Vector2<f32>)> {
+    // the beam is shorter than the arena, a few crossings at most
+    const MAX_SEGMENTS: usize = 8;
+    let mut segments = vec![];
+    let mut start = origin;
+    let mut left = length;
+    while left > 0.0 && segments.len() < MAX_SEGMENTS {
+        let (to_x, to_y) = edge_distances(start, direction, width, height);
+        let to_edge = to_x.min(to_y);
+        if to_edge >= left {
+            segments.push((start, start + direction * left));
+            break;
+        }
+        let end = start + direction * to_edge;
+        segments.push((start, end));
+        left -= to_edge;
+        start = end;
+        if to_x <= to_y {
+            start.x = if direction.x > 0.0 { 0.0 } else { width };
+        }
+        if to_y <= to_x {
+            start.y = if direction.y > 0.0 { 0.0 } else { height };
+        }
+    }
+    segments
+}
+
+// a laser shot, drawn for a moment after it was fired
+#[derive(Clone, Copy, Debug)]
+pub struct Beam {
+    pub origin: Vector2<f32>,
+    pub direction: Vector2<f32>,
+    pub length: f32,
+    // seconds since it was fired
+    pub age: f32,
+}
+
+#[derive(Default)]
+pub struct Beams {
+    pub beams: Vec<Beam>,
+}
+
+const BEAM_TIME: f32 = 0.15;
+const COLOR_BEAM: [f32; 4] = [1.0, 0.3, 0.3, 1.0];
+const COLOR_ENERGY: [f32; 4] = [0.3, 0.8, 1.0, 0.8];
+const ENERGY_BAR_WIDTH: f32 = 16.0;
+
+// fade the laser beams and show the energy of the selected weapon below
+// each ship
+#[derive(SystemDesc)]
+pub struct BeamRenderSystem;
+
+impl<'s> System<'s> for BeamRenderSystem {
+    type SystemData = (
+        ReadStorage<'s, Weapons>,
+        ReadStorage<'s, Transform>,
+        Write<'s, Beams>,
+        ReadExpect<'s, Arena>,
+        Read<'s, Time>,
+        WriteExpect<'s, DebugLines>,
+    );
+
+    fn run(&mut self,
+           (weapons,
+            transforms,
+            mut beams,
+            arena,
+            time,
+            mut lines): Self::SystemData) {
+        let delta = time.delta_seconds();
+        beams.beams.retain(|beam| beam.age < BEAM_TIME);
+        for beam in &mut beams.beams {
+            let [r, g, b, a] = COLOR_BEAM;
+            let color = [r, g, b, a * (1.0 - beam.age / BEAM_TIME)];
+            for (start, end) in beam_segments(beam.origin, beam.direction, beam.length,
+                                              arena.width, arena.height) {
+                vector::draw_polyline(&mut lines, &[start, end], false, color);
+            }
+            beam.age += delta;
+        }
+
+        for (weapons, transform) in (&weapons, &transforms).join() {
+            let charge = match weapons.selected().and_then(|weapon| weapon.charge()) {
+                Some(charge) => charge,
+                None => continue,
+            };
+            let below = Vector2::new(transform.translation().x - ENERGY_BAR_WIDTH * 0.5,
+                                     transform.translation().y - 14.0);
+            let full = below + Vector2::new(ENERGY_BAR_WIDTH * charge, 0.0);
+            vector::draw_polyline(&mut lines, &[below, full], false, COLOR_ENERGY);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use amethyst::ecs::{World, WorldExt};
+    use amethyst::ecs::prelude::{Builder, Entity};
+    use amethyst::shrev::ReaderId;
+
+    fn close(a: Vector2<f32>, b: Vector2<f32>) -> bool {
+        (a - b).norm() < 1e-3
+    }
+
+    // everything a weapon touches when it fires, in a 300 x 300 arena
+    struct Range {
+        grid: SpatialGrid,
+        ship_config: ShipPhysicsConfig,
+        pools: EntityPools,
+        collisions: EventChannel<CollisionEvent>,
+        collision_reader: ReaderId<CollisionEvent>,
+        game_events: EventChannel<GameEvent>,
+        game_reader: ReaderId<GameEvent>,
+        beams: Beams,
+        velocity: Vector2<f32>,
+    }
+
+    impl Range {
+        fn new(wrap: bool) -> Self {
+            let mut collisions = EventChannel::new();
+            let collision_reader = collisions.register_reader();
+            let mut game_events = EventChannel::new();
+            let game_reader = game_events.register_reader();
+            Self {
+                grid: SpatialGrid::new(300.0, 300.0, (wrap, wrap)),
+                ship_config: ShipPhysicsConfig::default(),
+                pools: EntityPools::default(),
+                collisions,
+                collision_reader,
+                game_events,
+                game_reader,
+                beams: Beams::default(),
+                velocity: Vector2::new(0.0, 0.0),
+            }
+        }
+
+        // fire from a ship at `position`, returns the game events
+        fn fire(&mut self, weapon: &mut dyn Weapon, position: Vector2<f32>,
+                heading: Vector2<f32>) -> Vec<GameEvent> {
+            let transform = Transform::default();
+            let mut muzzle = Muzzle {
+                transform: &transform,
+                position,
+                heading,
+                velocity: &mut self.velocity,
+            };
+            let mut context = WeaponContext {
+                arena: Arena { width: 300.0, height: 300.0 },
+                grid: &self.grid,
+                ship_config: &self.ship_config,
+                pools: &mut self.pools,
+                collisions: &mut self.collisions,
+                game_events: &mut self.game_events,
+                beams: &mut self.beams,
+            };
+            weapon.fire(&mut muzzle, &mut context);
+            self.game_events.read(&mut self.game_reader).cloned().collect()
+        }
+
+        fn collisions(&mut self) -> Vec<Entity> {
+            self.collisions.read(&mut self.collision_reader).map(|event| event.entity).collect()
+        }
+    }
+
+    fn entities(count: usize) -> Vec<Entity> {
+        let mut world = World::new();
+        (0..count).map(|_| world.create_entity().build()).collect()
+    }
+
+    fn laser(regeneration: f32) -> Laser {
+        Laser::new(&LaserConfig { regeneration, ..Default::default() })
+    }
+
+    fn center() -> Vector2<f32> {
+        Vector2::new(100.0, 100.0)
+    }
+
+    fn right() -> Vector2<f32> {
+        Vector2::new(1.0, 0.0)
+    }
+
+    #[test]
+    fn switch_once_per_press_and_wrap_around() {
+        let mut weapons = Weapons::new(vec![Box::new(Cannon::default()), Box::new(laser(20.0))]);
+        assert_eq!(weapons.selected().map(|weapon| weapon.name()), Some("Cannon"));
+        weapons.switch(true);
+        assert_eq!(weapons.selected, 1);
+        // held down
+        weapons.switch(true);
+        assert_eq!(weapons.selected, 1);
+        weapons.switch(false);
+        weapons.switch(true);
+        assert_eq!(weapons.selected, 0);
+        assert_eq!(weapons.selected_mut().map(|weapon| weapon.name()), Some("Cannon"));
+    }
+
+    #[test]
+    fn switch_without_weapons() {
+        let mut weapons = Weapons::new(vec![]);
+        weapons.switch(true);
+        assert!(weapons.selected().is_none());
+    }
+
+    #[test]
+    fn weapons_update_while_not_selected() {
+        let mut weapons = Weapons::new(vec![Box::new(Cannon::default()), Box::new(laser(20.0))]);
+        let mut range = Range::new(true);
+        range.fire(weapons.weapons[1].as_mut(), center(), right());
+        assert_eq!(weapons.weapons[1].charge(), Some(0.75));
+        weapons.update(0.5);
+        assert_eq!(weapons.weapons[1].charge(), Some(0.85));
+        assert_eq!(weapons.weapons[0].charge(), None);
+    }
+
+    #[test]
+    fn cannon_reloads() {
+        let mut cannon = Cannon::default();
+        let mut range = Range::new(true);
+        assert_eq!(range.fire(&mut cannon, center(), right()), vec![GameEvent::ShotFired]);
+        // the recoil pushes the ship back
+        assert!(range.velocity.x < 0.0);
+        assert!(range.fire(&mut cannon, center(), right()).is_empty());
+        cannon.update(0.25);
+        assert!(range.fire(&mut cannon, center(), right()).is_empty());
+        cannon.update(0.25);
+        assert_eq!(range.fire(&mut cannon, center(), right()), vec![GameEvent::ShotFired]);
+    }
+
+    #[test]
+    fn laser_uses_energy_and_cools_down() {
+        let mut laser = laser(0.0);
+        let mut range = Range::new(true);
+        assert_eq!(laser.charge(), Some(1.0));
+        for shot in 1..=4 {
+            assert_eq!(range.fire(&mut laser, center(), right()),
+                       vec![GameEvent::ShotFired, GameEvent::ShotMissed]);
+            assert_eq!(laser.charge(), Some(1.0 - 0.25 * shot as f32));
+            // not again before the cooldown is over
+            assert!(range.fire(&mut laser, center(), right()).is_empty());
+            laser.update(0.25);
+        }
+        // out of energy
+        assert!(range.fire(&mut laser, center(), right()).is_empty());
+        assert_eq!(range.beams.beams.len(), 4);
+    }
+
+    #[test]
+    fn laser_regenerates_up_to_the_maximum() {
+        let mut laser = laser(20.0);
+        let mut range = Range::new(true);
+        range.fire(&mut laser, center(), right());
+        laser.update(0.5);
+        assert_eq!(laser.charge(), Some(0.85));
+        laser.update(10.0);
+        assert_eq!(laser.charge(), Some(1.0));
+    }
+
+    #[test]
+    fn laser_hits_the_first_asteroid() {
+        let e = entities(2);
+        let mut range = Range::new(true);
+        range.grid.insert(e[0], ColliderType::Asteroid, Vector2::new(200.0, 100.0), 5.0);
+        range.grid.insert(e[1], ColliderType::Asteroid, Vector2::new(150.0, 100.0), 5.0);
+        let mut laser = laser(20.0);
+        assert_eq!(range.fire(&mut laser, center(), right()),
+                   vec![GameEvent::ShotFired, GameEvent::ShotHit]);
+        assert_eq!(range.collisions(), vec![e[1]]);
+        // from the nose to the edge of the asteroid
+        let beam = range.beams.beams[0];
+        assert_eq!(beam.origin, Vector2::new(100.0 + NOSE, 100.0));
+        assert!((beam.length - (45.0 - NOSE)).abs() < 1e-3);
+    }
+
+    #[test]
+    fn laser_stops_at_an_obstacle() {
+        let e = entities(2);
+        let mut range = Range::new(true);
+        range.grid.insert(e[0], ColliderType::Obstacle, Vector2::new(130.0, 100.0), 5.0);
+        range.grid.insert(e[1], ColliderType::Asteroid, Vector2::new(150.0, 100.0), 5.0);
+        let mut laser = laser(20.0);
+        assert_eq!(range.fire(&mut laser, center(), right()),
+                   vec![GameEvent::ShotFired, GameEvent::ShotMissed]);
+        assert!(range.collisions().is_empty());
+        assert!((range.beams.beams[0].length - (25.0 - NOSE)).abs() < 1e-3);
+    }
+
+    #[test]
+    fn laser_ends_at_an_edge_it_may_not_cross() {
+        let mut range = Range::new(false);
+        let mut laser = laser(20.0);
+        range.fire(&mut laser, Vector2::new(250.0, 100.0), right());
+        assert!((range.beams.beams[0].length - (50.0 - NOSE)).abs() < 1e-3);
+
+        // the nose is already past the edge
+        let mut laser = Laser::new(&LaserConfig::default());
+        assert!(range.fire(&mut laser, Vector2::new(295.0, 100.0), right()).is_empty());
+        assert_eq!(laser.charge(), Some(1.0));
+        assert_eq!(range.beams.beams.len(), 1);
+    }
+
+    #[test]
+    fn edge_distances_along_each_axis() {
+        assert_eq!(edge_distances(Vector2::new(100.0, 50.0), right(), 300.0, 200.0),
+                   (200.0, std::f32::INFINITY));
+        assert_eq!(edge_distances(Vector2::new(100.0, 50.0), -right(), 300.0, 200.0),
+                   (100.0, std::f32::INFINITY));
+        assert_eq!(edge_distances(Vector2::new(0.0, 0.0), Vector2::new(0.5, 0.25), 300.0, 200.0),
+                   (600.0, 800.0));
+        // already outside
+        assert_eq!(edge_distances(Vector2::new(310.0, -5.0), Vector2::new(0.5, -0.25), 300.0, 200.0),
+                   (0.0, 0.0));
+    }
+
+    #[test]
+    fn beam_inside_the_arena_is_one_segment() {
+        let segments = beam_segments(center(), right(), 50.0, 300.0, 300.0);
+        assert_eq!(segments, vec![(center(), Vector2::new(150.0, 100.0))]);
+    }
+
+    #[test]
+    fn beam_comes_in_from_the_other_side() {
+        let segments = beam_segments(Vector2::new(290.0, 50.0), right(), 30.0, 300.0, 200.0);
+        assert_eq!(segments, vec![
+            (Vector2::new(290.0, 50.0), Vector2::new(300.0, 50.0)),
+            (Vector2::new(0.0, 50.0), Vector2::new(20.0, 50.0)),
+        ]);
+    }
+
+    #[test]
+    fn beam_through_a_corner_wraps_both_axes() {
+        let direction = Vector2::new(1.0, 1.0).normalize();
+        let segments = beam_segments(Vector2::new(290.0, 190.0), direction, 20.0 * 2f32.sqrt(),
+                                     300.0, 200.0);
+        assert_eq!(segments.len(), 2);
+        assert!(close(segments[0].1, Vector2::new(300.0, 200.0)));
+        assert_eq!(segments[1].0, Vector2::new(0.0, 0.0));
+        assert!(close(segments[1].1, Vector2::new(10.0, 10.0)));
+    }
+
+    #[test]
+    fn long_beam_is_capped() {
+        let segments = beam_segments(center(), right(), 10_000.0, 300.0, 300.0);
+        assert_eq!(segments.len(), 8);
+        assert!(segments.iter().skip(1).all(|(start, _)| start.x == 0.0));
+    }
+}